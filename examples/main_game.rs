@@ -0,0 +1,14 @@
+//! The full game, as shipped by the `idaho` binary.
+//!
+//! cargo run --example main_game --release
+
+use bevy::prelude::*;
+
+use idaho::IdahoPlugins;
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        .add_plugins(IdahoPlugins)
+        .run();
+}
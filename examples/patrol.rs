@@ -0,0 +1,18 @@
+//! Loads the test fixture level so the patrolling Mob can be watched
+//! next to the player.
+//!
+//! cargo run --example patrol
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use idaho::{game_flow::GameFlowPlugin, IdahoPlugins};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        // before IdahoPlugins, so the debug feature's DebugPlugin reuses it
+        .add_plugins(RapierDebugRenderPlugin::default())
+        .add_plugins(IdahoPlugins.set(GameFlowPlugin::new("test_fixture.ldtk")))
+        .run();
+}
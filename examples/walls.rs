@@ -0,0 +1,21 @@
+//! Walks the player around the level with enemies left out, drawing the
+//! merged wall rectangles with Rapier's debug renderer.
+//!
+//! Characters, chests and doors stay, quests and sounds listen for the player
+//! using them.
+//!
+//! cargo run --example walls
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use idaho::{enemy::EnemyPlugin, IdahoPlugins};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+        // before IdahoPlugins, so the debug feature's DebugPlugin reuses it
+        .add_plugins(RapierDebugRenderPlugin::default())
+        .add_plugins(IdahoPlugins.build().disable::<EnemyPlugin>())
+        .run();
+}
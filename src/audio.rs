@@ -0,0 +1,438 @@
+use tracing::{event, Level};
+
+use bevy::{
+    audio::{DefaultSpatialScale, SpatialScale, Volume},
+    prelude::*,
+};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::dynamics::Velocity;
+
+use crate::{
+    camera::CameraController,
+    character::CharacterInteraction,
+    game_flow::update_level_selection,
+    health::Damage,
+    misc_objects::{OpenObject, Openable},
+    paths::sanitize,
+    player::Player,
+    terrain::CurrentTerrain,
+    timeline::{ActionStarted, Timeline},
+};
+
+/// Pixels per unit of distance used for positional sounds
+pub const AUDIO_PIXELS_PER_UNIT: f32 = 100.;
+
+/// Distance the player walks between footsteps, in pixels
+pub const STEP_DISTANCE: f32 = 14.;
+
+/// Damage below this is continuous, like hot rock or thirst, and makes no hit sound
+pub const HIT_SOUND_MIN_DAMAGE: f32 = 1.;
+
+/// Volumes from 0 to 1, the music and sound effect volumes are scaled by `master`.
+#[derive(Clone, PartialEq, Debug, Resource)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    /// Seconds for one level's music to fade into the next
+    pub crossfade_seconds: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master: 1.,
+            music: 0.6,
+            sfx: 0.8,
+            crossfade_seconds: 2.,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn music_volume(&self) -> f32 {
+        self.master * self.music
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.master * self.sfx
+    }
+}
+
+/// Sound effects not tied to terrain, relative to the asset folder.
+#[derive(Clone, PartialEq, Debug, Resource)]
+pub struct SoundEffects {
+    pub dialogue_blip: String,
+    pub hit: String,
+    pub chest: String,
+    pub door: String,
+}
+
+impl Default for SoundEffects {
+    fn default() -> Self {
+        SoundEffects {
+            dialogue_blip: "sounds/dialogue_blip.wav".to_string(),
+            hit: "sounds/hit.wav".to_string(),
+            chest: "sounds/chest.wav".to_string(),
+            door: "sounds/door.wav".to_string(),
+        }
+    }
+}
+
+/// Plays a sound effect once, at `position` in the world or without panning if `None`.
+#[derive(Clone, Debug, PartialEq, Event)]
+pub struct PlaySound {
+    pub path: String,
+    pub position: Option<Vec2>,
+}
+
+/// Music that should be playing, from the `music` field of the current LDtk level.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Resource)]
+pub struct CurrentMusic(pub Option<String>);
+
+/// Whether sounds are actually played.
+///
+/// Headless apps without Bevy's `AudioPlugin`, like tests, fall back to [`AudioBackend::Silent`],
+/// where sounds are only logged.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Resource)]
+pub enum AudioBackend {
+    Bevy,
+    Silent,
+}
+
+/// A looping music track, fading in or out.
+#[derive(Clone, Debug, PartialEq, Component)]
+pub struct MusicTrack {
+    pub path: String,
+    /// From 0 (silent) to 1 (full music volume)
+    pub fade: f32,
+    pub fading_out: bool,
+}
+
+/// Reads the `music` field of the selected level.
+pub fn select_level_music(
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut current_level: Local<Option<String>>,
+    mut current_music: ResMut<CurrentMusic>,
+) {
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+
+    let Some(level) = ldtk_project
+        .iter_raw_levels()
+        .find(|level| level_selection.is_match(&LevelIndices::default(), level))
+    else {
+        return;
+    };
+    if current_level.as_ref() == Some(&level.iid) {
+        return;
+    }
+    *current_level = Some(level.iid.clone());
+
+    let music = level
+        .get_string_field("music")
+        .ok()
+        .and_then(|music| match sanitize(music) {
+            Ok(path) => Some(path.to_string_lossy().into_owned()),
+            Err(e) => {
+                event!(Level::WARN, "Invalid music on level {} | {}", level.identifier, e);
+                None
+            }
+        });
+    if current_music.0 != music {
+        event!(Level::INFO, "Level {} music | {:?}", level.identifier, music);
+        current_music.0 = music;
+    }
+}
+
+pub fn play_footsteps(
+    time: Res<Time>,
+    mut walked: Local<f32>,
+    player_query: Query<(&GlobalTransform, &Velocity, &CurrentTerrain), With<Player>>,
+    mut sound_events: EventWriter<PlaySound>,
+) {
+    let Ok((transform, velocity, current_terrain)) = player_query.get_single() else {
+        return;
+    };
+
+    *walked += velocity.linvel.length() * time.delta_seconds();
+    if *walked < STEP_DISTANCE {
+        return;
+    }
+    *walked = 0.;
+
+    if let Some(footstep) = &current_terrain.0.footstep {
+        sound_events.send(PlaySound {
+            path: footstep.clone(),
+            position: Some(transform.translation().truncate()),
+        });
+    }
+}
+
+/// Blips when a character is talked to or a timeline action with text starts.
+pub fn play_dialogue_blips(
+    sounds: Res<SoundEffects>,
+    mut interaction_events: EventReader<CharacterInteraction>,
+    mut action_events: EventReader<ActionStarted>,
+    speaker_query: Query<(&GlobalTransform, Option<&Timeline>)>,
+    mut sound_events: EventWriter<PlaySound>,
+) {
+    let speakers = interaction_events
+        .read()
+        .map(|interaction| (interaction.character, None))
+        .chain(
+            action_events
+                .read()
+                .map(|started| (started.entity, Some(started.action.as_str()))),
+        );
+
+    for (speaker, action) in speakers {
+        let Ok((transform, timeline)) = speaker_query.get(speaker) else {
+            continue;
+        };
+        let speaks = match action {
+            Some(action) => timeline
+                .and_then(|timeline| timeline.action(action))
+                .is_some_and(|action| action.text.is_some()),
+            None => true,
+        };
+        if speaks {
+            sound_events.send(PlaySound {
+                path: sounds.dialogue_blip.clone(),
+                position: Some(transform.translation().truncate()),
+            });
+        }
+    }
+}
+
+pub fn play_hit_sounds(
+    sounds: Res<SoundEffects>,
+    mut damage_events: EventReader<Damage>,
+    target_query: Query<&GlobalTransform>,
+    mut sound_events: EventWriter<PlaySound>,
+) {
+    for damage in damage_events.read() {
+        if damage.amount < HIT_SOUND_MIN_DAMAGE {
+            continue;
+        }
+        sound_events.send(PlaySound {
+            path: sounds.hit.clone(),
+            position: target_query
+                .get(damage.target)
+                .ok()
+                .map(|transform| transform.translation().truncate()),
+        });
+    }
+}
+
+pub fn play_object_sounds(
+    sounds: Res<SoundEffects>,
+    mut open_events: EventReader<OpenObject>,
+    object_query: Query<&GlobalTransform>,
+    mut sound_events: EventWriter<PlaySound>,
+) {
+    for opened in open_events.read() {
+        let path = match opened.kind {
+            Openable::Chest => &sounds.chest,
+            Openable::Door => &sounds.door,
+        };
+        sound_events.send(PlaySound {
+            path: path.clone(),
+            position: object_query
+                .get(opened.entity)
+                .ok()
+                .map(|transform| transform.translation().truncate()),
+        });
+    }
+}
+
+/// Hears positional sounds from the camera.
+pub fn add_spatial_listener(mut commands: Commands, camera_query: Query<Entity, Added<CameraController>>) {
+    for camera in &camera_query {
+        // ears a couple of tiles apart
+        commands.entity(camera).insert(SpatialListener::new(32.));
+    }
+}
+
+pub fn play_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<AudioSettings>,
+    mut sound_events: EventReader<PlaySound>,
+) {
+    for sound in sound_events.read() {
+        let playback = PlaybackSettings::DESPAWN
+            .with_volume(Volume::new(settings.sfx_volume()))
+            .with_spatial(sound.position.is_some());
+        let translation = sound.position.unwrap_or_default().extend(0.);
+
+        commands.spawn((
+            AudioBundle {
+                source: asset_server.load(&sound.path),
+                settings: playback,
+            },
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+        ));
+    }
+}
+
+/// Fades out the playing track and fades in the level's music when it changes.
+pub fn crossfade_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    settings: Res<AudioSettings>,
+    current_music: Res<CurrentMusic>,
+    mut track_query: Query<(Entity, &mut MusicTrack, Option<&AudioSink>)>,
+) {
+    if current_music.is_changed() {
+        let mut already_playing = false;
+        for (_, mut track, _) in &mut track_query {
+            if current_music.0.as_ref() == Some(&track.path) {
+                track.fading_out = false;
+                already_playing = true;
+            } else {
+                track.fading_out = true;
+            }
+        }
+        if let (Some(path), false) = (&current_music.0, already_playing) {
+            commands.spawn((
+                AudioBundle {
+                    source: asset_server.load(path),
+                    settings: PlaybackSettings::LOOP.with_volume(Volume::new(0.)),
+                },
+                MusicTrack {
+                    path: path.clone(),
+                    fade: 0.,
+                    fading_out: false,
+                },
+            ));
+        }
+    }
+
+    let step = time.delta_seconds() / settings.crossfade_seconds.max(f32::EPSILON);
+    for (entity, mut track, sink) in &mut track_query {
+        track.fade = if track.fading_out {
+            track.fade - step
+        } else {
+            track.fade + step
+        }
+        .clamp(0., 1.);
+
+        if track.fading_out && track.fade == 0. {
+            commands.entity(entity).despawn();
+        } else if let Some(sink) = sink {
+            sink.set_volume(track.fade * settings.music_volume());
+        }
+    }
+}
+
+/// Drops sounds without playing them when there is no audio output.
+pub fn discard_sounds(mut sound_events: EventReader<PlaySound>) {
+    for sound in sound_events.read() {
+        event!(Level::DEBUG, "Silent sound | {}", sound.path);
+    }
+}
+
+/// Plugin for level music, sound effects and volumes.
+///
+/// Add it after Bevy's `AudioPlugin`; without it the plugin stays silent.
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        let backend = if app.world().contains_resource::<Assets<AudioSource>>() {
+            AudioBackend::Bevy
+        } else {
+            AudioBackend::Silent
+        };
+        event!(Level::INFO, "Audio backend | {:?}", backend);
+
+        app.insert_resource(backend)
+            .init_resource::<AudioSettings>()
+            .init_resource::<SoundEffects>()
+            .init_resource::<CurrentMusic>()
+            .add_event::<PlaySound>()
+            .add_systems(
+                Update,
+                (
+                    select_level_music.after(update_level_selection),
+                    play_footsteps,
+                    play_dialogue_blips,
+                    play_hit_sounds,
+                    play_object_sounds,
+                ),
+            );
+
+        match backend {
+            AudioBackend::Bevy => {
+                app.insert_resource(DefaultSpatialScale(SpatialScale::new_2d(
+                    1. / AUDIO_PIXELS_PER_UNIT,
+                )))
+                .add_systems(
+                    PostUpdate,
+                    (add_spatial_listener, play_sounds, crossfade_music),
+                );
+            }
+            AudioBackend::Silent => {
+                app.add_systems(PostUpdate, discard_sounds);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headless_apps_fall_back_to_silence() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GameAudioPlugin));
+        assert_eq!(*app.world().resource::<AudioBackend>(), AudioBackend::Silent);
+    }
+
+    #[test]
+    fn master_volume_scales_music_and_effects() {
+        let settings = AudioSettings {
+            master: 0.5,
+            music: 0.5,
+            sfx: 1.,
+            ..default()
+        };
+        assert_eq!(settings.music_volume(), 0.25);
+        assert_eq!(settings.sfx_volume(), 0.5);
+    }
+
+    #[test]
+    fn opening_a_chest_plays_its_sound() {
+        let mut app = App::new();
+        app.init_resource::<SoundEffects>()
+            .add_event::<OpenObject>()
+            .add_event::<PlaySound>()
+            .add_systems(Update, play_object_sounds);
+        let chest = app.world_mut().spawn(GlobalTransform::from_xyz(10., 20., 0.)).id();
+
+        app.world_mut().send_event(OpenObject {
+            entity: chest,
+            kind: Openable::Chest,
+        });
+        app.update();
+
+        let sounds: Vec<PlaySound> = app.world_mut().resource_mut::<Events<PlaySound>>().drain().collect();
+        assert_eq!(
+            sounds,
+            vec![PlaySound {
+                path: SoundEffects::default().chest,
+                position: Some(Vec2::new(10., 20.)),
+            }]
+        );
+    }
+}
@@ -1,71 +1,308 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::camera::{ScalingMode, Viewport},
+    window::PrimaryWindow,
+};
 use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::dynamics::Velocity;
 
 use crate::player::Player;
 
-const ASPECT_RATIO: f32 = 16. / 9.;
+#[derive(Clone, PartialEq, Debug, Resource)]
+pub struct CameraSettings {
+    /// Height of the world visible on screen, in pixels. The width follows the window.
+    pub view_height: f32,
+    /// Half size of the box around the camera target the player can move in without moving it
+    pub dead_zone: Vec2,
+    /// How quickly the camera catches up with its target, higher is snappier
+    pub smoothing: f32,
+    /// How far ahead of the player the camera looks in the direction of movement
+    pub look_ahead: f32,
+    /// How quickly the look-ahead follows changes of direction
+    pub look_ahead_smoothing: f32,
+    /// Duration of the pan when the selected level changes
+    pub transition_seconds: f32,
+    /// Forces this width / height ratio with black bars, or follows the window if unset
+    pub letterbox_aspect_ratio: Option<f32>,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            view_height: 176.,
+            dead_zone: Vec2::new(16., 12.),
+            smoothing: 8.,
+            look_ahead: 24.,
+            look_ahead_smoothing: 3.,
+            transition_seconds: 0.6,
+            letterbox_aspect_ratio: None,
+        }
+    }
+}
+
+/// World space bounds of the selected level, the camera never shows past them.
+#[derive(Copy, Clone, PartialEq, Debug, Default, Resource)]
+pub struct CurrentLevelBounds(pub Option<Rect>);
+
+/// A pan between two positions, eased in and out.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CameraTransition {
+    pub from: Vec2,
+    pub timer: Timer,
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Component)]
+pub struct CameraController {
+    /// Point the camera settles on, the dead zone is centered on it
+    pub target: Vec2,
+    pub look_ahead: Vec2,
+    pub transition: Option<CameraTransition>,
+    /// Set while a [`CameraScript`](crate::camera_script::CameraScript) moves the camera instead of the player
+    pub scripted: bool,
+}
+
+pub fn spawn_camera(mut commands: Commands, settings: Res<CameraSettings>) {
+    let mut camera = Camera2dBundle::default();
+    camera.projection.scaling_mode = ScalingMode::FixedVertical(settings.view_height);
+    commands.spawn((camera, CameraController::default()));
+}
+
+/// Keeps [`CurrentLevelBounds`] in sync with the [`LevelSelection`].
+///
+/// The LDtk project is only looked up when the selection changes or a level spawns.
+pub fn update_level_bounds(
+    level_selection: Res<LevelSelection>,
+    level_query: Query<(&LevelIid, &GlobalTransform)>,
+    added_levels: Query<(), Added<LevelIid>>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut bounds: ResMut<CurrentLevelBounds>,
+) {
+    if !level_selection.is_changed() && added_levels.is_empty() {
+        return;
+    }
+
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+
+    for (level_iid, level_transform) in &level_query {
+        let Some(level) = ldtk_project.get_raw_level_by_iid(&level_iid.to_string()) else {
+            continue;
+        };
+
+        if level_selection.is_match(&LevelIndices::default(), level) {
+            let min = level_transform.translation().truncate();
+            let new_bounds = Some(Rect::from_corners(
+                min,
+                min + Vec2::new(level.px_wid as f32, level.px_hei as f32),
+            ));
+            if bounds.0 != new_bounds {
+                bounds.0 = new_bounds;
+            }
+        }
+    }
+}
+
+/// Clamps a camera center so a view of `half_view` around it stays inside `bounds`.
+///
+/// Levels smaller than the view are centered.
+pub fn clamp_to_bounds(center: Vec2, half_view: Vec2, bounds: Rect) -> Vec2 {
+    let clamp_axis = |value: f32, half: f32, min: f32, max: f32| {
+        if max - min <= half * 2. {
+            (min + max) / 2.
+        } else {
+            value.clamp(min + half, max - half)
+        }
+    };
+
+    Vec2::new(
+        clamp_axis(center.x, half_view.x, bounds.min.x, bounds.max.x),
+        clamp_axis(center.y, half_view.y, bounds.min.y, bounds.max.y),
+    )
+}
+
+/// Moves `target` just enough for `focus` to be inside the dead zone around it.
+pub fn apply_dead_zone(target: Vec2, focus: Vec2, dead_zone: Vec2) -> Vec2 {
+    let offset = focus - target;
+    target + offset - offset.clamp(-dead_zone, dead_zone)
+}
 
 #[allow(clippy::type_complexity)]
-pub fn camera_fit_inside_current_level(
+pub fn follow_player(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    bounds: Res<CurrentLevelBounds>,
     mut camera_query: Query<
-        (
-            &mut bevy::render::camera::OrthographicProjection,
-            &mut Transform,
-        ),
+        (&mut Transform, &OrthographicProjection, &mut CameraController),
         Without<Player>,
     >,
-    player_query: Query<&Transform, With<Player>>,
-    level_query: Query<(&Transform, &LevelIid), (Without<OrthographicProjection>, Without<Player>)>,
-    ldtk_projects: Query<&Handle<LdtkProject>>,
-    level_selection: Res<LevelSelection>,
-    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    player_query: Query<(&Transform, Option<&Velocity>), With<Player>>,
 ) {
-    if let Ok(Transform {
-        translation: player_translation,
-        ..
-    }) = player_query.get_single()
+    let Ok((player_transform, player_velocity)) = player_query.get_single() else {
+        return;
+    };
+    let Ok((mut camera_transform, projection, mut controller)) = camera_query.get_single_mut() else {
+        return;
+    };
+    if controller.scripted {
+        return;
+    }
+    let dt = time.delta_seconds();
+
+    // look ahead in the direction of movement
+    let direction = player_velocity
+        .map(|velocity| velocity.linvel.normalize_or_zero())
+        .unwrap_or_default();
+    let look_ahead_blend = 1. - (-settings.look_ahead_smoothing * dt).exp();
+    controller.look_ahead = controller
+        .look_ahead
+        .lerp(direction * settings.look_ahead, look_ahead_blend);
+
+    let focus = player_transform.translation.truncate() + controller.look_ahead;
+    let mut target = apply_dead_zone(controller.target, focus, settings.dead_zone);
+
+    let half_view = projection.area.size() / 2.;
+    if let Some(bounds) = bounds.0 {
+        target = clamp_to_bounds(target, half_view, bounds);
+    }
+    controller.target = target;
+
+    if bounds.is_changed() && controller.transition.is_none() {
+        controller.transition = Some(CameraTransition {
+            from: camera_transform.translation.truncate(),
+            timer: Timer::from_seconds(settings.transition_seconds, TimerMode::Once),
+        });
+    }
+
+    let position = camera_transform.translation.truncate();
+    let new_position = match &mut controller.transition {
+        Some(transition) => {
+            transition.timer.tick(time.delta());
+            let t = transition.timer.fraction();
+            // smoothstep
+            let eased = t * t * (3. - 2. * t);
+            transition.from.lerp(target, eased)
+        }
+        None => position.lerp(target, 1. - (-settings.smoothing * dt).exp()),
+    };
+
+    if controller
+        .transition
+        .as_ref()
+        .is_some_and(|transition| transition.timer.finished())
     {
-        let player_translation = *player_translation;
-
-        let (mut orthographic_projection, mut camera_transform) = camera_query.single_mut();
-
-        for (level_transform, level_iid) in &level_query {
-            let ldtk_project = ldtk_project_assets
-                .get(ldtk_projects.single())
-                .expect("Project should be loaded if level has spawned");
-
-            let level = ldtk_project
-                .get_raw_level_by_iid(&level_iid.to_string())
-                .expect("Spawned level should exist in LDtk project");
-
-            if level_selection.is_match(&LevelIndices::default(), level) {
-                let level_ratio = level.px_wid as f32 / level.px_hei as f32;
-                orthographic_projection.viewport_origin = Vec2::ZERO;
-                if level_ratio > ASPECT_RATIO {
-                    // level is wider than the screen
-                    let height = (level.px_hei as f32 / 9.).round() * 9.;
-                    let width = height * ASPECT_RATIO;
-                    orthographic_projection.scaling_mode =
-                        bevy::render::camera::ScalingMode::Fixed { width, height };
-                    camera_transform.translation.x =
-                        (player_translation.x - level_transform.translation.x - width / 2.)
-                            .clamp(0., level.px_wid as f32 - width);
-                    camera_transform.translation.y = 0.;
-                } else {
-                    // level is taller than the screen
-                    let width = (level.px_wid as f32 / 16.).round() * 16.;
-                    let height = width / ASPECT_RATIO;
-                    orthographic_projection.scaling_mode =
-                        bevy::render::camera::ScalingMode::Fixed { width, height };
-                    camera_transform.translation.y =
-                        (player_translation.y - level_transform.translation.y - height / 2.)
-                            .clamp(0., level.px_hei as f32 - height);
-                    camera_transform.translation.x = 0.;
-                }
-
-                camera_transform.translation.x += level_transform.translation.x;
-                camera_transform.translation.y += level_transform.translation.y;
-            }
+        controller.transition = None;
+    }
+
+    camera_transform.translation.x = new_position.x;
+    camera_transform.translation.y = new_position.y;
+}
+
+/// Snaps the camera onto the player when it first appears, instead of panning from the origin.
+#[allow(clippy::type_complexity)]
+pub fn snap_to_new_player(
+    bounds: Res<CurrentLevelBounds>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection, &mut CameraController), Without<Player>>,
+    player_query: Query<&Transform, Added<Player>>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    for (mut camera_transform, projection, mut controller) in &mut camera_query {
+        let mut target = player_transform.translation.truncate();
+        if let Some(bounds) = bounds.0 {
+            target = clamp_to_bounds(target, projection.area.size() / 2., bounds);
         }
+        controller.target = target;
+        controller.transition = None;
+        camera_transform.translation.x = target.x;
+        camera_transform.translation.y = target.y;
+    }
+}
+
+/// Keeps the projection height and the letterbox viewport in sync with the settings and the window.
+pub fn fit_camera_to_window(
+    settings: Res<CameraSettings>,
+    window_query: Query<Ref<Window>, With<PrimaryWindow>>,
+    mut camera_query: Query<(&mut Camera, &mut OrthographicProjection), With<CameraController>>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    if !settings.is_changed() && !window.is_changed() {
+        return;
+    }
+
+    for (mut camera, mut projection) in &mut camera_query {
+        projection.scaling_mode = ScalingMode::FixedVertical(settings.view_height);
+
+        camera.viewport = settings.letterbox_aspect_ratio.map(|aspect_ratio| {
+            let window_size = window.physical_size();
+            let window_ratio = window_size.x as f32 / window_size.y.max(1) as f32;
+            let physical_size = if window_ratio > aspect_ratio {
+                // pillarbox
+                UVec2::new((window_size.y as f32 * aspect_ratio) as u32, window_size.y)
+            } else {
+                // letterbox
+                UVec2::new(window_size.x, (window_size.x as f32 / aspect_ratio) as u32)
+            };
+            Viewport {
+                physical_position: (window_size - physical_size) / 2,
+                physical_size,
+                ..default()
+            }
+        });
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraSettings>()
+            .init_resource::<CurrentLevelBounds>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(
+                Update,
+                (
+                    fit_camera_to_window,
+                    update_level_bounds,
+                    snap_to_new_player,
+                    follow_player,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_zone_only_moves_by_the_excess() {
+        let dead_zone = Vec2::new(16., 12.);
+        assert_eq!(apply_dead_zone(Vec2::ZERO, Vec2::new(10., -5.), dead_zone), Vec2::ZERO);
+        assert_eq!(
+            apply_dead_zone(Vec2::ZERO, Vec2::new(20., -30.), dead_zone),
+            Vec2::new(4., -18.)
+        );
+    }
+
+    #[test]
+    fn camera_stays_inside_level() {
+        let bounds = Rect::new(0., 0., 848., 176.);
+        let half_view = Vec2::new(156., 88.);
+
+        assert_eq!(clamp_to_bounds(Vec2::new(10., 10.), half_view, bounds), Vec2::new(156., 88.));
+        assert_eq!(clamp_to_bounds(Vec2::new(900., 88.), half_view, bounds), Vec2::new(692., 88.));
+        // narrower than the view: centered
+        let small = Rect::new(0., 0., 200., 100.);
+        assert_eq!(clamp_to_bounds(Vec2::new(0., 0.), half_view, small), Vec2::new(100., 50.));
     }
 }
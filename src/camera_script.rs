@@ -0,0 +1,305 @@
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    camera::{clamp_to_bounds, follow_player, CameraController, CurrentLevelBounds},
+    character::is_named,
+    player::{InputLock, Player},
+    timeline::{ActionStarted, Timeline},
+};
+
+/// Name the [`InputLock`] is held under while a script plays
+const INPUT_LOCK_REASON: &str = "camera_script";
+
+/// One step of a [`CameraScript`].
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraMove {
+    /// Pans to an entity, found by its LDtk identifier or `name` field, or to a point in the world
+    PanTo {
+        entity: Option<String>,
+        #[serde(default)]
+        x: f32,
+        #[serde(default)]
+        y: f32,
+        seconds: f32,
+    },
+    /// Zooms to a projection scale, below 1 is closer
+    Zoom { scale: f32, seconds: f32 },
+    Hold { seconds: f32 },
+    /// Shakes in place, fading out over the duration
+    Shake { intensity: f32, seconds: f32 },
+    /// Pans back to the player and resets the zoom
+    ReturnToPlayer { seconds: f32 },
+}
+
+impl CameraMove {
+    pub fn seconds(&self) -> f32 {
+        match self {
+            CameraMove::PanTo { seconds, .. }
+            | CameraMove::Zoom { seconds, .. }
+            | CameraMove::Hold { seconds }
+            | CameraMove::Shake { seconds, .. }
+            | CameraMove::ReturnToPlayer { seconds } => *seconds,
+        }
+    }
+}
+
+/// Camera moves played one after the other, while the player can't move.
+///
+/// ```xml
+/// <camera>
+///     <pan_to><entity>William Hickman</entity><seconds>1.5</seconds></pan_to>
+///     <hold><seconds>2</seconds></hold>
+///     <return_to_player><seconds>1</seconds></return_to_player>
+/// </camera>
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct CameraScript {
+    #[serde(rename = "$value", default)]
+    pub moves: Vec<CameraMove>,
+}
+
+/// Queues a script after the ones already playing.
+#[derive(Clone, Debug, PartialEq, Event)]
+pub struct PlayCameraScript(pub CameraScript);
+
+/// Ends every queued script and gives the camera back to the player.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Event)]
+pub struct SkipCameraScript;
+
+#[derive(Clone, Debug, PartialEq)]
+struct ActiveMove {
+    step: CameraMove,
+    from: Vec2,
+    to: Vec2,
+    from_zoom: f32,
+    to_zoom: f32,
+    timer: Timer,
+}
+
+/// Plays queued [`CameraScript`]s on the [`CameraController`] camera.
+#[derive(Clone, Debug, PartialEq, Resource)]
+pub struct CameraDirector {
+    queue: VecDeque<CameraMove>,
+    current: Option<ActiveMove>,
+    pub skip_key: KeyCode,
+    /// Multiplies the intensity of shakes, 0 turns them off
+    pub shake_scale: f32,
+}
+
+impl Default for CameraDirector {
+    fn default() -> Self {
+        CameraDirector {
+            queue: VecDeque::new(),
+            current: None,
+            skip_key: KeyCode::Space,
+            shake_scale: 1.,
+        }
+    }
+}
+
+impl CameraDirector {
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some() || !self.queue.is_empty()
+    }
+}
+
+/// Offset of a shake `elapsed` seconds in, fading out as `t` goes from 0 to 1.
+pub fn shake_offset(intensity: f32, elapsed: f32, t: f32) -> Vec2 {
+    Vec2::new((elapsed * 53.).sin(), (elapsed * 41.).cos()) * intensity * (1. - t)
+}
+
+/// Queues the camera moves of scripts sent directly or by starting timeline actions.
+pub fn queue_camera_scripts(
+    mut play_events: EventReader<PlayCameraScript>,
+    mut action_started: EventReader<ActionStarted>,
+    timeline_query: Query<&Timeline>,
+    mut director: ResMut<CameraDirector>,
+) {
+    for PlayCameraScript(script) in play_events.read() {
+        director.queue.extend(script.moves.iter().cloned());
+    }
+
+    for started in action_started.read() {
+        let script = timeline_query
+            .get(started.entity)
+            .ok()
+            .and_then(|timeline| timeline.action(&started.action))
+            .and_then(|action| action.camera.as_ref());
+        if let Some(script) = script {
+            director.queue.extend(script.moves.iter().cloned());
+        }
+    }
+}
+
+fn player_view(
+    player_query: &Query<&GlobalTransform, With<Player>>,
+    bounds: &CurrentLevelBounds,
+    half_view: Vec2,
+) -> Option<Vec2> {
+    let target = player_query.get_single().ok()?.translation().truncate();
+    Some(match bounds.0 {
+        Some(bounds) => clamp_to_bounds(target, half_view, bounds),
+        None => target,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_camera_script(
+    time: Res<Time>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut skip_events: EventReader<SkipCameraScript>,
+    mut director: ResMut<CameraDirector>,
+    mut input_lock: ResMut<InputLock>,
+    bounds: Res<CurrentLevelBounds>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection, &mut CameraController)>,
+    entity_query: Query<(&EntityInstance, &GlobalTransform)>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+) {
+    let Ok((mut camera_transform, mut projection, mut controller)) = camera_query.get_single_mut() else {
+        return;
+    };
+    let half_view = projection.area.size() / 2.;
+
+    let skipped = skip_events.read().count() > 0 || input.just_pressed(director.skip_key);
+    if controller.scripted && skipped {
+        event!(Level::INFO, "Camera script skipped");
+        director.queue.clear();
+        director.current = None;
+        projection.scale = 1.;
+        if let Some(target) = player_view(&player_query, &bounds, half_view) {
+            camera_transform.translation.x = target.x;
+            camera_transform.translation.y = target.y;
+        }
+    }
+
+    if director.current.is_none() {
+        if let Some(step) = director.queue.pop_front() {
+            let from = camera_transform.translation.truncate();
+            let (to, to_zoom) = match &step {
+                CameraMove::PanTo { entity: Some(name), .. } => {
+                    let found = entity_query
+                        .iter()
+                        .find(|(entity_instance, _)| is_named(entity_instance, name));
+                    let to = match found {
+                        Some((_, transform)) => transform.translation().truncate(),
+                        None => {
+                            event!(Level::WARN, "Camera can't pan to missing entity {}", name);
+                            from
+                        }
+                    };
+                    (to, projection.scale)
+                }
+                CameraMove::PanTo { x, y, .. } => (Vec2::new(*x, *y), projection.scale),
+                CameraMove::Zoom { scale, .. } => (from, *scale),
+                CameraMove::Hold { .. } | CameraMove::Shake { .. } => (from, projection.scale),
+                CameraMove::ReturnToPlayer { .. } => {
+                    (player_view(&player_query, &bounds, half_view).unwrap_or(from), 1.)
+                }
+            };
+            let to = match bounds.0 {
+                Some(bounds) => clamp_to_bounds(to, half_view, bounds),
+                None => to,
+            };
+
+            if !controller.scripted {
+                controller.scripted = true;
+                input_lock.lock(INPUT_LOCK_REASON);
+            }
+            director.current = Some(ActiveMove {
+                timer: Timer::from_seconds(step.seconds(), TimerMode::Once),
+                step,
+                from,
+                to,
+                from_zoom: projection.scale,
+                to_zoom,
+            });
+        }
+    }
+
+    let shake_scale = director.shake_scale;
+    let Some(active) = &mut director.current else {
+        if controller.scripted {
+            // hand the camera back from wherever the script left it
+            controller.scripted = false;
+            controller.target = camera_transform.translation.truncate();
+            input_lock.unlock(INPUT_LOCK_REASON);
+        }
+        return;
+    };
+
+    active.timer.tick(time.delta());
+    let t = active.timer.fraction();
+    // smoothstep
+    let eased = t * t * (3. - 2. * t);
+    let mut position = active.from.lerp(active.to, eased);
+    if let CameraMove::Shake { intensity, .. } = active.step {
+        position += shake_offset(intensity * shake_scale, active.timer.elapsed_secs(), t);
+    }
+    camera_transform.translation.x = position.x;
+    camera_transform.translation.y = position.y;
+    projection.scale = active.from_zoom + (active.to_zoom - active.from_zoom) * eased;
+
+    if active.timer.finished() {
+        director.current = None;
+    }
+}
+
+/// Plugin which lets timelines and other systems direct the camera.
+pub struct CameraScriptPlugin;
+
+impl Plugin for CameraScriptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraDirector>()
+            .add_event::<PlayCameraScript>()
+            .add_event::<SkipCameraScript>()
+            .add_systems(
+                Update,
+                (queue_camera_scripts, play_camera_script)
+                    .chain()
+                    .before(follow_player),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_parses_from_timeline_xml() {
+        let script: CameraScript = serde_xml_rs::from_str(
+            r#"<camera>
+                <pan_to><entity>William Hickman</entity><seconds>1.5</seconds></pan_to>
+                <zoom><scale>0.5</scale><seconds>1</seconds></zoom>
+                <shake><intensity>4</intensity><seconds>0.3</seconds></shake>
+                <return_to_player><seconds>1</seconds></return_to_player>
+            </camera>"#,
+        )
+        .unwrap();
+
+        assert_eq!(script.moves.len(), 4);
+        assert_eq!(
+            script.moves[0],
+            CameraMove::PanTo {
+                entity: Some("William Hickman".to_string()),
+                x: 0.,
+                y: 0.,
+                seconds: 1.5,
+            }
+        );
+        assert_eq!(script.moves[3].seconds(), 1.);
+    }
+
+    #[test]
+    fn shake_fades_out() {
+        assert_eq!(shake_offset(4., 0.2, 1.), Vec2::ZERO);
+        assert!(shake_offset(4., 0.2, 0.).length() <= 4. * std::f32::consts::SQRT_2);
+    }
+}
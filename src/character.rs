@@ -1,6 +1,8 @@
 use crate::{
-    colliders::ColliderBundle,
+    console::ConsoleAppExt,
     misc_objects::spawn_interaction_indicator,
+    player::{InputLock, Player},
+    settings::Settings,
 };
 
 use bevy::prelude::*;
@@ -9,12 +11,29 @@ use bevy_ecs_ldtk::prelude::*;
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Character;
 
+/// How close the player has to be to interact with a character
+pub const INTERACTION_DISTANCE: f32 = 45.0;
+
+/// Sent when the player interacts with a nearby character.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Event)]
+pub struct CharacterInteraction {
+    pub character: Entity,
+}
+
+/// Whether an LDtk entity is called `name`, by its `name` field or its identifier.
+pub fn is_named(entity_instance: &EntityInstance, name: &str) -> bool {
+    entity_instance.identifier == name
+        || entity_instance
+            .get_string_field("name")
+            .is_ok_and(|entity_name| entity_name == name)
+}
+
 #[derive(Clone, Default, Bundle)]
 pub struct CharacterBundle {
     pub sprite_sheet_bundle: LdtkSpriteSheetBundle,
-    pub collider_bundle: ColliderBundle,
     pub character: Character,
     pub worldly: Worldly,
+    pub entity_instance: EntityInstance,
 }
 
 impl LdtkEntity for CharacterBundle {
@@ -42,9 +61,9 @@ impl LdtkEntity for CharacterBundle {
                 texture_atlas: atlas_layout.into(),
                 ..default()
             },
-            collider_bundle: ColliderBundle::from(entity_instance),
             character: Character,
             worldly: Worldly::from_entity_info(entity_instance),
+            entity_instance: entity_instance.clone(),
         }
     }
 }
@@ -59,11 +78,40 @@ fn spawn_characters(
     }
 }
 
+/// Pressing E interacts with the closest character in reach.
+pub fn interact_with_characters(
+    input: Res<ButtonInput<KeyCode>>,
+    input_lock: Res<InputLock>,
+    settings: Res<Settings>,
+    player_query: Query<&Transform, With<Player>>,
+    character_query: Query<(Entity, &Transform), With<Character>>,
+    mut interactions: EventWriter<CharacterInteraction>,
+) {
+    if input_lock.is_locked() || !input.just_pressed(settings.controls.interact.0) {
+        return;
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    let closest = character_query
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation.distance(player_transform.translation)))
+        .filter(|(_, distance)| *distance <= INTERACTION_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    if let Some((character, _)) = closest {
+        interactions.send(CharacterInteraction { character });
+    }
+}
+
 pub struct CharacterPlugin;
 
 impl Plugin for CharacterPlugin {
     fn build(&self, app: &mut App) {
         app.register_ldtk_entity::<CharacterBundle>("Character")
-            .add_systems(Update, spawn_characters);
+            .add_console_spawnable::<CharacterBundle>("Character")
+            .add_event::<CharacterInteraction>()
+            .add_systems(Update, (spawn_characters, interact_with_characters));
     }
 }
\ No newline at end of file
@@ -0,0 +1,316 @@
+use std::time::Duration;
+
+use tracing::{event, Level};
+
+use bevy::{
+    app::{PluginGroupBuilder, ScheduleRunnerPlugin},
+    audio::AudioPlugin,
+    core::FrameCount,
+    prelude::*,
+    render::{
+        settings::{RenderCreation, WgpuSettings},
+        RenderPlugin,
+    },
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::render::RapierDebugRenderPlugin;
+
+use crate::{
+    game_flow::GameFlowPlugin,
+    logging::LogSettings,
+    paths::sanitize,
+    random::RandomPlugin,
+    save::{LoadGame, SaveSlot},
+    settings::Settings,
+    IdahoPlugins,
+};
+
+/// Printed by `--help`.
+pub const USAGE: &str = "\
+Usage: idaho [OPTIONS]
+
+Options:
+  --level <IID|IDENTIFIER>       Start in this level instead of the first one
+  --load <SLOT>                  Load this save slot on startup
+  --window-size <WIDTHxHEIGHT>   Window size for this run, like 1280x704
+  --headless <FRAMES>            Run FRAMES frames without a window or audio, then quit
+  --debug                        Show debug overlays, only colliders without the debug feature
+  --seed <SEED>                  Seed random numbers, to repeat a run
+  --project <FILE>               LDtk project to load, relative to the assets folder
+  --log-filter <FILTER>          RUST_LOG style log filter, like info,idaho=debug
+  --log-dir <DIR>                Folder for log files
+  --log-rotation <ROTATION>      minutely, hourly, daily or never
+  --log-format <FORMAT>          pretty, compact or json
+  --log-retention-days <DAYS>    Delete log files older than this on startup
+  --no-log-file                  Only log to the console
+  -h, --help                     Print this help";
+
+/// Options of the game binary, so designers can jump straight to the scene they work on.
+#[derive(Clone, Debug, Default, PartialEq, Resource)]
+pub struct LaunchOptions {
+    /// Iid or identifier of the level to start in
+    pub start_level: Option<String>,
+    pub load_slot: Option<u32>,
+    pub window_size: Option<UVec2>,
+    /// Frames to run without a window, renderer or audio before quitting
+    pub headless_frames: Option<u32>,
+    pub debug: bool,
+    pub seed: Option<u64>,
+    /// LDtk project relative to the asset folder
+    pub project: Option<String>,
+    pub log: LogSettings,
+    pub help: bool,
+}
+
+impl LaunchOptions {
+    /// Reads options from command line arguments, without the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = LaunchOptions::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            if options.log.apply_arg(&flag, &mut args)? {
+                continue;
+            }
+            let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", flag));
+            match flag.as_str() {
+                "--level" => options.start_level = Some(value()?),
+                "--load" => options.load_slot = Some(parse_number(&flag, &value()?)?),
+                "--window-size" => options.window_size = Some(parse_size(&value()?)?),
+                "--headless" => options.headless_frames = Some(parse_number(&flag, &value()?)?),
+                "--debug" => options.debug = true,
+                "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+                "--project" => {
+                    let project = value()?;
+                    sanitize(&project).map_err(|e| format!("Invalid project: {}", e))?;
+                    options.project = Some(project);
+                }
+                "-h" | "--help" => options.help = true,
+                _ => return Err(format!("Unknown option: {}\n\n{}", flag, USAGE)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Level to select first, by iid if `start_level` looks like one and by identifier otherwise.
+    pub fn start_level_selection(&self) -> Option<LevelSelection> {
+        self.start_level.as_ref().map(|level| {
+            if is_iid(level) {
+                LevelSelection::iid(level.clone())
+            } else {
+                LevelSelection::Identifier(level.clone())
+            }
+        })
+    }
+
+    /// Bevy's default plugins, logging as configured and without a window when headless.
+    pub fn default_plugins(&self) -> PluginGroupBuilder {
+        let plugins = DefaultPlugins
+            .set(ImagePlugin::default_nearest())
+            .set(self.log.log_plugin());
+
+        if self.headless_frames.is_some() {
+            plugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..default()
+                })
+                .set(RenderPlugin {
+                    render_creation: RenderCreation::Automatic(WgpuSettings {
+                        backends: None,
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .disable::<WinitPlugin>()
+                .disable::<AudioPlugin>()
+                .add(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1. / 60.)))
+        } else {
+            let mut window = Window::default();
+            if let Some(size) = self.window_size {
+                window.resolution.set(size.x as f32, size.y as f32);
+            }
+            plugins.set(WindowPlugin {
+                primary_window: Some(window),
+                ..default()
+            })
+        }
+    }
+
+    /// The game's plugins, starting from the chosen project, level and seed.
+    pub fn idaho_plugins(&self) -> PluginGroupBuilder {
+        let mut game_flow = GameFlowPlugin::default();
+        if let Some(project) = &self.project {
+            game_flow.project.clone_from(project);
+        }
+        if let Some(start_level) = self.start_level_selection() {
+            game_flow.start_level = start_level;
+        }
+
+        let plugins = IdahoPlugins
+            .set(game_flow)
+            .set(RandomPlugin { seed: self.seed });
+
+        #[cfg(feature = "debug")]
+        let plugins = if self.headless_frames.is_some() {
+            // the inspector needs a window
+            plugins.disable::<crate::debug::DebugPlugin>()
+        } else if self.debug {
+            plugins.set(crate::debug::DebugPlugin {
+                overlays: crate::debug::DebugOverlays::all(),
+            })
+        } else {
+            plugins
+        };
+        plugins
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number for {}: {}", flag, value))
+}
+
+/// Parses `WIDTHxHEIGHT`.
+fn parse_size(value: &str) -> Result<UVec2, String> {
+    let invalid = || format!("Invalid window size, expected WIDTHxHEIGHT: {}", value);
+    let (width, height) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let size = UVec2::new(
+        width.trim().parse().map_err(|_| invalid())?,
+        height.trim().parse().map_err(|_| invalid())?,
+    );
+    if size.min_element() == 0 {
+        return Err(invalid());
+    }
+    Ok(size)
+}
+
+/// Whether `level` is an LDtk iid, a UUID like `a315ac10-66b0-11ec-9cd7-99f223ad6ade`.
+fn is_iid(level: &str) -> bool {
+    let groups: Vec<&str> = level.split('-').collect();
+    groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12])
+        && groups
+            .iter()
+            .all(|group| group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Quits after the requested number of headless frames.
+pub fn exit_after_frames(
+    options: Res<LaunchOptions>,
+    frame_count: Res<FrameCount>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    if let Some(frames) = options.headless_frames {
+        if frame_count.0 >= frames {
+            event!(Level::INFO, "Ran {} headless frames, exiting", frame_count.0);
+            exit_events.send(AppExit::Success);
+        }
+    }
+}
+
+/// Loads the requested save slot once everything is set up.
+pub fn load_start_slot(options: Res<LaunchOptions>, mut load_events: EventWriter<LoadGame>) {
+    if options.load_slot.is_some() {
+        load_events.send(LoadGame);
+    }
+}
+
+/// Plugin applying the [`LaunchOptions`] which aren't plugin configuration.
+///
+/// Add it after [`IdahoPlugins`], configured from [`LaunchOptions::idaho_plugins`].
+pub struct LaunchPlugin(pub LaunchOptions);
+
+impl Plugin for LaunchPlugin {
+    fn build(&self, app: &mut App) {
+        let options = &self.0;
+
+        if let Some(size) = options.window_size {
+            // kept for this run, and in the settings file only if settings change in game
+            if let Some(mut settings) = app.world_mut().get_resource_mut::<Settings>() {
+                settings.video.width = size.x;
+                settings.video.height = size.y;
+                for problem in settings.validate() {
+                    event!(Level::WARN, "Window size | {}", problem);
+                }
+            }
+        }
+        if let Some(slot) = options.load_slot {
+            app.insert_resource(SaveSlot(slot));
+        }
+        if options.debug && cfg!(not(feature = "debug")) {
+            event!(
+                Level::WARN,
+                "Only Rapier's collider rendering is shown, the other debug overlays need the debug feature, like cargo run --features debug"
+            );
+            // the debug plugin adds it otherwise, and it needs a window to draw in
+            if options.headless_frames.is_none() && !app.is_plugin_added::<RapierDebugRenderPlugin>() {
+                app.add_plugins(RapierDebugRenderPlugin::default());
+            }
+        }
+        if let Some(frames) = options.headless_frames {
+            event!(Level::INFO, "Running {} frames headless", frames);
+        }
+
+        app.insert_resource(options.clone())
+            .add_systems(Startup, load_start_slot)
+            .add_systems(Last, exit_after_frames);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<LaunchOptions, String> {
+        LaunchOptions::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let options = parse(&[
+            "--level",
+            "River_Scene_One",
+            "--load",
+            "2",
+            "--window-size",
+            "640x352",
+            "--headless",
+            "120",
+            "--debug",
+            "--seed",
+            "7",
+            "--log-format",
+            "json",
+        ])
+        .unwrap();
+        assert_eq!(options.start_level.as_deref(), Some("River_Scene_One"));
+        assert_eq!(options.load_slot, Some(2));
+        assert_eq!(options.window_size, Some(UVec2::new(640, 352)));
+        assert_eq!(options.headless_frames, Some(120));
+        assert!(options.debug);
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.log.format, crate::logging::LogFormat::Json);
+
+        assert!(parse(&["--window-size", "640"]).is_err());
+        assert!(parse(&["--load"]).is_err());
+        assert!(parse(&["--project", "../elsewhere.ldtk"]).is_err());
+        assert!(parse(&["--fly"]).is_err());
+    }
+
+    #[test]
+    fn start_level_is_an_iid_or_identifier() {
+        let options = parse(&["--level", "a315ac10-66b0-11ec-9cd7-99f223ad6ade"]).unwrap();
+        assert_eq!(
+            options.start_level_selection(),
+            Some(LevelSelection::iid("a315ac10-66b0-11ec-9cd7-99f223ad6ade"))
+        );
+        let options = parse(&["--level", "River_Scene_One"]).unwrap();
+        assert_eq!(
+            options.start_level_selection(),
+            Some(LevelSelection::Identifier("River_Scene_One".to_string()))
+        );
+    }
+}
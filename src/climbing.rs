@@ -2,7 +2,13 @@ use bevy::{prelude::*, utils::HashSet};
 use bevy_ecs_ldtk::prelude::*;
 use bevy_rapier2d::prelude::*;
 
-use crate::colliders::SensorBundle;
+use crate::{
+    collision_layers::{apply_collision_layers, CollisionLayer, CollisionMatrix},
+    colliders::SensorBundle,
+};
+
+/// Vertical speed while attached to a climbable
+pub const CLIMB_SPEED: f32 = 60.;
 
 #[derive(Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Climber {
@@ -68,12 +74,68 @@ pub fn ignore_gravity_if_climbing(
     }
 }
 
+/// Lets climbers pass through walls while attached, so they can scale ledges.
+///
+/// Climbables should extend past the walls they cross,
+/// so that letting go at the top or bottom never leaves a climber inside a wall.
+pub fn pass_through_walls_while_climbing(
+    matrix: Res<CollisionMatrix>,
+    mut query: Query<(&Climber, &CollisionLayer, &mut CollisionGroups)>,
+) {
+    for (climber, layer, mut collision_groups) in &mut query {
+        let mut filters = matrix.filter(*layer);
+        if climber.climbing {
+            filters.remove(CollisionLayer::Wall.group());
+        }
+
+        if collision_groups.filters != filters {
+            collision_groups.filters = filters;
+        }
+    }
+}
+
+/// Plugin which lets climbers attach to ladders and cliffs.
+///
+/// Climbables are int cell values 2 (ladder) and 9 (cliff).
 pub struct ClimbingPlugin;
 
 impl Plugin for ClimbingPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, detect_climb_range)
             .add_systems(Update, ignore_gravity_if_climbing)
-            .register_ldtk_int_cell::<LadderBundle>(2);
+            .add_systems(
+                PostUpdate,
+                pass_through_walls_while_climbing
+                    .after(apply_collision_layers)
+                    .before(PhysicsSet::SyncBackend),
+            )
+            .register_ldtk_int_cell::<LadderBundle>(2) //ladder
+            .register_ldtk_int_cell::<LadderBundle>(9); //cliff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{player::Player, test_support::TestApp};
+
+    #[test]
+    fn climbing_only_allows_vertical_movement() {
+        let mut app = TestApp::new();
+        app.load_level();
+
+        // the fixture's ladder spans cells (3, 5) to (3, 8)
+        app.for_each::<&mut Transform, With<Player>>(|mut transform| {
+            transform.translation.x = 56.;
+            transform.translation.y = 48.;
+        });
+        app.advance(5).press(KeyCode::KeyW).advance(1);
+        assert!(app.single::<Climber, With<Player>>().climbing);
+        app.release(KeyCode::KeyW).advance(1);
+
+        let start = app.player_translation();
+        app.hold(KeyCode::KeyD, 20);
+        let end = app.player_translation();
+        assert!((end.x - start.x).abs() < 0.5, "moved sideways while climbing: {start} -> {end}");
     }
 }
@@ -0,0 +1,336 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::ColliderDisabled;
+
+use crate::{
+    menu::GameState,
+    pixel_perfect::PixelCanvas,
+    story::{StoryGate, StoryState},
+};
+
+pub const HOURS_PER_DAY: f32 = 24.;
+
+/// In-game date and time, saved with the rest of the game.
+#[derive(Copy, Clone, PartialEq, Debug, Resource, Serialize, Deserialize)]
+pub struct GameClock {
+    /// Days since the start of the game, starting at 1
+    pub day: u32,
+    /// Hour of the day, in `0..24`
+    pub hour: f32,
+    /// In-game hours passed during the last update
+    #[serde(skip)]
+    delta_hours: f32,
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        GameClock {
+            day: 1,
+            hour: 8.,
+            delta_hours: 0.,
+        }
+    }
+}
+
+impl GameClock {
+    /// Moves the clock forward, rolling over to the next day at midnight.
+    pub fn advance(&mut self, hours: f32) {
+        self.delta_hours = hours;
+        self.hour += hours;
+        while self.hour >= HOURS_PER_DAY {
+            self.hour -= HOURS_PER_DAY;
+            self.day += 1;
+            event!(Level::INFO, "Day {} begins", self.day);
+        }
+    }
+
+    /// In-game hours passed during the last update, zero while paused.
+    pub fn delta_hours(&self) -> f32 {
+        self.delta_hours
+    }
+
+    /// Whether the clock is between `from` and `to`, wrapping past midnight when `from > to`.
+    pub fn is_between(&self, from: f32, to: f32) -> bool {
+        if from <= to {
+            from <= self.hour && self.hour < to
+        } else {
+            self.hour >= from || self.hour < to
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Resource)]
+pub struct ClockSettings {
+    /// Real seconds for one in-game day
+    pub day_length_seconds: f32,
+    /// Hour night falls, for the `night` story flag
+    pub night_start: f32,
+    /// Hour night ends
+    pub night_end: f32,
+    /// Scene tint through the day as `(hour, color)`, sorted by hour
+    pub tint: Vec<(f32, Color)>,
+    pub tint_enabled: bool,
+}
+
+impl Default for ClockSettings {
+    fn default() -> Self {
+        let night = Color::srgb(0.35, 0.4, 0.65);
+        ClockSettings {
+            day_length_seconds: 20. * 60.,
+            night_start: 20.,
+            night_end: 5.,
+            tint: vec![
+                (0., night),
+                (4.5, night),
+                (6., Color::srgb(1.0, 0.8, 0.7)),
+                (8., Color::WHITE),
+                (17., Color::WHITE),
+                (19., Color::srgb(1.0, 0.7, 0.5)),
+                (20.5, night),
+                (24., night),
+            ],
+            tint_enabled: true,
+        }
+    }
+}
+
+impl ClockSettings {
+    /// Tint at `hour`, blending between the surrounding keys of [`ClockSettings::tint`].
+    pub fn tint_at(&self, hour: f32) -> Color {
+        let Some(&(_, first)) = self.tint.first() else {
+            return Color::WHITE;
+        };
+        let mut previous = (0., first);
+        for &(key_hour, color) in &self.tint {
+            if hour < key_hour {
+                let t = (hour - previous.0) / (key_hour - previous.0);
+                return mix(previous.1, color, t);
+            }
+            previous = (key_hour, color);
+        }
+        previous.1
+    }
+}
+
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    Srgba::from(from).mix(&Srgba::from(to), t).into()
+}
+
+/// Advances the clock with real time, standing still while the game is paused.
+pub fn advance_clock(
+    time: Res<Time>,
+    settings: Res<ClockSettings>,
+    game_state: Option<Res<State<GameState>>>,
+    mut clock: ResMut<GameClock>,
+) {
+    let paused = game_state.is_some_and(|state| *state.get() == GameState::Paused);
+    let hours = if paused {
+        0.
+    } else {
+        time.delta_seconds() / settings.day_length_seconds * HOURS_PER_DAY
+    };
+    clock.advance(hours);
+}
+
+/// Mirrors the clock into the `hour` and `day` story counters and the `night` flag.
+///
+/// This lets timeline actions and LDtk `condition` fields depend on the time of
+/// day, like an NPC with `hour >= 6 && hour < 18` or a mob that only shows up at `night`.
+pub fn sync_story_clock(clock: Res<GameClock>, settings: Res<ClockSettings>, mut story: ResMut<StoryState>) {
+    let hour = clock.hour.floor() as i64;
+    let day = clock.day as i64;
+    let night = clock.is_between(settings.night_start, settings.night_end);
+
+    if story.get("hour").is_none() || story.counter("hour") != hour {
+        story.set_counter("hour", hour);
+    }
+    if story.counter("day") != day {
+        story.set_counter("day", day);
+    }
+    if story.flag("night") != night {
+        if night {
+            story.set_flag("night");
+        } else {
+            story.clear_flag("night");
+        }
+    }
+}
+
+/// Tints the pixel canvas, which grades the whole scene including the level background.
+pub fn tint_scene(
+    clock: Res<GameClock>,
+    settings: Res<ClockSettings>,
+    mut canvas_query: Query<&mut Sprite, With<PixelCanvas>>,
+) {
+    let tint = if settings.tint_enabled {
+        settings.tint_at(clock.hour)
+    } else {
+        Color::WHITE
+    };
+    for mut sprite in &mut canvas_query {
+        if sprite.color != tint {
+            sprite.color = tint;
+        }
+    }
+}
+
+/// Hours of the day an LDtk entity is around, from its `active_hours` field.
+///
+/// Written like `6-21`, or `20-5` for an NPC or mob that only shows up at night.
+#[derive(Copy, Clone, PartialEq, Debug, Component)]
+pub struct ActiveHours {
+    pub from: f32,
+    pub to: f32,
+}
+
+impl FromStr for ActiveHours {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Expected hours like 6-21, got {}", s))?;
+        let hour = |hour: &str| match hour.trim().parse::<f32>() {
+            Ok(hour) if (0. ..=HOURS_PER_DAY).contains(&hour) => Ok(hour),
+            _ => Err(format!("Invalid hour {} in {}", hour.trim(), s)),
+        };
+        Ok(ActiveHours {
+            from: hour(from)?,
+            to: hour(to)?,
+        })
+    }
+}
+
+/// Adds [`ActiveHours`] to every LDtk entity with an `active_hours` field.
+pub fn add_active_hours(
+    mut commands: Commands,
+    entity_query: Query<(Entity, &EntityInstance), Added<EntityInstance>>,
+) {
+    for (entity, entity_instance) in &entity_query {
+        let Ok(source) = entity_instance.get_string_field("active_hours") else {
+            continue;
+        };
+        match source.parse::<ActiveHours>() {
+            Ok(hours) => {
+                commands.entity(entity).insert(hours);
+            }
+            Err(e) => {
+                event!(Level::WARN, "Invalid active hours on {} | {}", entity_instance.identifier, e);
+            }
+        }
+    }
+}
+
+/// Hides entities and disables their collider outside of their [`ActiveHours`].
+///
+/// A [`StoryGate`] on the same entity has to be open as well, which is why these
+/// entities are left alone by [`apply_story_gates`](crate::story::apply_story_gates).
+pub fn apply_active_hours(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    story: Res<StoryState>,
+    mut query: Query<(Entity, &ActiveHours, Option<&StoryGate>, &mut Visibility)>,
+) {
+    for (entity, hours, gate, mut visibility) in &mut query {
+        let active = clock.is_between(hours.from, hours.to)
+            && gate.map_or(true, |gate| gate.0.evaluate(&story));
+        let new_visibility = if active {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+            if active {
+                commands.entity(entity).remove::<ColliderDisabled>();
+            } else {
+                commands.entity(entity).insert(ColliderDisabled);
+            }
+        }
+    }
+}
+
+/// Plugin for the in-game clock and day/night cycle.
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameClock>()
+            .init_resource::<ClockSettings>()
+            .add_systems(
+                Update,
+                (
+                    advance_clock,
+                    sync_story_clock,
+                    tint_scene,
+                    add_active_hours,
+                    apply_active_hours,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{character::Character, story::StoryPlugin, test_support::TestApp};
+
+    #[test]
+    fn clock_rolls_over_at_midnight() {
+        let mut clock = GameClock {
+            hour: 23.,
+            ..default()
+        };
+        clock.advance(2.);
+        assert_eq!(clock.day, 2);
+        assert_eq!(clock.hour, 1.);
+        assert!(clock.is_between(20., 5.));
+        assert!(!clock.is_between(6., 18.));
+    }
+
+    #[test]
+    fn tint_blends_between_keys() {
+        let settings = ClockSettings {
+            tint: vec![(0., Color::BLACK), (12., Color::WHITE)],
+            ..default()
+        };
+        assert_eq!(settings.tint_at(6.), Color::srgb(0.5, 0.5, 0.5));
+        assert_eq!(settings.tint_at(18.), Color::WHITE);
+    }
+
+    #[test]
+    fn active_hours_parse() {
+        assert_eq!("6-21".parse(), Ok(ActiveHours { from: 6., to: 21. }));
+        assert_eq!("20 - 5".parse(), Ok(ActiveHours { from: 20., to: 5. }));
+        assert!("25-3".parse::<ActiveHours>().is_err());
+        assert!("noon".parse::<ActiveHours>().is_err());
+    }
+
+    #[test]
+    fn characters_keep_to_their_hours() {
+        let mut app = TestApp::new();
+        app.add_plugins((StoryPlugin, ClockPlugin));
+        app.load_level();
+
+        let world = app.world();
+        let character = world.query_filtered::<Entity, With<Character>>().single(world);
+        app.world().entity_mut(character).insert(ActiveHours { from: 6., to: 21. });
+
+        app.world().resource_mut::<GameClock>().hour = 22.;
+        app.advance(2);
+        assert_eq!(app.world().get::<Visibility>(character), Some(&Visibility::Hidden));
+        assert!(app.world().get::<ColliderDisabled>(character).is_some());
+
+        // the next morning
+        app.world().resource_mut::<GameClock>().advance(9.);
+        app.advance(2);
+        assert_eq!(app.world().get::<Visibility>(character), Some(&Visibility::Inherited));
+        assert!(app.world().get::<ColliderDisabled>(character).is_none());
+    }
+}
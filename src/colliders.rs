@@ -1,8 +1,14 @@
-use bevy::prelude::*;
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use bevy::{asset::LoadState, prelude::*};
+use bevy_common_assets::xml::XmlAssetPlugin;
 use bevy_ecs_ldtk::prelude::*;
 
 use bevy_rapier2d::prelude::*;
 
+use crate::collision_layers::CollisionLayer;
+
 #[derive(Clone, Default, Bundle, LdtkIntCell)]
 pub struct ColliderBundle {
     pub collider: Collider,
@@ -14,44 +20,308 @@ pub struct ColliderBundle {
     pub density: ColliderMassProperties,
 }
 
-impl From<&EntityInstance> for ColliderBundle {
-    fn from(entity_instance: &EntityInstance) -> ColliderBundle {
-        let rotation_constraints = LockedAxes::ROTATION_LOCKED;
+/// Shape of a collider preset.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColliderShape {
+    Cuboid,
+    /// Vertical capsule, rounded at the top and bottom
+    Capsule,
+    Ball,
+}
+
+impl std::str::FromStr for ColliderShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "cuboid" => Ok(ColliderShape::Cuboid),
+            "capsule" => Ok(ColliderShape::Capsule),
+            "ball" => Ok(ColliderShape::Ball),
+            _ => Err(format!("Invalid collider shape: {}", s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ColliderShape {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Rapier body type of a collider preset.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BodyType(pub RigidBody);
+
+impl std::str::FromStr for BodyType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dynamic" => Ok(BodyType(RigidBody::Dynamic)),
+            "fixed" => Ok(BodyType(RigidBody::Fixed)),
+            "kinematic" | "kinematic_velocity" => Ok(BodyType(RigidBody::KinematicVelocityBased)),
+            "kinematic_position" => Ok(BodyType(RigidBody::KinematicPositionBased)),
+            _ => Err(format!("Invalid body type: {}", s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BodyType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+fn default_friction() -> f32 {
+    0.5
+}
+
+fn default_density() -> f32 {
+    1.0
+}
+
+fn default_gravity_scale() -> f32 {
+    1.0
+}
+
+/// Physics description of an LDtk entity.
+///
+/// Sizes are full widths and heights in pixels.
+/// A ball uses `width` as its diameter, a capsule uses `width` as its diameter
+/// and `height` as its total height.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ColliderPreset {
+    /// LDtk entity identifier, or any name referenced by a `collider_preset` field
+    pub name: String,
+    pub shape: ColliderShape,
+    pub width: f32,
+    pub height: f32,
+    #[serde(default)]
+    pub offset_x: f32,
+    #[serde(default)]
+    pub offset_y: f32,
+    pub body: BodyType,
+    #[serde(default = "default_friction")]
+    pub friction: f32,
+    #[serde(default = "default_density")]
+    pub density: f32,
+    /// Only felt in side-view levels, kinematic and fixed bodies ignore it
+    #[serde(default = "default_gravity_scale")]
+    pub gravity_scale: f32,
+    /// Collides with everything when unset
+    pub layer: Option<CollisionLayer>,
+}
+
+impl ColliderPreset {
+    /// Builds a preset from `collider_*` fields on the entity.
+    ///
+    /// Returns `None` unless the entity has at least a `collider_shape` field.
+    /// Missing sizes default to the entity's size in LDtk.
+    pub fn from_fields(entity_instance: &EntityInstance) -> Option<Result<ColliderPreset, String>> {
+        let shape = entity_instance.get_string_field("collider_shape").ok()?;
+        let float = |name: &str, default: f32| {
+            entity_instance.get_float_field(name).unwrap_or(default)
+        };
 
-        match entity_instance.identifier.as_ref() {
-            "Player" => ColliderBundle {
-                collider: Collider::cuboid(6., 8.),
-                rigid_body: RigidBody::Dynamic,
-                friction: Friction {
-                    coefficient: 0.0,
-                    combine_rule: CoefficientCombineRule::Min,
+        let preset = (|| -> Result<ColliderPreset, String> {
+            Ok(ColliderPreset {
+                name: entity_instance.identifier.clone(),
+                shape: shape.parse()?,
+                width: float("collider_width", entity_instance.width as f32),
+                height: float("collider_height", entity_instance.height as f32),
+                offset_x: float("collider_offset_x", 0.),
+                offset_y: float("collider_offset_y", 0.),
+                body: match entity_instance.get_string_field("collider_body") {
+                    Ok(body) => body.parse()?,
+                    Err(_) => BodyType(RigidBody::Dynamic),
+                },
+                friction: float("collider_friction", default_friction()),
+                density: float("collider_density", default_density()),
+                gravity_scale: float("collider_gravity_scale", default_gravity_scale()),
+                layer: match entity_instance.get_string_field("collider_layer") {
+                    Ok(layer) => Some(layer.parse()?),
+                    Err(_) => None,
+                },
+            })
+        })();
+
+        Some(preset.and_then(|preset| preset.validate().map(|_| preset)))
+    }
+
+    /// Checks that the preset describes a usable collider.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width <= 0. || self.height <= 0. {
+            return Err(format!(
+                "Collider preset {} has a non-positive size {}x{}",
+                self.name, self.width, self.height
+            ));
+        }
+        if self.shape == ColliderShape::Capsule && self.height < self.width {
+            return Err(format!(
+                "Collider preset {} is a capsule shorter than it is wide",
+                self.name
+            ));
+        }
+        if self.density <= 0. {
+            return Err(format!("Collider preset {} has a non-positive density", self.name));
+        }
+        Ok(())
+    }
+
+    pub fn collider(&self) -> Collider {
+        let shape = match self.shape {
+            ColliderShape::Cuboid => Collider::cuboid(self.width / 2., self.height / 2.),
+            ColliderShape::Capsule => {
+                Collider::capsule_y((self.height - self.width) / 2., self.width / 2.)
+            }
+            ColliderShape::Ball => Collider::ball(self.width / 2.),
+        };
+
+        if self.offset_x == 0. && self.offset_y == 0. {
+            shape
+        } else {
+            Collider::compound(vec![(Vec2::new(self.offset_x, self.offset_y), 0., shape)])
+        }
+    }
+
+}
+
+impl From<&ColliderPreset> for ColliderBundle {
+    fn from(preset: &ColliderPreset) -> ColliderBundle {
+        ColliderBundle {
+            collider: preset.collider(),
+            rigid_body: preset.body.0,
+            rotation_constraints: LockedAxes::ROTATION_LOCKED,
+            gravity_scale: GravityScale(preset.gravity_scale),
+            friction: Friction {
+                coefficient: preset.friction,
+                combine_rule: if preset.friction == 0. {
+                    CoefficientCombineRule::Min
+                } else {
+                    CoefficientCombineRule::Average
                 },
-                rotation_constraints,
-                ..Default::default()
-            },
-            "Character" => ColliderBundle {
-                collider: Collider::cuboid(5., 5.),
-                rigid_body: RigidBody::KinematicVelocityBased,
-                rotation_constraints,
-                ..Default::default()
-            },
-            "Mob" => ColliderBundle {
-                collider: Collider::cuboid(5., 5.),
-                rigid_body: RigidBody::KinematicVelocityBased,
-                rotation_constraints,
-                ..Default::default()
-            },
-            "Chest" => ColliderBundle {
-                collider: Collider::cuboid(8., 8.),
-                rigid_body: RigidBody::Dynamic,
-                rotation_constraints,
-                gravity_scale: GravityScale(1.0),
-                friction: Friction::new(0.5),
-                density: ColliderMassProperties::Density(15.0),
-                ..Default::default()
             },
-            _ => ColliderBundle::default(),
+            density: ColliderMassProperties::Density(preset.density),
+            ..Default::default()
+        }
+    }
+}
+
+/// Collider presets keyed by name, loaded from a `*.colliders.xml` asset.
+#[derive(Asset, Clone, Debug, Default, PartialEq, Deserialize, TypePath)]
+pub struct ColliderPresets {
+    #[serde(rename = "preset", default)]
+    pub presets: Vec<ColliderPreset>,
+}
+
+impl ColliderPresets {
+    pub fn get(&self, name: &str) -> Option<&ColliderPreset> {
+        self.presets.iter().find(|preset| preset.name == name)
+    }
+}
+
+#[derive(Resource)]
+pub struct ColliderPresetsHandle(pub Handle<ColliderPresets>);
+
+/// Marks LDtk entities whose collider preset has been looked up, whether or not one was found.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct ColliderResolved;
+
+fn load_collider_presets(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ColliderPresetsHandle(
+        asset_server.load("physics/default.colliders.xml"),
+    ));
+}
+
+fn validate_collider_presets(
+    mut asset_events: EventReader<AssetEvent<ColliderPresets>>,
+    presets: Res<Assets<ColliderPresets>>,
+) {
+    for asset_event in asset_events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } = asset_event {
+            if let Some(presets) = presets.get(*id) {
+                for preset in &presets.presets {
+                    if let Err(e) = preset.validate() {
+                        event!(Level::WARN, "{}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Inserts a [`ColliderBundle`] on every spawned LDtk entity.
+///
+/// Presets come from the entity's own `collider_*` fields first,
+/// then from a `collider_preset` field naming a preset,
+/// then from the preset named after the entity identifier.
+/// Entities without a preset are left without physics and logged.
+pub fn apply_collider_presets(
+    mut commands: Commands,
+    entity_query: Query<(Entity, &EntityInstance), Without<ColliderResolved>>,
+    presets_handle: Res<ColliderPresetsHandle>,
+    preset_assets: Res<Assets<ColliderPresets>>,
+    asset_server: Res<AssetServer>,
+) {
+    if entity_query.is_empty() {
+        return;
+    }
+
+    let presets = preset_assets.get(&presets_handle.0);
+    if presets.is_none() && !matches!(asset_server.get_load_state(presets_handle.0.id()), Some(LoadState::Failed(_))) {
+        // wait for the presets to load
+        return;
+    }
+
+    for (entity, entity_instance) in &entity_query {
+        let preset = match ColliderPreset::from_fields(entity_instance) {
+            Some(Ok(preset)) => Some(preset),
+            Some(Err(e)) => {
+                event!(Level::WARN, "Invalid collider fields on {} | {}", entity_instance.identifier, e);
+                None
+            }
+            None => {
+                let name = entity_instance
+                    .get_string_field("collider_preset")
+                    .unwrap_or(&entity_instance.identifier);
+                presets.and_then(|presets| presets.get(name)).cloned()
+            }
+        };
+
+        let mut entity_commands = commands.entity(entity);
+        match preset {
+            Some(preset) => {
+                entity_commands.insert(ColliderBundle::from(&preset));
+                if let Some(layer) = preset.layer {
+                    entity_commands.insert(layer);
+                }
+            }
+            None => {
+                event!(Level::WARN, "No collider preset for entity {}", entity_instance.identifier);
+            }
         }
+        entity_commands.insert(ColliderResolved);
+    }
+}
+
+/// Plugin which gives LDtk entities their physics from collider presets.
+pub struct ColliderPlugin;
+
+impl Plugin for ColliderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(XmlAssetPlugin::<ColliderPresets>::new(&["colliders.xml"]))
+            .add_systems(Startup, load_collider_presets)
+            .add_systems(Update, (validate_collider_presets, apply_collider_presets));
     }
 }
 
@@ -61,22 +331,88 @@ pub struct SensorBundle {
     pub sensor: Sensor,
     pub active_events: ActiveEvents,
     pub rotation_constraints: LockedAxes,
+    pub collision_layer: CollisionLayer,
 }
 
 impl From<IntGridCell> for SensorBundle {
     fn from(int_grid_cell: IntGridCell) -> SensorBundle {
         let rotation_constraints = LockedAxes::ROTATION_LOCKED;
 
-        // ladder
-        if int_grid_cell.value == 2 {
+        // ladder or cliff
+        if int_grid_cell.value == 2 || int_grid_cell.value == 9 {
             SensorBundle {
                 collider: Collider::cuboid(8., 8.),
                 sensor: Sensor,
                 rotation_constraints,
                 active_events: ActiveEvents::COLLISION_EVENTS,
+                collision_layer: CollisionLayer::Trigger,
             }
         } else {
             SensorBundle::default()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_parse_from_xml() {
+        let presets: ColliderPresets = serde_xml_rs::from_str(
+            r#"<colliders>
+                <preset>
+                    <name>Player</name>
+                    <shape>capsule</shape>
+                    <width>12</width>
+                    <height>16</height>
+                    <body>dynamic</body>
+                    <friction>0</friction>
+                    <layer>player</layer>
+                </preset>
+                <preset>
+                    <name>Mob</name>
+                    <shape>Cuboid</shape>
+                    <width>10</width>
+                    <height>10</height>
+                    <body>kinematic</body>
+                </preset>
+            </colliders>"#,
+        )
+        .unwrap();
+
+        let player = presets.get("Player").unwrap();
+        assert_eq!(player.shape, ColliderShape::Capsule);
+        assert_eq!(player.body, BodyType(RigidBody::Dynamic));
+        assert_eq!(player.friction, 0.);
+        assert_eq!(player.density, 1.);
+        assert_eq!(player.gravity_scale, 1.);
+        assert_eq!(player.layer, Some(CollisionLayer::Player));
+        assert!(player.validate().is_ok());
+
+        let mob = presets.get("Mob").unwrap();
+        assert_eq!(mob.body, BodyType(RigidBody::KinematicVelocityBased));
+        assert_eq!(mob.friction, 0.5);
+        assert_eq!(mob.layer, None);
+        assert!(presets.get("Chest").is_none());
+    }
+
+    #[test]
+    fn invalid_presets_are_rejected() {
+        let preset: ColliderPresets = serde_xml_rs::from_str(
+            r#"<colliders>
+                <preset>
+                    <name>Flat</name>
+                    <shape>cuboid</shape>
+                    <width>10</width>
+                    <height>0</height>
+                    <body>fixed</body>
+                </preset>
+            </colliders>"#,
+        )
+        .unwrap();
+        assert!(preset.presets[0].validate().is_err());
+
+        assert!("triangle".parse::<ColliderShape>().is_err());
+    }
+}
@@ -0,0 +1,234 @@
+use serde::Deserialize;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier2d::prelude::*;
+
+/// Named Rapier collision group.
+///
+/// Adding this component to a collider gives it the [`CollisionGroups`] and
+/// [`SolverGroups`] described by the [`CollisionMatrix`].
+/// Defaults to [`CollisionLayer::Trigger`], the layer of int grid sensors.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Component)]
+pub enum CollisionLayer {
+    Player,
+    Npc,
+    Enemy,
+    Wall,
+    Water,
+    Pickup,
+    #[default]
+    Trigger,
+    Projectile,
+}
+
+impl CollisionLayer {
+    pub const ALL: [CollisionLayer; 8] = [
+        CollisionLayer::Player,
+        CollisionLayer::Npc,
+        CollisionLayer::Enemy,
+        CollisionLayer::Wall,
+        CollisionLayer::Water,
+        CollisionLayer::Pickup,
+        CollisionLayer::Trigger,
+        CollisionLayer::Projectile,
+    ];
+
+    /// The Rapier group bit backing this layer.
+    pub fn group(self) -> Group {
+        match self {
+            CollisionLayer::Player => Group::GROUP_1,
+            CollisionLayer::Npc => Group::GROUP_2,
+            CollisionLayer::Enemy => Group::GROUP_3,
+            CollisionLayer::Wall => Group::GROUP_4,
+            CollisionLayer::Water => Group::GROUP_5,
+            CollisionLayer::Pickup => Group::GROUP_6,
+            CollisionLayer::Trigger => Group::GROUP_7,
+            CollisionLayer::Projectile => Group::GROUP_8,
+        }
+    }
+
+    /// Union of the group bits of several layers.
+    pub fn groups(layers: &[CollisionLayer]) -> Group {
+        layers
+            .iter()
+            .fold(Group::NONE, |groups, layer| groups | layer.group())
+    }
+}
+
+impl std::str::FromStr for CollisionLayer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "player" => Ok(CollisionLayer::Player),
+            "npc" => Ok(CollisionLayer::Npc),
+            "enemy" => Ok(CollisionLayer::Enemy),
+            "wall" => Ok(CollisionLayer::Wall),
+            "water" => Ok(CollisionLayer::Water),
+            "pickup" => Ok(CollisionLayer::Pickup),
+            "trigger" => Ok(CollisionLayer::Trigger),
+            "projectile" => Ok(CollisionLayer::Projectile),
+            _ => Err(format!("Invalid collision layer: {}", s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CollisionLayer {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Which collision layers interact with each other.
+///
+/// Interactions are always symmetric: Rapier only reports a contact
+/// when each collider's filter contains the other's membership.
+#[derive(Clone, Debug, PartialEq, Resource)]
+pub struct CollisionMatrix {
+    interactions: HashMap<CollisionLayer, Group>,
+}
+
+impl CollisionMatrix {
+    /// A matrix where no layer interacts with any other.
+    pub fn empty() -> Self {
+        CollisionMatrix {
+            interactions: CollisionLayer::ALL
+                .iter()
+                .map(|layer| (*layer, Group::NONE))
+                .collect(),
+        }
+    }
+
+    /// Makes `a` and `b` interact, or stop interacting.
+    pub fn set(&mut self, a: CollisionLayer, b: CollisionLayer, interacts: bool) -> &mut Self {
+        for (layer, other) in [(a, b), (b, a)] {
+            let filter = self.interactions.entry(layer).or_insert(Group::NONE);
+            filter.set(other.group(), interacts);
+        }
+        self
+    }
+
+    pub fn interacts(&self, a: CollisionLayer, b: CollisionLayer) -> bool {
+        self.filter(a).contains(b.group())
+    }
+
+    /// Group bits of every layer `layer` interacts with.
+    pub fn filter(&self, layer: CollisionLayer) -> Group {
+        self.interactions.get(&layer).copied().unwrap_or(Group::NONE)
+    }
+
+    pub fn collision_groups(&self, layer: CollisionLayer) -> CollisionGroups {
+        CollisionGroups::new(layer.group(), self.filter(layer))
+    }
+
+    pub fn solver_groups(&self, layer: CollisionLayer) -> SolverGroups {
+        SolverGroups::new(layer.group(), self.filter(layer))
+    }
+
+    /// Scene query filter that only hits colliders `layer` interacts with.
+    pub fn query_filter(&self, layer: CollisionLayer) -> QueryFilter<'static> {
+        QueryFilter::new().groups(self.collision_groups(layer))
+    }
+}
+
+impl Default for CollisionMatrix {
+    fn default() -> Self {
+        use CollisionLayer::*;
+
+        let mut matrix = CollisionMatrix::empty();
+        for (a, others) in [
+            (Player, &[Npc, Enemy, Wall, Water, Pickup, Trigger, Projectile][..]),
+            (Npc, &[Npc, Enemy, Wall, Water, Projectile][..]),
+            (Enemy, &[Enemy, Wall, Water, Projectile][..]),
+            (Wall, &[Pickup, Projectile][..]),
+            (Pickup, &[Pickup][..]),
+        ] {
+            for b in others {
+                matrix.set(a, *b, true);
+            }
+        }
+        matrix
+    }
+}
+
+/// Scene query filter that only hits colliders on the given layers.
+pub fn layers_filter(layers: &[CollisionLayer]) -> QueryFilter<'static> {
+    QueryFilter::new().groups(CollisionGroups::new(Group::ALL, CollisionLayer::groups(layers)))
+}
+
+/// Casts a ray that only hits colliders on the given layers.
+///
+/// Returns the entity hit and the distance along `direction`, in units of its length.
+pub fn cast_ray_on_layers(
+    rapier_context: &RapierContext,
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    layers: &[CollisionLayer],
+) -> Option<(Entity, f32)> {
+    rapier_context.cast_ray(origin, direction, max_distance, true, layers_filter(layers))
+}
+
+/// Gives every [`CollisionLayer`] its groups, and refreshes them all when the
+/// [`CollisionMatrix`] changes.
+pub fn apply_collision_layers(
+    mut commands: Commands,
+    matrix: Res<CollisionMatrix>,
+    layer_query: Query<(Entity, Ref<CollisionLayer>)>,
+) {
+    for (entity, layer) in &layer_query {
+        if matrix.is_changed() || layer.is_changed() {
+            commands.entity(entity).insert((
+                matrix.collision_groups(*layer),
+                matrix.solver_groups(*layer),
+            ));
+        }
+    }
+}
+
+/// Plugin which turns [`CollisionLayer`] components into Rapier groups.
+pub struct CollisionLayerPlugin;
+
+impl Plugin for CollisionLayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CollisionMatrix>().add_systems(
+            PostUpdate,
+            apply_collision_layers.before(PhysicsSet::SyncBackend),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_matrix_is_symmetric() {
+        let matrix = CollisionMatrix::default();
+        for a in CollisionLayer::ALL {
+            for b in CollisionLayer::ALL {
+                assert_eq!(matrix.interacts(a, b), matrix.interacts(b, a), "{a:?} / {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn triggers_only_see_the_player() {
+        let matrix = CollisionMatrix::default();
+        for layer in CollisionLayer::ALL {
+            assert_eq!(
+                matrix.interacts(CollisionLayer::Trigger, layer),
+                layer == CollisionLayer::Player
+            );
+        }
+
+        let mut matrix = matrix;
+        matrix.set(CollisionLayer::Trigger, CollisionLayer::Npc, true);
+        assert!(matrix.interacts(CollisionLayer::Npc, CollisionLayer::Trigger));
+    }
+}
@@ -0,0 +1,614 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use tracing::{event, Level};
+
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState, InputSystem,
+    },
+    prelude::*,
+};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    player::{InputLock, Player},
+    settings::Settings,
+};
+
+/// Name the [`InputLock`] is held under while the console is open
+const INPUT_LOCK_REASON: &str = "console";
+
+/// Lines of output kept on screen
+pub const CONSOLE_LINES: usize = 12;
+
+/// Commands kept in the history
+pub const HISTORY_LENGTH: usize = 50;
+
+/// A command typed into the developer console, like `tp 100 200`.
+///
+/// Plugins register their own with [`ConsoleAppExt::add_console_command`].
+pub trait ConsoleCommand: Send + Sync + 'static {
+    /// First word of the command
+    fn name(&self) -> &'static str;
+
+    /// Arguments shown by `help`, like `<x> <y>`
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    /// Candidates for the argument at `args.len() - 1`, filtered by the console as it is typed.
+    fn complete(&self, _world: &mut World, _args: &[&str]) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Runs the command, returning what to print.
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String>;
+}
+
+/// Every registered [`ConsoleCommand`], by name.
+#[derive(Clone, Default, Resource)]
+pub struct ConsoleCommands(BTreeMap<&'static str, Arc<dyn ConsoleCommand>>);
+
+impl ConsoleCommands {
+    pub fn add(&mut self, command: impl ConsoleCommand) {
+        if self.0.insert(command.name(), Arc::new(command)).is_some() {
+            event!(Level::WARN, "Console command registered twice, keeping the last one");
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ConsoleCommand>> {
+        self.0.get(name).cloned()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.0.keys().copied()
+    }
+
+    /// Runs a line like `give Water 2` against the world.
+    pub fn run(&self, world: &mut World, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, args)) = words.split_first() else {
+            return Ok(String::new());
+        };
+        let command = self
+            .get(name)
+            .ok_or_else(|| format!("Unknown command: {}", name))?;
+        command.run(world, args)
+    }
+}
+
+/// Registration of console commands and spawnable entities.
+pub trait ConsoleAppExt {
+    fn add_console_command(&mut self, command: impl ConsoleCommand) -> &mut Self;
+
+    /// Lets `spawn <identifier>` create LDtk entities bundled as `B`.
+    fn add_console_spawnable<B: LdtkEntity + Bundle>(&mut self, identifier: &str) -> &mut Self;
+}
+
+impl ConsoleAppExt for App {
+    fn add_console_command(&mut self, command: impl ConsoleCommand) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .add(command);
+        self
+    }
+
+    fn add_console_spawnable<B: LdtkEntity + Bundle>(&mut self, identifier: &str) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(Spawnables::default)
+            .0
+            .insert(identifier.to_string(), spawn_ldtk_entity::<B>);
+        self
+    }
+}
+
+/// Text typed into the console, its output and history.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub output: Vec<String>,
+    pub history: Vec<String>,
+    /// Position in `history` while browsing it with the arrow keys
+    pub history_index: Option<usize>,
+    /// Set by Tab, completed once the world is available
+    pub completing: bool,
+}
+
+impl Console {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.output.extend(line.into().lines().map(String::from));
+        let excess = self.output.len().saturating_sub(CONSOLE_LINES);
+        self.output.drain(..excess);
+    }
+
+    /// Takes the input line, remembering it in the history.
+    pub fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        self.history_index = None;
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            let excess = self.history.len().saturating_sub(HISTORY_LENGTH);
+            self.history.drain(..excess);
+        }
+        line
+    }
+
+    /// Steps through the history, `back` towards older commands.
+    pub fn browse_history(&mut self, back: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() - 1;
+        self.history_index = match (self.history_index, back) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index < last => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.input = self
+            .history_index
+            .map(|index| self.history[index].clone())
+            .unwrap_or_default();
+    }
+}
+
+/// Runs a console command, as if typed.
+#[derive(Clone, Debug, Eq, PartialEq, Event)]
+pub struct RunConsoleCommand(pub String);
+
+/// Completes the last word of `input` from `candidates`.
+///
+/// Returns the new input, extended to the longest common prefix of the matches, and the matches.
+pub fn complete(input: &str, candidates: &[String]) -> (String, Vec<String>) {
+    let start = input.rfind(' ').map_or(0, |space| space + 1);
+    let (head, word) = input.split_at(start);
+    let matches: Vec<String> = candidates
+        .iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .cloned()
+        .collect();
+
+    let Some(first) = matches.first() else {
+        return (input.to_string(), matches);
+    };
+    let mut prefix = first.as_str();
+    for other in &matches[1..] {
+        let common = prefix
+            .char_indices()
+            .zip(other.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(other.len()), |((index, _), _)| index);
+        prefix = &prefix[..common];
+    }
+
+    let mut completed = format!("{}{}", head, prefix);
+    if matches.len() == 1 {
+        completed.push(' ');
+    }
+    (completed, matches)
+}
+
+type SpawnFn = fn(&mut World, &str, Vec2) -> Result<Entity, String>;
+
+/// LDtk entities `spawn` can create, by identifier.
+#[derive(Clone, Default, Resource)]
+pub struct Spawnables(BTreeMap<String, SpawnFn>);
+
+/// Spawns a copy of the first `identifier` entity found in the LDtk project at `position`,
+/// inside the selected level.
+pub fn spawn_ldtk_entity<B: LdtkEntity + Bundle>(
+    world: &mut World,
+    identifier: &str,
+    position: Vec2,
+) -> Result<Entity, String> {
+    let ldtk_project = current_project(world).ok_or("The LDtk project isn't loaded")?;
+    let (entity_instance, layer_instance) = ldtk_project
+        .iter_raw_levels()
+        .flat_map(|level| level.layer_instances.iter().flatten())
+        .flat_map(|layer| layer.entity_instances.iter().map(move |entity| (entity, layer)))
+        .find(|(entity, _)| entity.identifier == identifier)
+        .map(|(entity, layer)| (entity.clone(), layer.clone()))
+        .ok_or_else(|| format!("No {} in the LDtk project to copy", identifier))?;
+    let tileset_uid = entity_instance.tile.as_ref().map(|tile| tile.tileset_uid);
+    let tileset = tileset_uid.and_then(|uid| ldtk_project.tileset_map().get(&uid).cloned());
+    let tileset_definition = tileset_uid.and_then(|uid| {
+        ldtk_project
+            .json_data()
+            .defs
+            .tilesets
+            .iter()
+            .find(|definition| definition.uid == uid)
+            .cloned()
+    });
+
+    let asset_server = world.resource::<AssetServer>().clone();
+    let bundle = world.resource_scope(|_, mut texture_atlases: Mut<Assets<TextureAtlasLayout>>| {
+        B::bundle_entity(
+            &entity_instance,
+            &layer_instance,
+            tileset.as_ref(),
+            tileset_definition.as_ref(),
+            &asset_server,
+            &mut texture_atlases,
+        )
+    });
+
+    let level_selection = world.resource::<LevelSelection>().clone();
+    let level = world
+        .query::<(Entity, &LevelIid, &GlobalTransform)>()
+        .iter(world)
+        .find(|(_, level_iid, _)| {
+            ldtk_project
+                .get_raw_level_by_iid(&level_iid.to_string())
+                .is_some_and(|level| level_selection.is_match(&LevelIndices::default(), level))
+        })
+        .map(|(entity, _, transform)| (entity, transform.translation().truncate()));
+    let (parent, offset) = level.map_or((None, Vec2::ZERO), |(entity, origin)| (Some(entity), origin));
+
+    // inserted after the bundle, which may already hold the entity instance
+    let mut entity = world.spawn(bundle);
+    entity.insert(entity_instance);
+    let mut transform = entity.get::<Transform>().copied().unwrap_or_default();
+    transform.translation = (position - offset).extend(transform.translation.z);
+    entity.insert(transform);
+    let id = entity.id();
+    if let Some(parent) = parent {
+        world.entity_mut(parent).add_child(id);
+    }
+    Ok(id)
+}
+
+/// The loaded LDtk project, if any.
+pub fn current_project(world: &mut World) -> Option<LdtkProject> {
+    let handle = world
+        .query::<&Handle<LdtkProject>>()
+        .get_single(world)
+        .ok()?
+        .clone();
+    world.resource::<Assets<LdtkProject>>().get(&handle).cloned()
+}
+
+/// The player entity, for commands acting on it.
+pub fn player_entity(world: &mut World) -> Result<Entity, String> {
+    world
+        .query_filtered::<Entity, With<Player>>()
+        .get_single(world)
+        .map_err(|_| "There is no player".to_string())
+}
+
+/// Parses a command argument, naming it in the error.
+pub fn parse_arg<T: std::str::FromStr>(args: &[&str], index: usize, name: &str) -> Result<T, String> {
+    let arg = args.get(index).ok_or_else(|| format!("Missing {}", name))?;
+    arg.parse().map_err(|_| format!("Invalid {}: {}", name, arg))
+}
+
+/// `help`: lists every command.
+pub struct HelpCommand;
+
+impl ConsoleCommand for HelpCommand {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn run(&self, world: &mut World, _: &[&str]) -> Result<String, String> {
+        let commands = world.resource::<ConsoleCommands>();
+        Ok(commands
+            .0
+            .values()
+            .map(|command| format!("{} {}", command.name(), command.usage()).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+/// `clear`: empties the console output.
+pub struct ClearCommand;
+
+impl ConsoleCommand for ClearCommand {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn run(&self, world: &mut World, _: &[&str]) -> Result<String, String> {
+        world.resource_mut::<Console>().output.clear();
+        Ok(String::new())
+    }
+}
+
+/// `timescale <scale>`: speeds up or slows down game time, 1 being normal.
+pub struct TimescaleCommand;
+
+impl ConsoleCommand for TimescaleCommand {
+    fn name(&self) -> &'static str {
+        "timescale"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<scale>"
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        let scale: f32 = parse_arg(args, 0, "scale")?;
+        if !(0. ..=10.).contains(&scale) {
+            return Err(format!("Scale should be between 0 and 10, was {}", scale));
+        }
+        world.resource_mut::<Time<Virtual>>().set_relative_speed(scale);
+        Ok(format!("Time scale {}", scale))
+    }
+}
+
+/// `spawn <identifier>`: copies an LDtk entity next to the player.
+pub struct SpawnCommand;
+
+impl ConsoleCommand for SpawnCommand {
+    fn name(&self) -> &'static str {
+        "spawn"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<entity>"
+    }
+
+    fn complete(&self, world: &mut World, _: &[&str]) -> Vec<String> {
+        world
+            .get_resource::<Spawnables>()
+            .map(|spawnables| spawnables.0.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        let identifier = *args.first().ok_or("Missing entity")?;
+        let spawn = world
+            .get_resource::<Spawnables>()
+            .and_then(|spawnables| spawnables.0.get(identifier).copied())
+            .ok_or_else(|| format!("Can't spawn {}", identifier))?;
+
+        let player = player_entity(world)?;
+        let player_position = world
+            .get::<GlobalTransform>(player)
+            .map(|transform| transform.translation().truncate())
+            .unwrap_or_default();
+        let entity = spawn(world, identifier, player_position + Vec2::new(24., 0.))?;
+        Ok(format!("Spawned {} {:?}", identifier, entity))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct ConsoleUi;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct ConsoleText;
+
+/// Reads typing into the open console, and keeps keys from reaching the game meanwhile.
+pub fn console_input(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut console: ResMut<Console>,
+    mut input_lock: ResMut<InputLock>,
+    mut run_events: EventWriter<RunConsoleCommand>,
+    settings: Res<Settings>,
+) {
+    for keyboard_event in keyboard_events.read() {
+        if keyboard_event.state != ButtonState::Pressed {
+            continue;
+        }
+        if keyboard_event.key_code == settings.controls.console.0 {
+            console.open = !console.open;
+            if console.open {
+                input_lock.lock(INPUT_LOCK_REASON);
+            } else {
+                input_lock.unlock(INPUT_LOCK_REASON);
+            }
+            continue;
+        }
+        if !console.open {
+            continue;
+        }
+
+        match (&keyboard_event.key_code, &keyboard_event.logical_key) {
+            (KeyCode::Enter | KeyCode::NumpadEnter, _) => {
+                let line = console.submit();
+                run_events.send(RunConsoleCommand(line));
+            }
+            (KeyCode::Escape, _) => {
+                console.open = false;
+                input_lock.unlock(INPUT_LOCK_REASON);
+            }
+            (KeyCode::Backspace, _) => {
+                console.input.pop();
+            }
+            (KeyCode::ArrowUp, _) => console.browse_history(true),
+            (KeyCode::ArrowDown, _) => console.browse_history(false),
+            (KeyCode::Tab, _) => console.completing = true,
+            (_, Key::Space) => console.input.push(' '),
+            (_, Key::Character(text)) => console.input.push_str(text),
+            _ => (),
+        }
+    }
+
+    if console.open {
+        keys.reset_all();
+    }
+}
+
+/// Runs submitted commands and completes the input, with the whole world at hand.
+pub fn run_console_commands(world: &mut World) {
+    let lines: Vec<String> = world
+        .resource_mut::<Events<RunConsoleCommand>>()
+        .drain()
+        .map(|run| run.0)
+        .collect();
+    if lines.is_empty() && !world.resource::<Console>().completing {
+        return;
+    }
+    let commands = world.resource::<ConsoleCommands>().clone();
+
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        event!(Level::INFO, "Console | {}", line);
+        let result = commands.run(world, &line);
+        let mut console = world.resource_mut::<Console>();
+        console.print(format!("> {}", line));
+        match result {
+            Ok(output) if output.is_empty() => (),
+            Ok(output) => console.print(output),
+            Err(e) => console.print(format!("error: {}", e)),
+        }
+    }
+
+    if !world.resource::<Console>().completing {
+        return;
+    }
+    let input = world.resource::<Console>().input.clone();
+    let words: Vec<&str> = input.split(' ').collect();
+    let candidates = match words.split_first() {
+        Some((_, [])) | None => commands.names().map(String::from).collect(),
+        Some((name, args)) => commands
+            .get(name)
+            .map(|command| command.complete(world, args))
+            .unwrap_or_default(),
+    };
+    let (completed, matches) = complete(&input, &candidates);
+
+    let mut console = world.resource_mut::<Console>();
+    console.completing = false;
+    console.input = completed;
+    if matches.len() > 1 {
+        console.print(matches.join("  "));
+    }
+}
+
+/// Shows or hides the console overlay and keeps its text up to date.
+pub fn update_console_ui(
+    mut commands: Commands,
+    console: Res<Console>,
+    ui_query: Query<Entity, With<ConsoleUi>>,
+    mut text_query: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    if !console.open {
+        for entity in &ui_query {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let text = format!("{}\n> {}_", console.output.join("\n"), console.input);
+    if let Ok(mut console_text) = text_query.get_single_mut() {
+        console_text.sections[0].value = text;
+        return;
+    }
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    padding: UiRect::all(Val::Px(6.)),
+                    ..default()
+                },
+                background_color: Color::srgba(0., 0., 0., 0.8).into(),
+                z_index: ZIndex::Global(100),
+                ..default()
+            },
+            ConsoleUi,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    text,
+                    TextStyle {
+                        font_size: 14.,
+                        color: Color::srgb(0.8, 1., 0.8),
+                        ..default()
+                    },
+                ),
+                ConsoleText,
+            ));
+        });
+}
+
+/// Plugin for the developer console, opened with [`Controls::console`](crate::settings::Controls::console).
+///
+/// Commands are registered by the plugins they belong to, see [`ConsoleAppExt`].
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .init_resource::<ConsoleCommands>()
+            .init_resource::<Spawnables>()
+            .init_resource::<InputLock>()
+            .add_event::<RunConsoleCommand>()
+            .add_console_command(HelpCommand)
+            .add_console_command(ClearCommand)
+            .add_console_command(TimescaleCommand)
+            .add_console_command(SpawnCommand)
+            .add_systems(PreUpdate, console_input.after(InputSystem))
+            .add_systems(Update, (run_console_commands, update_console_ui).chain());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn completes_to_the_common_prefix() {
+        let candidates = strings(&["timescale", "timeline", "tp"]);
+        let (completed, matches) = complete("tim", &candidates);
+        assert_eq!(completed, "time");
+        assert_eq!(matches.len(), 2);
+
+        let (completed, _) = complete("give Wa", &strings(&["Water", "Jerky"]));
+        assert_eq!(completed, "give Water ");
+
+        let (completed, matches) = complete("x", &candidates);
+        assert_eq!(completed, "x");
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn history_is_browsed_with_arrows() {
+        let mut console = Console::default();
+        for line in ["tp 0 0", "god", "god"] {
+            console.input = line.to_string();
+            console.submit();
+        }
+        assert_eq!(console.history, strings(&["tp 0 0", "god"]));
+
+        console.browse_history(true);
+        assert_eq!(console.input, "god");
+        console.browse_history(true);
+        console.browse_history(true);
+        assert_eq!(console.input, "tp 0 0");
+        console.browse_history(false);
+        console.browse_history(false);
+        assert_eq!(console.input, "");
+    }
+
+    #[test]
+    fn commands_run_against_the_world() {
+        let mut world = World::new();
+        world.init_resource::<Time<Virtual>>();
+        let mut commands = ConsoleCommands::default();
+        commands.add(TimescaleCommand);
+
+        assert!(commands.run(&mut world, "timescale 0.5").is_ok());
+        assert_eq!(world.resource::<Time<Virtual>>().relative_speed(), 0.5);
+        assert!(commands.run(&mut world, "timescale fast").is_err());
+        assert!(commands.run(&mut world, "warp").is_err());
+    }
+}
@@ -0,0 +1,400 @@
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use bevy_rapier2d::{
+    prelude::*,
+    render::{DebugRenderContext, RapierDebugRenderPlugin},
+};
+
+use crate::{
+    console::{ConsoleAppExt, ConsoleCommand},
+    enemy::Patrol,
+    game_flow::level_bounds,
+    misc_objects::FloatingInteractionIndicator,
+    walls::{Wall, WallCollider},
+    water::Water,
+};
+
+/// Key toggling every overlay at once
+pub const DEBUG_KEY: KeyCode = KeyCode::F3;
+
+const WALL_COLOR: Color = Color::srgb(1., 0.3, 0.2);
+const SENSOR_COLOR: Color = Color::srgb(0.3, 0.6, 1.);
+const PATROL_COLOR: Color = Color::srgb(1., 0.8, 0.2);
+const INTERACTION_COLOR: Color = Color::srgb(0.3, 1., 0.4);
+const LEVEL_COLOR: Color = Color::srgba(1., 1., 1., 0.5);
+const SELECTED_LEVEL_COLOR: Color = Color::srgb(1., 1., 0.);
+
+/// Which debug overlays are shown.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Resource)]
+pub struct DebugOverlays {
+    /// Rapier's own rendering of every collider
+    pub physics: bool,
+    /// Merged wall rectangles from [`spawn_wall_collision`](crate::walls::spawn_wall_collision)
+    pub walls: bool,
+    pub sensors: bool,
+    /// Patrol routes, with the point each mob is heading to
+    pub patrols: bool,
+    /// Distance from which characters can be talked to
+    pub interactions: bool,
+    /// Level rectangles the player is matched against to select a level
+    pub level_bounds: bool,
+    /// IntGrid cells, blocked in red, water in blue and other terrain in grey
+    pub nav_grid: bool,
+    /// The egui world inspector
+    pub inspector: bool,
+}
+
+impl DebugOverlays {
+    /// Names taken by the `debug` command.
+    pub const NAMES: [&'static str; 8] = [
+        "physics",
+        "walls",
+        "sensors",
+        "patrols",
+        "interactions",
+        "level_bounds",
+        "nav_grid",
+        "inspector",
+    ];
+
+    pub fn all() -> Self {
+        DebugOverlays {
+            physics: true,
+            walls: true,
+            sensors: true,
+            patrols: true,
+            interactions: true,
+            level_bounds: true,
+            nav_grid: true,
+            inspector: true,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        let mut overlays = *self;
+        overlays.get_mut(name).map(|shown| *shown)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "physics" => Some(&mut self.physics),
+            "walls" => Some(&mut self.walls),
+            "sensors" => Some(&mut self.sensors),
+            "patrols" => Some(&mut self.patrols),
+            "interactions" => Some(&mut self.interactions),
+            "level_bounds" => Some(&mut self.level_bounds),
+            "nav_grid" => Some(&mut self.nav_grid),
+            "inspector" => Some(&mut self.inspector),
+            _ => None,
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        *self != DebugOverlays::default()
+    }
+}
+
+/// Outline of a collider, a circle for balls and its bounding box otherwise.
+fn draw_collider(gizmos: &mut Gizmos, transform: &GlobalTransform, collider: &Collider, color: Color) {
+    let position = transform.translation().truncate();
+    if let Some(ball) = collider.as_ball() {
+        gizmos.circle_2d(position, ball.radius(), color);
+        return;
+    }
+    let aabb = collider.raw.compute_local_aabb();
+    let min = Vec2::new(aabb.mins.x, aabb.mins.y);
+    let max = Vec2::new(aabb.maxs.x, aabb.maxs.y);
+    gizmos.rect_2d(position + (min + max) / 2., 0., max - min, color);
+}
+
+/// F3 shows every overlay, or hides them all if any is shown.
+pub fn toggle_overlays(input: Res<ButtonInput<KeyCode>>, mut overlays: ResMut<DebugOverlays>) {
+    if input.just_pressed(DEBUG_KEY) {
+        *overlays = if overlays.any() {
+            DebugOverlays::default()
+        } else {
+            DebugOverlays::all()
+        };
+    }
+}
+
+pub fn sync_physics_render(overlays: Res<DebugOverlays>, mut context: ResMut<DebugRenderContext>) {
+    if overlays.is_changed() {
+        context.enabled = overlays.physics;
+    }
+}
+
+pub fn draw_walls(
+    overlays: Res<DebugOverlays>,
+    mut gizmos: Gizmos,
+    wall_query: Query<(&GlobalTransform, &Collider), With<WallCollider>>,
+) {
+    if !overlays.walls {
+        return;
+    }
+    for (transform, collider) in &wall_query {
+        draw_collider(&mut gizmos, transform, collider, WALL_COLOR);
+    }
+}
+
+pub fn draw_sensors(
+    overlays: Res<DebugOverlays>,
+    mut gizmos: Gizmos,
+    sensor_query: Query<(&GlobalTransform, &Collider), With<Sensor>>,
+) {
+    if !overlays.sensors {
+        return;
+    }
+    for (transform, collider) in &sensor_query {
+        draw_collider(&mut gizmos, transform, collider, SENSOR_COLOR);
+    }
+}
+
+pub fn draw_patrols(
+    overlays: Res<DebugOverlays>,
+    mut gizmos: Gizmos,
+    patrol_query: Query<(&Patrol, &Transform, &GlobalTransform)>,
+) {
+    if !overlays.patrols {
+        return;
+    }
+    for (patrol, transform, global_transform) in &patrol_query {
+        // points are relative to the mob's parent, like its transform
+        let offset = (global_transform.translation() - transform.translation).truncate();
+        gizmos.linestrip_2d(patrol.points.iter().map(|point| *point + offset), PATROL_COLOR);
+        for (index, point) in patrol.points.iter().enumerate() {
+            let radius = if index == patrol.index { 5. } else { 2. };
+            gizmos.circle_2d(*point + offset, radius, PATROL_COLOR);
+        }
+    }
+}
+
+pub fn draw_interaction_radii(
+    overlays: Res<DebugOverlays>,
+    mut gizmos: Gizmos,
+    indicator_query: Query<(&FloatingInteractionIndicator, &Parent)>,
+    transform_query: Query<&GlobalTransform>,
+) {
+    if !overlays.interactions {
+        return;
+    }
+    for (indicator, parent) in &indicator_query {
+        if let Ok(transform) = transform_query.get(parent.get()) {
+            gizmos.circle_2d(
+                transform.translation().truncate(),
+                indicator.trigger_distance,
+                INTERACTION_COLOR,
+            );
+        }
+    }
+}
+
+pub fn draw_level_bounds(
+    overlays: Res<DebugOverlays>,
+    mut gizmos: Gizmos,
+    level_selection: Res<LevelSelection>,
+    level_query: Query<(&LevelIid, &Transform)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    if !overlays.level_bounds {
+        return;
+    }
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+
+    for (level_iid, level_transform) in &level_query {
+        let Some(level) = ldtk_project.get_raw_level_by_iid(&level_iid.to_string()) else {
+            continue;
+        };
+        let bounds = level_bounds(level_transform, level);
+        let color = if level_selection.is_match(&LevelIndices::default(), level) {
+            SELECTED_LEVEL_COLOR
+        } else {
+            LEVEL_COLOR
+        };
+        gizmos.rect_2d(bounds.center(), 0., bounds.size(), color);
+    }
+}
+
+pub fn draw_nav_grid(
+    overlays: Res<DebugOverlays>,
+    mut gizmos: Gizmos,
+    cell_query: Query<(&GridCoords, &Parent, Has<Wall>, Has<Water>), With<IntGridCell>>,
+    layer_query: Query<(&LayerMetadata, &GlobalTransform)>,
+) {
+    if !overlays.nav_grid {
+        return;
+    }
+    for (coords, parent, wall, water) in &cell_query {
+        let Ok((layer, layer_transform)) = layer_query.get(parent.get()) else {
+            continue;
+        };
+        let grid_size = layer.grid_size as f32;
+        let center = layer_transform.translation().truncate()
+            + (Vec2::new(coords.x as f32, coords.y as f32) + 0.5) * grid_size;
+        let color = match (wall, water) {
+            (true, _) => WALL_COLOR.with_alpha(0.4),
+            (false, true) => SENSOR_COLOR.with_alpha(0.4),
+            (false, false) => Color::srgba(0.7, 0.7, 0.7, 0.3),
+        };
+        gizmos.rect_2d(center, 0., Vec2::splat(grid_size - 1.), color);
+    }
+}
+
+/// `debug [overlay|all|off]`: toggles a debug overlay, or lists them.
+pub struct DebugCommand;
+
+impl ConsoleCommand for DebugCommand {
+    fn name(&self) -> &'static str {
+        "debug"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[overlay|all|off]"
+    }
+
+    fn complete(&self, _: &mut World, args: &[&str]) -> Vec<String> {
+        match args.len() {
+            1 => DebugOverlays::NAMES
+                .iter()
+                .chain(&["all", "off"])
+                .map(|name| name.to_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        let mut overlays = world.resource_mut::<DebugOverlays>();
+        match args.first() {
+            None => {}
+            Some(&"all") => *overlays = DebugOverlays::all(),
+            Some(&"off") => *overlays = DebugOverlays::default(),
+            Some(name) => {
+                let shown = overlays
+                    .get_mut(name)
+                    .ok_or_else(|| format!("Unknown overlay: {}", name))?;
+                *shown = !*shown;
+            }
+        }
+
+        let overlays = *overlays;
+        Ok(DebugOverlays::NAMES
+            .iter()
+            .map(|name| {
+                let shown = overlays.get(name).unwrap_or_default();
+                format!("{} {}", name, if shown { "on" } else { "off" })
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+/// Plugin for debug overlays and the world inspector, toggled with F3 or the `debug` command.
+///
+/// Only built with the `debug` feature.
+#[derive(Default)]
+pub struct DebugPlugin {
+    /// Overlays shown on startup
+    pub overlays: DebugOverlays,
+}
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        event!(Level::INFO, "Debug overlays available, F3 to toggle");
+        let mut overlays = self.overlays;
+        if app.is_plugin_added::<RapierDebugRenderPlugin>() {
+            // added by the app itself, like the examples, to be shown from the start
+            overlays.physics = true;
+        } else {
+            app.add_plugins(RapierDebugRenderPlugin {
+                enabled: overlays.physics,
+                ..default()
+            });
+        }
+
+        app.insert_resource(overlays)
+            .add_plugins(
+                WorldInspectorPlugin::new().run_if(|overlays: Res<DebugOverlays>| overlays.inspector),
+            )
+            .add_systems(
+                Update,
+                (
+                    toggle_overlays,
+                    sync_physics_render,
+                    draw_walls,
+                    draw_sensors,
+                    draw_patrols,
+                    draw_interaction_radii,
+                    draw_level_bounds,
+                    draw_nav_grid,
+                )
+                    .chain(),
+            )
+            .add_console_command(DebugCommand);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::ConsoleCommands;
+
+    #[test]
+    fn overlays_toggle_by_name() {
+        let mut overlays = DebugOverlays::default();
+        assert!(!overlays.any());
+        for name in DebugOverlays::NAMES {
+            *overlays.get_mut(name).unwrap() = true;
+        }
+        assert_eq!(overlays, DebugOverlays::all());
+        assert_eq!(overlays.get("walls"), Some(true));
+        assert!(overlays.get("unicorns").is_none());
+    }
+
+    #[test]
+    fn command_and_key_toggle_physics_rendering() {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<DebugOverlays>()
+            .insert_resource(DebugRenderContext {
+                enabled: false,
+                ..default()
+            })
+            .add_systems(Update, (toggle_overlays, sync_physics_render).chain());
+        let mut commands = ConsoleCommands::default();
+        commands.add(DebugCommand);
+
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(DEBUG_KEY);
+        app.update();
+        assert_eq!(*app.world().resource::<DebugOverlays>(), DebugOverlays::all());
+        assert!(app.world().resource::<DebugRenderContext>().enabled);
+
+        let mut input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        input.release(DEBUG_KEY);
+        input.clear();
+        assert!(commands.run(app.world_mut(), "debug physics").is_ok());
+        app.update();
+        assert!(!app.world().resource::<DebugOverlays>().physics);
+        assert!(!app.world().resource::<DebugRenderContext>().enabled);
+
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(DEBUG_KEY);
+        app.update();
+        assert!(!app.world().resource::<DebugOverlays>().any());
+
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().clear();
+        assert!(commands.run(app.world_mut(), "debug all").is_ok());
+        app.update();
+        assert!(app.world().resource::<DebugRenderContext>().enabled);
+        assert!(commands.run(app.world_mut(), "debug unicorns").is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::VecDeque;
+
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+
+use crate::{
+    player::InputLock,
+    settings::Settings,
+    timeline::{ActionStarted, Timeline},
+};
+
+/// Name the [`InputLock`] is held under while a dialogue is shown
+const INPUT_LOCK_REASON: &str = "dialogue";
+
+/// A dialogue action of a [`Timeline`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DialogueLine {
+    /// Entity holding the [`Timeline`]
+    pub entity: Entity,
+    pub action: String,
+}
+
+/// The dialogue shown, followed by the ones started while it was open.
+#[derive(Clone, Debug, Default, PartialEq, Resource)]
+pub struct Dialogues {
+    pub queue: VecDeque<DialogueLine>,
+}
+
+impl Dialogues {
+    pub fn current(&self) -> Option<&DialogueLine> {
+        self.queue.front()
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct DialogueUi;
+
+/// Text of the dialogue shown, revealed a few characters at a time.
+#[derive(Clone, PartialEq, Debug, Default, Component)]
+pub struct DialogueText {
+    pub full: String,
+    /// Characters shown so far
+    pub revealed: f32,
+}
+
+impl DialogueText {
+    pub fn is_revealed(&self) -> bool {
+        self.revealed as usize >= self.full.chars().count()
+    }
+
+    pub fn reveal_all(&mut self) {
+        self.revealed = self.full.chars().count() as f32;
+    }
+
+    pub fn shown(&self) -> String {
+        self.full.chars().take(self.revealed as usize).collect()
+    }
+}
+
+/// Queues the dialogue actions that start.
+pub fn queue_dialogues(
+    mut action_started: EventReader<ActionStarted>,
+    timeline_query: Query<&Timeline>,
+    mut dialogues: ResMut<Dialogues>,
+    mut input_lock: ResMut<InputLock>,
+) {
+    for started in action_started.read() {
+        let is_dialogue = timeline_query
+            .get(started.entity)
+            .ok()
+            .and_then(|timeline| timeline.action(&started.action))
+            .is_some_and(|action| action.is_dialogue());
+        if is_dialogue {
+            dialogues.queue.push_back(DialogueLine {
+                entity: started.entity,
+                action: started.action.clone(),
+            });
+            input_lock.lock(INPUT_LOCK_REASON);
+        }
+    }
+}
+
+/// A response key answers the dialogue shown, the interact key closes one without responses.
+///
+/// Either way the action is completed, so the actions depending on it can start.
+/// While the text is still being revealed, the interact key shows all of it instead.
+pub fn dialogue_input(
+    mut input: ResMut<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut dialogues: ResMut<Dialogues>,
+    mut timeline_query: Query<&mut Timeline>,
+    mut text_query: Query<&mut DialogueText>,
+    mut input_lock: ResMut<InputLock>,
+) {
+    let Some(line) = dialogues.current().cloned() else {
+        return;
+    };
+    let interact = settings.controls.interact.0;
+    if let Ok(mut text) = text_query.get_single_mut() {
+        if !text.is_revealed() && input.just_pressed(interact) {
+            text.reveal_all();
+            input.clear_just_pressed(interact);
+            return;
+        }
+    }
+    let action = timeline_query
+        .get(line.entity)
+        .ok()
+        .and_then(|timeline| timeline.action(&line.action));
+
+    match action {
+        Some(action) => {
+            let response = action
+                .available_responses
+                .iter()
+                .find(|response| input.just_pressed(response.key.0))
+                .cloned();
+            let key = match &response {
+                Some(response) => response.key.0,
+                None if action.available_responses.is_empty() => interact,
+                None => return,
+            };
+            if !input.just_pressed(key) {
+                return;
+            }
+            // the key shouldn't also reach whatever runs after the dialogue closes
+            input.clear_just_pressed(key);
+
+            let mut timeline = timeline_query
+                .get_mut(line.entity)
+                .expect("timeline was just read");
+            if let Some(action) = timeline
+                .actions
+                .iter_mut()
+                .find(|action| action.name == line.action)
+            {
+                event!(
+                    Level::INFO,
+                    "Dialogue {} answered | {}",
+                    action.name,
+                    response.as_ref().map_or("", |response| response.text.as_str())
+                );
+                action.given_response = response;
+                action.completed = true;
+            }
+        }
+        // the speaker is gone, like after loading another level
+        None => event!(Level::WARN, "Dropping dialogue {}, its timeline is gone", line.action),
+    }
+
+    dialogues.queue.pop_front();
+    if dialogues.queue.is_empty() {
+        input_lock.unlock(INPUT_LOCK_REASON);
+    }
+}
+
+fn text_line(parent: &mut ChildBuilder, text: String, color: Color) {
+    parent.spawn(TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size: 16.0,
+            color,
+            ..default()
+        },
+    ));
+}
+
+/// Rebuilds the dialogue box whenever the dialogue shown changes.
+pub fn update_dialogue_ui(
+    mut commands: Commands,
+    dialogues: Res<Dialogues>,
+    settings: Res<Settings>,
+    timeline_query: Query<&Timeline>,
+    ui_query: Query<Entity, With<DialogueUi>>,
+) {
+    if !dialogues.is_changed() {
+        return;
+    }
+    for entity in &ui_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(action) = dialogues.current().and_then(|line| {
+        timeline_query
+            .get(line.entity)
+            .ok()
+            .and_then(|timeline| timeline.action(&line.action))
+    }) else {
+        return;
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(16.0),
+                    left: Val::Px(16.0),
+                    right: Val::Px(16.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.7).into(),
+                ..default()
+            },
+            DialogueUi,
+        ))
+        .with_children(|parent| {
+            if let Some(text) = &action.text {
+                parent.spawn((
+                    TextBundle::from_section(
+                        String::new(),
+                        TextStyle {
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    DialogueText {
+                        full: text.clone(),
+                        revealed: 0.,
+                    },
+                ));
+            }
+            for response in &action.available_responses {
+                text_line(
+                    parent,
+                    format!("{}  {}", response.key, response.text),
+                    Color::srgb(1.0, 0.85, 0.3),
+                );
+            }
+            if action.available_responses.is_empty() {
+                text_line(
+                    parent,
+                    format!("{}  continue", settings.controls.interact),
+                    Color::srgb(0.6, 0.6, 0.6),
+                );
+            }
+        });
+}
+
+/// Reveals dialogue text at the [`Settings`] text speed.
+pub fn reveal_dialogue_text(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut text_query: Query<(&mut DialogueText, &mut Text)>,
+) {
+    for (mut dialogue_text, mut text) in &mut text_query {
+        let shown = dialogue_text.revealed as usize;
+        if !dialogue_text.is_revealed() {
+            dialogue_text.revealed += settings.text_speed * time.delta_seconds();
+        }
+        if text.sections[0].value.is_empty() || dialogue_text.revealed as usize != shown {
+            text.sections[0].value = dialogue_text.shown();
+        }
+    }
+}
+
+/// Plugin which shows timeline actions with text as dialogue and waits for a response.
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Dialogues>().add_systems(
+            Update,
+            (queue_dialogues, dialogue_input, update_dialogue_ui, reveal_dialogue_text).chain(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game_flow::LevelEntered,
+        inventory::{Inventory, InventoryPlugin},
+        paths::GamePaths,
+        player::Player,
+        quest::StartQuest,
+        sequence::SequencePlugin,
+        story::{StoryPlugin, StoryState},
+        test_support::TestApp,
+        timeline::TimelinePlugin,
+    };
+
+    #[test]
+    fn text_is_revealed_a_few_characters_at_a_time() {
+        let mut text = DialogueText {
+            full: "Howdy".to_string(),
+            revealed: 2.5,
+        };
+        assert_eq!(text.shown(), "Ho");
+        assert!(!text.is_revealed());
+        text.reveal_all();
+        assert_eq!(text.shown(), "Howdy");
+        assert!(text.is_revealed());
+    }
+
+    #[test]
+    fn shipped_timeline_runs_to_the_end() {
+        let timeline: Timeline =
+            serde_xml_rs::from_str(include_str!("../assets/timelines/harry_rickard_init.xml")).unwrap();
+
+        let mut app = TestApp::new();
+        app.add_plugins((TimelinePlugin, StoryPlugin, InventoryPlugin, SequencePlugin, DialoguePlugin));
+        app.world().init_resource::<GamePaths>();
+        app.world().init_resource::<Events<LevelEntered>>();
+        app.world().init_resource::<Events<StartQuest>>();
+        app.load_level();
+        app.for_each::<&mut Timeline, With<Player>>(|mut player_timeline| *player_timeline = timeline.clone());
+
+        app.advance(2);
+        assert_eq!(
+            app.world().resource::<Dialogues>().current().map(|line| line.action.as_str()),
+            Some("opening conversation william")
+        );
+        assert!(app.world().resource::<InputLock>().is_locked());
+
+        // give the text time to show at the default text speed
+        app.advance(60);
+        assert!(app.single::<DialogueText, ()>().is_revealed());
+
+        app.press(KeyCode::KeyE).advance(1).release(KeyCode::KeyE);
+        let finished = app.run_until(600, |world| {
+            world
+                .query_filtered::<&Timeline, With<Player>>()
+                .single(world)
+                .actions
+                .iter()
+                .all(|action| action.completed)
+        });
+        assert!(finished, "the timeline did not run to the end");
+
+        assert!(app.world().resource::<StoryState>().flag("met_hickman"));
+        assert!(app.single::<Inventory, With<Player>>().contains("Water"));
+        let opening = app.single::<Timeline, With<Player>>();
+        let opening = opening.action("opening conversation william").unwrap();
+        assert_eq!(opening.given_response.as_ref().map(|response| response.text.as_str()), Some("Sure"));
+    }
+}
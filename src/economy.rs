@@ -0,0 +1,516 @@
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_common_assets::xml::XmlAssetPlugin;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    character::{is_named, CharacterInteraction},
+    console::{parse_arg, player_entity, ConsoleAppExt, ConsoleCommand},
+    inventory::{Inventory, InventoryChanged},
+    player::{InputLock, Player},
+    quest::QuestCompleted,
+    settings::Settings,
+};
+
+/// Name the [`InputLock`] is held under while a shop is open
+const INPUT_LOCK_REASON: &str = "shop";
+
+fn one() -> u32 {
+    1
+}
+
+/// Money carried by an entity, initialized from its LDtk `money` field.
+#[derive(Copy, Clone, PartialEq, Debug, Default, Component)]
+pub struct Wallet(pub f32);
+
+impl From<&EntityInstance> for Wallet {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Wallet(entity_instance.get_float_field("money").copied().unwrap_or(0.))
+    }
+}
+
+/// An item and what it costs.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Offer {
+    pub item: String,
+    pub price: f32,
+}
+
+/// Items swapped for other items, without money.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Trade {
+    pub give: String,
+    #[serde(default = "one")]
+    pub give_count: u32,
+    pub get: String,
+    #[serde(default = "one")]
+    pub get_count: u32,
+}
+
+/// What a Character sells to and buys from the player.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Shop {
+    /// Character running the shop, by `name` field or identifier
+    pub character: String,
+    #[serde(rename = "sell", default)]
+    pub sells: Vec<Offer>,
+    #[serde(rename = "buy", default)]
+    pub buys: Vec<Offer>,
+    #[serde(rename = "trade", default)]
+    pub trades: Vec<Trade>,
+}
+
+/// Result of a transaction, items that changed hands and a message for the player.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Receipt {
+    pub message: String,
+    pub changed_items: Vec<String>,
+}
+
+impl Offer {
+    /// The player buys the item.
+    pub fn buy(&self, wallet: &mut Wallet, inventory: &mut Inventory) -> Result<Receipt, String> {
+        if wallet.0 < self.price {
+            return Err(format!("Not enough money for {}", self.item));
+        }
+        wallet.0 -= self.price;
+        inventory.add(self.item.clone());
+        Ok(Receipt {
+            message: format!("Bought {} for ${}", self.item, self.price),
+            changed_items: vec![self.item.clone()],
+        })
+    }
+
+    /// The player sells the item.
+    pub fn sell(&self, wallet: &mut Wallet, inventory: &mut Inventory) -> Result<Receipt, String> {
+        if !inventory.remove(&self.item) {
+            return Err(format!("You have no {}", self.item));
+        }
+        wallet.0 += self.price;
+        Ok(Receipt {
+            message: format!("Sold {} for ${}", self.item, self.price),
+            changed_items: vec![self.item.clone()],
+        })
+    }
+}
+
+impl Trade {
+    pub fn barter(&self, inventory: &mut Inventory) -> Result<Receipt, String> {
+        if inventory.count(&self.give) < self.give_count as usize {
+            return Err(format!("You need {} {}", self.give_count, self.give));
+        }
+        for _ in 0..self.give_count {
+            inventory.remove(&self.give);
+        }
+        for _ in 0..self.get_count {
+            inventory.add(self.get.clone());
+        }
+        Ok(Receipt {
+            message: format!(
+                "Traded {} {} for {} {}",
+                self.give_count, self.give, self.get_count, self.get
+            ),
+            changed_items: vec![self.give.clone(), self.get.clone()],
+        })
+    }
+}
+
+/// Every shop of the game, loaded from a `*.shops.xml` asset.
+#[derive(Asset, Clone, Debug, Default, PartialEq, Deserialize, TypePath)]
+pub struct ShopBook {
+    #[serde(rename = "shop", default)]
+    pub shops: Vec<Shop>,
+}
+
+#[derive(Resource)]
+pub struct ShopBookHandle(pub Handle<ShopBook>);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ShopMode {
+    #[default]
+    Buy,
+    Sell,
+    Barter,
+}
+
+impl ShopMode {
+    fn next(self) -> Self {
+        match self {
+            ShopMode::Buy => ShopMode::Sell,
+            ShopMode::Sell => ShopMode::Barter,
+            ShopMode::Barter => ShopMode::Buy,
+        }
+    }
+}
+
+/// The shop the player is trading with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShopSession {
+    pub shop: Shop,
+    pub mode: ShopMode,
+    pub selected: usize,
+}
+
+impl ShopSession {
+    fn entries(&self) -> usize {
+        match self.mode {
+            ShopMode::Buy => self.shop.sells.len(),
+            ShopMode::Sell => self.shop.buys.len(),
+            ShopMode::Barter => self.shop.trades.len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Resource)]
+pub struct OpenShop(pub Option<ShopSession>);
+
+/// Message shown to the player after a transaction.
+#[derive(Clone, Debug, PartialEq, Event)]
+pub struct TransactionFeedback {
+    pub message: String,
+    pub success: bool,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct ShopUi;
+
+#[derive(Clone, Debug, Component)]
+pub struct Toast {
+    pub timer: Timer,
+}
+
+fn load_shop_book(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ShopBookHandle(asset_server.load("shops/default.shops.xml")));
+}
+
+/// Opens the shop of a character the player interacts with.
+pub fn open_shops(
+    mut interactions: EventReader<CharacterInteraction>,
+    entity_query: Query<&EntityInstance>,
+    book_handle: Res<ShopBookHandle>,
+    books: Res<Assets<ShopBook>>,
+    mut open_shop: ResMut<OpenShop>,
+    mut input_lock: ResMut<InputLock>,
+) {
+    let Some(book) = books.get(&book_handle.0) else {
+        return;
+    };
+
+    for interaction in interactions.read() {
+        let Ok(entity_instance) = entity_query.get(interaction.character) else {
+            continue;
+        };
+        if let Some(shop) = book.shops.iter().find(|shop| is_named(entity_instance, &shop.character)) {
+            event!(Level::INFO, "Opening the shop of {}", shop.character);
+            open_shop.0 = Some(ShopSession {
+                shop: shop.clone(),
+                mode: ShopMode::default(),
+                selected: 0,
+            });
+            input_lock.lock(INPUT_LOCK_REASON);
+        }
+    }
+}
+
+/// Tab switches between buying, selling and bartering, W and S pick an entry,
+/// Enter confirms and Escape leaves, as bound in the [`Settings`] controls.
+pub fn shop_input(
+    input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut open_shop: ResMut<OpenShop>,
+    mut input_lock: ResMut<InputLock>,
+    mut player_query: Query<(Entity, &mut Wallet, &mut Inventory), With<Player>>,
+    mut inventory_events: EventWriter<InventoryChanged>,
+    mut feedback: EventWriter<TransactionFeedback>,
+) {
+    let controls = &settings.controls;
+    if input.just_pressed(controls.cancel.0) && open_shop.0.is_some() {
+        open_shop.0 = None;
+        input_lock.unlock(INPUT_LOCK_REASON);
+        return;
+    }
+    let Some(session) = open_shop.0.as_mut() else {
+        return;
+    };
+
+    if input.just_pressed(controls.shop_mode.0) {
+        session.mode = session.mode.next();
+        session.selected = 0;
+    }
+    let entries = session.entries();
+    if entries > 0 {
+        if input.just_pressed(controls.down.0) {
+            session.selected = (session.selected + 1) % entries;
+        }
+        if input.just_pressed(controls.up.0) {
+            session.selected = (session.selected + entries - 1) % entries;
+        }
+    }
+
+    if !input.just_pressed(controls.confirm.0) || entries == 0 {
+        return;
+    }
+    let Ok((player, mut wallet, mut inventory)) = player_query.get_single_mut() else {
+        return;
+    };
+    let result = match session.mode {
+        ShopMode::Buy => session.shop.sells[session.selected].buy(&mut wallet, &mut inventory),
+        ShopMode::Sell => session.shop.buys[session.selected].sell(&mut wallet, &mut inventory),
+        ShopMode::Barter => session.shop.trades[session.selected].barter(&mut inventory),
+    };
+
+    match result {
+        Ok(receipt) => {
+            event!(Level::INFO, "{}", receipt.message);
+            for item in receipt.changed_items {
+                inventory_events.send(InventoryChanged { entity: player, item });
+            }
+            feedback.send(TransactionFeedback {
+                message: receipt.message,
+                success: true,
+            });
+        }
+        Err(message) => {
+            feedback.send(TransactionFeedback {
+                message,
+                success: false,
+            });
+        }
+    }
+}
+
+pub fn pay_quest_rewards(
+    mut completed_events: EventReader<QuestCompleted>,
+    mut wallet_query: Query<&mut Wallet, With<Player>>,
+    mut feedback: EventWriter<TransactionFeedback>,
+) {
+    let Ok(mut wallet) = wallet_query.get_single_mut() else {
+        return;
+    };
+    for completed in completed_events.read() {
+        if completed.reward.money != 0. {
+            wallet.0 += completed.reward.money;
+            feedback.send(TransactionFeedback {
+                message: format!("Received ${}", completed.reward.money),
+                success: true,
+            });
+        }
+    }
+}
+
+fn text_line(parent: &mut ChildBuilder, text: String, color: Color) {
+    parent.spawn(TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size: 16.0,
+            color,
+            ..default()
+        },
+    ));
+}
+
+/// Rebuilds the shop panel whenever the session or the player's wallet changes.
+pub fn update_shop_ui(
+    mut commands: Commands,
+    open_shop: Res<OpenShop>,
+    settings: Res<Settings>,
+    wallet_query: Query<Ref<Wallet>, With<Player>>,
+    ui_query: Query<Entity, With<ShopUi>>,
+) {
+    let wallet = wallet_query.get_single().ok();
+    if !open_shop.is_changed() && !wallet.as_ref().is_some_and(|wallet| wallet.is_changed()) {
+        return;
+    }
+    for entity in &ui_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(session) = &open_shop.0 else {
+        return;
+    };
+
+    let entries: Vec<String> = match session.mode {
+        ShopMode::Buy => session
+            .shop
+            .sells
+            .iter()
+            .map(|offer| format!("{} ${}", offer.item, offer.price))
+            .collect(),
+        ShopMode::Sell => session
+            .shop
+            .buys
+            .iter()
+            .map(|offer| format!("{} ${}", offer.item, offer.price))
+            .collect(),
+        ShopMode::Barter => session
+            .shop
+            .trades
+            .iter()
+            .map(|trade| format!("{} {} -> {} {}", trade.give_count, trade.give, trade.get_count, trade.get))
+            .collect(),
+    };
+    let money = wallet.map_or(0., |wallet| wallet.0);
+
+    let controls = &settings.controls;
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(16.0),
+                    right: Val::Px(16.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.7).into(),
+                ..default()
+            },
+            ShopUi,
+        ))
+        .with_children(|parent| {
+            text_line(parent, format!("{} - {:?}", session.shop.character, session.mode), Color::WHITE);
+            text_line(parent, format!("Money ${}", money), Color::WHITE);
+            for (index, entry) in entries.into_iter().enumerate() {
+                let (prefix, color) = if index == session.selected {
+                    ("> ", Color::srgb(1.0, 0.85, 0.3))
+                } else {
+                    ("  ", Color::WHITE)
+                };
+                text_line(parent, format!("{}{}", prefix, entry), color);
+            }
+            text_line(
+                parent,
+                format!(
+                    "{} mode, {}/{} select, {} confirm, {} leave",
+                    controls.shop_mode, controls.up, controls.down, controls.confirm, controls.cancel
+                ),
+                Color::srgb(0.6, 0.6, 0.6),
+            );
+        });
+}
+
+/// Shows each transaction message for a couple of seconds.
+pub fn show_transaction_feedback(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut feedback: EventReader<TransactionFeedback>,
+    mut toast_query: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in &mut toast_query {
+        if toast.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for transaction in feedback.read() {
+        let color = if transaction.success {
+            Color::srgb(0.5, 1.0, 0.5)
+        } else {
+            Color::srgb(1.0, 0.4, 0.4)
+        };
+        commands.spawn((
+            TextBundle::from_section(
+                transaction.message.clone(),
+                TextStyle {
+                    font_size: 18.0,
+                    color,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(24.0),
+                left: Val::Px(16.0),
+                ..default()
+            }),
+            Toast {
+                timer: Timer::from_seconds(2.0, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+/// `money <amount>`: sets the money the player carries.
+pub struct MoneyCommand;
+
+impl ConsoleCommand for MoneyCommand {
+    fn name(&self) -> &'static str {
+        "money"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<amount>"
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        let amount: f32 = parse_arg(args, 0, "amount")?;
+        if amount < 0. {
+            return Err(format!("Amount should not be negative, was {}", amount));
+        }
+        let player = player_entity(world)?;
+        world.entity_mut(player).insert(Wallet(amount));
+        Ok(format!("Money set to {}", amount))
+    }
+}
+
+/// Plugin which handles money, shops and barter from `shops/default.shops.xml`.
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(XmlAssetPlugin::<ShopBook>::new(&["shops.xml"]))
+            .init_resource::<OpenShop>()
+            .add_event::<TransactionFeedback>()
+            .add_systems(Startup, load_shop_book)
+            .add_systems(
+                Update,
+                (
+                    open_shops,
+                    shop_input,
+                    pay_quest_rewards,
+                    update_shop_ui,
+                    show_transaction_feedback,
+                )
+                    .chain(),
+            )
+            .add_console_command(MoneyCommand);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buying_selling_and_bartering() {
+        let shop: ShopBook = serde_xml_rs::from_str(
+            r#"<shops>
+                <shop>
+                    <character>William Hickman</character>
+                    <sell><item>Water</item><price>5</price></sell>
+                    <buy><item>Gem</item><price>30</price></buy>
+                    <trade><give>Meat</give><give_count>2</give_count><get>Water</get></trade>
+                </shop>
+            </shops>"#,
+        )
+        .unwrap();
+        let shop = &shop.shops[0];
+
+        let mut wallet = Wallet(6.);
+        let mut inventory = Inventory::default();
+
+        assert!(shop.sells[0].buy(&mut wallet, &mut inventory).is_ok());
+        assert_eq!(wallet, Wallet(1.));
+        assert!(shop.sells[0].buy(&mut wallet, &mut inventory).is_err());
+        assert!(shop.buys[0].sell(&mut wallet, &mut inventory).is_err());
+
+        inventory.add("Meat");
+        assert!(shop.trades[0].barter(&mut inventory).is_err());
+        inventory.add("Meat");
+        assert!(shop.trades[0].barter(&mut inventory).is_ok());
+        assert_eq!(inventory.count("Water"), 2);
+        assert_eq!(inventory.count("Meat"), 0);
+    }
+}
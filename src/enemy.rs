@@ -1,8 +1,15 @@
+use tracing::{event, Level};
+
 use bevy::prelude::*;
 use bevy_ecs_ldtk::{prelude::*, utils::ldtk_pixel_coords_to_translation_pivoted};
-use bevy_rapier2d::dynamics::Velocity;
+use bevy_rapier2d::prelude::*;
 
-use crate::colliders::ColliderBundle;
+use crate::{
+    console::ConsoleAppExt,
+    health::{Died, Health},
+    terrain::CurrentTerrain,
+    weather::{Weather, WeatherSettings},
+};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Enemy;
@@ -11,11 +18,11 @@ pub struct Enemy;
 pub struct MobBundle {
     #[sprite_sheet_bundle]
     pub sprite_sheet_bundle: LdtkSpriteSheetBundle,
-    #[from_entity_instance]
-    pub collider_bundle: ColliderBundle,
     pub enemy: Enemy,
     #[ldtk_entity]
     pub patrol: Patrol,
+    pub current_terrain: CurrentTerrain,
+    pub health: Health,
 }
 
 #[derive(Clone, PartialEq, Debug, Default, Component)]
@@ -71,14 +78,22 @@ impl LdtkEntity for Patrol {
     }
 }
 
-pub fn patrol(mut query: Query<(&mut Transform, &mut Velocity, &mut Patrol)>) {
-    for (mut transform, mut velocity, mut patrol) in &mut query {
+pub fn patrol(
+    weather: Res<Weather>,
+    weather_settings: Res<WeatherSettings>,
+    mut query: Query<(&mut Transform, &mut Velocity, &mut Patrol, Option<&CurrentTerrain>)>,
+) {
+    for (mut transform, mut velocity, mut patrol, current_terrain) in &mut query {
         if patrol.points.len() <= 1 {
             continue;
         }
 
+        let speed = 75.
+            * current_terrain.map_or(1., |terrain| terrain.0.speed_multiplier)
+            * weather_settings.speed_multiplier(*weather);
+
         let mut new_velocity =
-            (patrol.points[patrol.index] - transform.translation.truncate()).normalize() * 75.;
+            (patrol.points[patrol.index] - transform.translation.truncate()).normalize() * speed;
 
         if new_velocity.dot(velocity.linvel) < 0. {
             if patrol.index == 0 {
@@ -97,18 +112,38 @@ pub fn patrol(mut query: Query<(&mut Transform, &mut Velocity, &mut Patrol)>) {
             }
 
             new_velocity =
-                (patrol.points[patrol.index] - transform.translation.truncate()).normalize() * 75.;
+                (patrol.points[patrol.index] - transform.translation.truncate()).normalize() * speed;
         }
 
         velocity.linvel = new_velocity;
     }
 }
 
+/// Stops, hides and removes the collider of enemies whose health ran out.
+///
+/// The entity itself stays until its level unloads, so that quests can still tell who was defeated.
+pub fn defeat_enemies(
+    mut commands: Commands,
+    mut died_events: EventReader<Died>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    for died in died_events.read() {
+        if enemy_query.contains(died.entity) {
+            event!(Level::INFO, "Enemy {:?} defeated", died.entity);
+            commands
+                .entity(died.entity)
+                .insert((Visibility::Hidden, Velocity::zero()))
+                .remove::<(Patrol, Collider)>();
+        }
+    }
+}
+
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, patrol)
-            .register_ldtk_entity::<MobBundle>("Mob");
+        app.add_systems(Update, (patrol, defeat_enemies))
+            .register_ldtk_entity::<MobBundle>("Mob")
+            .add_console_spawnable::<MobBundle>("Mob");
     }
 }
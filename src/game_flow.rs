@@ -1,24 +1,44 @@
-use crate::player::{AnimationTimer, Player};
+use crate::{
+    console::{current_project, player_entity, ConsoleAppExt, ConsoleCommand},
+    player::Player,
+};
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
 
-pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let camera = Camera2dBundle::default();
-    commands.spawn(camera);
+/// Path of the LDtk project to load, relative to the asset folder.
+#[derive(Clone, Debug, Eq, PartialEq, Resource)]
+pub struct ProjectFile(pub String);
 
-    let ldtk_handle = asset_server.load("Idaho.ldtk");
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>, project_file: Res<ProjectFile>) {
+    let ldtk_handle = asset_server.load(project_file.0.clone());
     commands.spawn(LdtkWorldBundle {
         ldtk_handle,
         ..Default::default()
     });
+}
+
+/// Area covered by a spawned level, from its transform and size.
+pub fn level_bounds(level_transform: &Transform, level: &bevy_ecs_ldtk::ldtk::Level) -> Rect {
+    let min = level_transform.translation.truncate();
+    Rect {
+        min,
+        max: min + Vec2::new(level.px_wid as f32, level.px_hei as f32),
+    }
+}
 
-    commands.insert_resource(AnimationTimer{timer: Timer::from_seconds(0.1, TimerMode::Repeating)})
+/// Sent when the player moves into a different level.
+#[derive(Clone, Debug, Eq, PartialEq, Event)]
+pub struct LevelEntered {
+    pub iid: String,
+    pub identifier: String,
 }
 
 pub fn update_level_selection(
     level_query: Query<(&LevelIid, &Transform), Without<Player>>,
     player_query: Query<&Transform, With<Player>>,
     mut level_selection: ResMut<LevelSelection>,
+    mut level_entered: EventWriter<LevelEntered>,
     ldtk_projects: Query<&Handle<LdtkProject>>,
     ldtk_project_assets: Res<Assets<LdtkProject>>,
 ) {
@@ -31,22 +51,20 @@ pub fn update_level_selection(
             .get_raw_level_by_iid(&level_iid.to_string())
             .expect("Spawned level should exist in LDtk project");
 
-        let level_bounds = Rect {
-            min: Vec2::new(level_transform.translation.x, level_transform.translation.y),
-            max: Vec2::new(
-                level_transform.translation.x + level.px_wid as f32,
-                level_transform.translation.y + level.px_hei as f32,
-            ),
-        };
+        let bounds = level_bounds(level_transform, level);
 
         for player_transform in &player_query {
-            if player_transform.translation.x < level_bounds.max.x
-                && player_transform.translation.x > level_bounds.min.x
-                && player_transform.translation.y < level_bounds.max.y
-                && player_transform.translation.y > level_bounds.min.y
+            if player_transform.translation.x < bounds.max.x
+                && player_transform.translation.x > bounds.min.x
+                && player_transform.translation.y < bounds.max.y
+                && player_transform.translation.y > bounds.min.y
                 && !level_selection.is_match(&LevelIndices::default(), level)
             {
                 *level_selection = LevelSelection::iid(level.iid.clone());
+                level_entered.send(LevelEntered {
+                    iid: level.iid.clone(),
+                    identifier: level.identifier.clone(),
+                });
             }
         }
     }
@@ -64,12 +82,109 @@ pub fn restart_level(
     }
 }
 
-pub struct GameFlowPlugin;
+/// `level <identifier>`: selects a level and moves the player to its center.
+pub struct LevelCommand;
+
+impl ConsoleCommand for LevelCommand {
+    fn name(&self) -> &'static str {
+        "level"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<identifier>"
+    }
+
+    fn complete(&self, world: &mut World, _: &[&str]) -> Vec<String> {
+        current_project(world)
+            .map(|project| {
+                project
+                    .iter_raw_levels()
+                    .map(|level| level.identifier.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        let name = *args.first().ok_or("Missing level")?;
+        let project = current_project(world).ok_or("The LDtk project isn't loaded")?;
+        let level = project
+            .iter_raw_levels()
+            .find(|level| level.identifier == name || level.iid == name)
+            .ok_or_else(|| format!("No level {}", name))?;
+
+        // LDtk's y axis points down, from the top left of the world
+        let center = Vec2::new(
+            (level.world_x + level.px_wid / 2) as f32,
+            -(level.world_y + level.px_hei / 2) as f32,
+        );
+        let player = player_entity(world)?;
+        if let Some(mut transform) = world.get_mut::<Transform>(player) {
+            transform.translation = center.extend(transform.translation.z);
+        }
+        *world.resource_mut::<LevelSelection>() = LevelSelection::iid(level.iid.clone());
+        world.send_event(LevelEntered {
+            iid: level.iid.clone(),
+            identifier: level.identifier.clone(),
+        });
+        Ok(format!("Moved to {}", level.identifier))
+    }
+}
+
+/// Plugin which loads the LDtk project, configures the physics world and
+/// keeps [`LevelSelection`] following the player.
+pub struct GameFlowPlugin {
+    /// LDtk project to load, relative to the asset folder.
+    pub project: String,
+    /// Level selected when the project first spawns.
+    pub start_level: LevelSelection,
+}
+
+impl GameFlowPlugin {
+    pub fn new(project: impl Into<String>) -> Self {
+        GameFlowPlugin {
+            project: project.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for GameFlowPlugin {
+    fn default() -> Self {
+        GameFlowPlugin {
+            project: "Idaho.ldtk".to_string(),
+            start_level: LevelSelection::Uid(0),
+        }
+    }
+}
 
 impl Plugin for GameFlowPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup)
-            .add_systems(Update, update_level_selection)
-            .add_systems(Update, restart_level);
+        app.insert_resource(RapierConfiguration {
+            gravity: Vec2::new(0.0, 0.0),
+            physics_pipeline_active: true,
+            query_pipeline_active: true,
+            timestep_mode: TimestepMode::Variable {
+                max_dt: 1.0 / 60.0,
+                time_scale: 1.0,
+                substeps: 1,
+            },
+            scaled_shape_subdivision: 10,
+            force_update_from_transform_changes: false,
+        })
+        .insert_resource(self.start_level.clone())
+        .insert_resource(LdtkSettings {
+            level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation {
+                load_level_neighbors: true,
+            },
+            set_clear_color: SetClearColor::FromLevelBackground,
+            ..Default::default()
+        })
+        .insert_resource(ProjectFile(self.project.clone()))
+        .add_event::<LevelEntered>()
+        .add_systems(Startup, setup)
+        .add_systems(Update, update_level_selection)
+        .add_systems(Update, restart_level)
+        .add_console_command(LevelCommand);
     }
 }
@@ -0,0 +1,74 @@
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+
+#[derive(Copy, Clone, PartialEq, Debug, Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health {
+            current: 100.,
+            max: 100.,
+        }
+    }
+}
+
+impl Health {
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+}
+
+/// Takes no damage, though it can still be healed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Invulnerable;
+
+/// Damage dealt to an entity with [`Health`]. Negative amounts heal.
+#[derive(Copy, Clone, PartialEq, Debug, Event)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+/// Sent once when an entity's health reaches zero.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Event)]
+pub struct Died {
+    pub entity: Entity,
+}
+
+pub fn apply_damage(
+    mut damage_events: EventReader<Damage>,
+    mut died_events: EventWriter<Died>,
+    mut query: Query<(&mut Health, Has<Invulnerable>)>,
+) {
+    for damage in damage_events.read() {
+        if let Ok((mut health, invulnerable)) = query.get_mut(damage.target) {
+            if invulnerable && damage.amount > 0. {
+                continue;
+            }
+            let was_dead = health.is_dead();
+            health.current = (health.current - damage.amount).clamp(0., health.max);
+
+            if health.is_dead() && !was_dead {
+                event!(Level::INFO, "Entity {:?} died", damage.target);
+                died_events.send(Died {
+                    entity: damage.target,
+                });
+            }
+        }
+    }
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>()
+            .add_event::<Died>()
+            .add_systems(PostUpdate, apply_damage);
+    }
+}
@@ -1,32 +1,142 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
-use crate::player::Player;
+use crate::{
+    console::{parse_arg, player_entity, ConsoleAppExt, ConsoleCommand},
+    survival::Consumables,
+};
 
 #[derive(Clone, Component, Debug, Eq, Default, PartialEq)]
 pub struct Inventory(Vec<String>);
 
 impl From<&EntityInstance> for Inventory {
     fn from(entity_instance: &EntityInstance) -> Self {
+        // entities without an `items` field start empty, like a project loaded with `--project`
         Inventory(
             entity_instance
                 .iter_enums_field("items")
-                .expect("items field should be correctly typed")
-                .cloned()
-                .collect(),
+                .map(|items| items.cloned().collect())
+                .unwrap_or_default(),
         )
     }
 }
 
-/// Prints the contents of the player's inventory.
-pub fn dbg_print_inventory(
-    input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&Inventory, &EntityInstance), With<Player>>,
-) {
-    for (items, entity_instance) in &mut query {
-        if input.just_pressed(KeyCode::KeyP) {
-            dbg!(&items);
-            dbg!(&entity_instance);
+impl Inventory {
+    pub fn contains(&self, item: &str) -> bool {
+        self.0.iter().any(|i| i == item)
+    }
+
+    pub fn count(&self, item: &str) -> usize {
+        self.0.iter().filter(|i| *i == item).count()
+    }
+
+    pub fn add(&mut self, item: impl Into<String>) {
+        self.0.push(item.into());
+    }
+
+    /// Removes one of an item, returns whether there was one.
+    pub fn remove(&mut self, item: &str) -> bool {
+        match self.0.iter().position(|i| i == item) {
+            Some(index) => {
+                self.0.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.0
+    }
+}
+
+/// Sent by whatever adds an item to or removes an item from an [`Inventory`].
+#[derive(Clone, Debug, Eq, PartialEq, Event)]
+pub struct InventoryChanged {
+    pub entity: Entity,
+    pub item: String,
+}
+
+/// `give <item> [count]`: adds items to the player's inventory.
+pub struct GiveCommand;
+
+impl ConsoleCommand for GiveCommand {
+    fn name(&self) -> &'static str {
+        "give"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<item> [count]"
+    }
+
+    fn complete(&self, world: &mut World, args: &[&str]) -> Vec<String> {
+        if args.len() > 1 {
+            return Vec::new();
         }
+        world
+            .get_resource::<Assets<Consumables>>()
+            .map(|consumables| {
+                consumables
+                    .iter()
+                    .flat_map(|(_, consumables)| consumables.consumables.iter().map(|consumable| consumable.item.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        let item = *args.first().ok_or("Missing item")?;
+        let count: usize = if args.len() > 1 { parse_arg(args, 1, "count")? } else { 1 };
+        let player = player_entity(world)?;
+        {
+            let mut inventory = world.get_mut::<Inventory>(player).ok_or("The player has no inventory")?;
+            for _ in 0..count {
+                inventory.add(item);
+            }
+        }
+        world.send_event(InventoryChanged {
+            entity: player,
+            item: item.to_string(),
+        });
+        Ok(format!("Gave {} {}", count, item))
+    }
+}
+
+/// `inventory`: lists what the player carries.
+pub struct InventoryCommand;
+
+impl ConsoleCommand for InventoryCommand {
+    fn name(&self) -> &'static str {
+        "inventory"
+    }
+
+    fn run(&self, world: &mut World, _: &[&str]) -> Result<String, String> {
+        let player = player_entity(world)?;
+        let inventory = world.get::<Inventory>(player).ok_or("The player has no inventory")?;
+        if inventory.items().is_empty() {
+            Ok("Nothing".to_string())
+        } else {
+            Ok(inventory.items().join(", "))
+        }
+    }
+}
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<InventoryChanged>()
+            .add_console_command(GiveCommand)
+            .add_console_command(InventoryCommand);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_items_field_is_empty() {
+        assert_eq!(Inventory::from(&EntityInstance::default()), Inventory::default());
     }
 }
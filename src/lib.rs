@@ -0,0 +1,134 @@
+use bevy::{app::PluginGroupBuilder, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+/// Level music, sound effects and volumes
+pub mod audio;
+pub mod camera;
+/// Scripted camera moves for story moments
+pub mod camera_script;
+pub mod character;
+/// Command line options of the game binary
+pub mod cli;
+pub mod climbing;
+/// In-game clock and day/night cycle
+pub mod clock;
+/// Named Rapier collision groups and their interaction matrix
+pub mod collision_layers;
+/// Bundles for auto-loading Rapier colliders as part of the level
+pub mod colliders;
+/// Developer console and the commands plugins register with it
+pub mod console;
+/// Debug overlays and the world inspector
+#[cfg(feature = "debug")]
+pub mod debug;
+/// Dialogue boxes for timeline actions with text and responses
+pub mod dialogue;
+/// Money, shops and barter
+pub mod economy;
+pub mod enemy;
+/// Handles initialization and switching levels
+pub mod game_flow;
+pub mod health;
+pub mod inventory;
+/// Log filters, files, rotation and retention
+pub mod logging;
+pub mod menu;
+pub mod misc_objects;
+/// CPU driven sprite particles
+pub mod particles;
+/// Asset, user data, config and log folders
+pub mod paths;
+/// Per-level top-down or side-view physics
+pub mod physics_mode;
+/// Low resolution rendering with integer upscaling
+pub mod pixel_perfect;
+pub mod player;
+/// Quests, their objectives and the quest log
+pub mod quest;
+/// Seeded random numbers
+pub mod random;
+/// Save slots
+pub mod save;
+/// Player settings file
+pub mod settings;
+/// Scripted commands run from timeline actions
+pub mod sequence;
+/// Story flags, counters and conditions shared across timelines
+pub mod story;
+/// Thirst, hunger and fatigue, and the items that restore them
+pub mod survival;
+/// Gameplay properties of IntGrid values
+pub mod terrain;
+/// Headless app harness for integration tests
+#[cfg(any(test, feature = "test_support"))]
+pub mod test_support;
+pub mod timeline;
+pub mod walls;
+pub mod water;
+/// Per-level weather affecting visibility and speed
+pub mod weather;
+
+/// Every plugin the game is made of, in the order they need to be added.
+///
+/// Sub-plugins can be configured or left out through the usual
+/// [`PluginGroup`] methods:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use idaho::{IdahoPlugins, game_flow::GameFlowPlugin, enemy::EnemyPlugin};
+/// App::new()
+///     .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+///     .add_plugins(
+///         IdahoPlugins
+///             .set(GameFlowPlugin::new("my_level.ldtk"))
+///             .disable::<EnemyPlugin>(),
+///     )
+///     .run();
+/// ```
+pub struct IdahoPlugins;
+
+impl PluginGroup for IdahoPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(paths::PathsPlugin)
+            .add(random::RandomPlugin::default())
+            .add(settings::SettingsPlugin::default())
+            .add(LdtkPlugin)
+            .add(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+            .add(timeline::TimelinePlugin)
+            .add(story::StoryPlugin)
+            .add(clock::ClockPlugin)
+            .add(sequence::SequencePlugin)
+            .add(dialogue::DialoguePlugin)
+            .add(quest::QuestPlugin)
+            .add(save::SavePlugin)
+            .add(game_flow::GameFlowPlugin::default())
+            .add(collision_layers::CollisionLayerPlugin)
+            .add(colliders::ColliderPlugin)
+            .add(physics_mode::PhysicsModePlugin)
+            .add(health::HealthPlugin)
+            .add(terrain::TerrainPlugin)
+            .add(walls::WallPlugin)
+            .add(water::WaterPlugin)
+            .add(particles::ParticlePlugin)
+            .add(weather::WeatherPlugin)
+            .add(climbing::ClimbingPlugin)
+            .add(player::PlayerPlugin)
+            .add(enemy::EnemyPlugin)
+            .add(character::CharacterPlugin)
+            .add(misc_objects::MiscObjectsPlugin)
+            .add(inventory::InventoryPlugin)
+            .add(economy::EconomyPlugin)
+            .add(survival::SurvivalPlugin)
+            .add(camera::CameraPlugin)
+            .add(pixel_perfect::PixelPerfectPlugin)
+            .add(camera_script::CameraScriptPlugin)
+            .add(audio::GameAudioPlugin)
+            .add(menu::MenuPlugin)
+            .add(console::ConsolePlugin);
+        #[cfg(feature = "debug")]
+        let group = group.add(debug::DebugPlugin::default());
+        group
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::Layer;
+
+use bevy::{
+    log::{BoxedLayer, LogPlugin},
+    prelude::*,
+};
+
+use crate::paths::GamePaths;
+
+/// Prefix of log file names, followed by the date and hour of the rotation.
+pub const LOG_FILE_PREFIX: &str = "idaho.log";
+
+/// Filter used when neither `RUST_LOG` nor `--log-filter` is given.
+pub const DEFAULT_LOG_FILTER: &str = "info,wgpu=error,naga=warn";
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum LogRotation {
+    Minutely,
+    #[default]
+    Hourly,
+    Daily,
+    Never,
+}
+
+impl FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "minutely" => Ok(LogRotation::Minutely),
+            "hourly" => Ok(LogRotation::Hourly),
+            "daily" => Ok(LogRotation::Daily),
+            "never" => Ok(LogRotation::Never),
+            _ => Err(format!("Invalid log rotation: {}", s)),
+        }
+    }
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum LogFormat {
+    Pretty,
+    #[default]
+    Compact,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "compact" => Ok(LogFormat::Compact),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Invalid log format: {}", s)),
+        }
+    }
+}
+
+/// How the game logs, on top of Bevy's console output.
+///
+/// Insert it before adding Bevy's [`LogPlugin`] from [`LogSettings::log_plugin`],
+/// which reads it back to set up the log file.
+#[derive(Clone, Debug, PartialEq, Resource)]
+pub struct LogSettings {
+    /// `RUST_LOG` style filter like `info,idaho::sequence=debug`, overridden by `RUST_LOG` itself
+    pub filter: String,
+    /// Folder of the log files, the [`GamePaths`] log folder if `None`
+    pub directory: Option<PathBuf>,
+    pub rotation: LogRotation,
+    /// Format of the log files
+    pub format: LogFormat,
+    /// Log files older than this many days are deleted on startup, none if `None`
+    pub retention_days: Option<u64>,
+    /// Whether to write log files at all
+    pub to_file: bool,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            filter: DEFAULT_LOG_FILTER.to_string(),
+            directory: None,
+            rotation: LogRotation::default(),
+            format: LogFormat::default(),
+            retention_days: Some(7),
+            to_file: true,
+        }
+    }
+}
+
+impl LogSettings {
+    /// Applies a command line option, returning `Ok(false)` if it isn't a logging option.
+    ///
+    /// The option's value is taken from `values` only by options which need one.
+    pub fn apply_arg(&mut self, flag: &str, values: &mut impl Iterator<Item = String>) -> Result<bool, String> {
+        let mut value = || values.next().ok_or_else(|| format!("Missing value for {}", flag));
+        match flag {
+            "--log-filter" => self.filter = value()?,
+            "--log-dir" => self.directory = Some(PathBuf::from(value()?)),
+            "--log-rotation" => self.rotation = value()?.parse()?,
+            "--log-format" => self.format = value()?.parse()?,
+            "--log-retention-days" => {
+                let days = value()?;
+                self.retention_days = Some(days.parse().map_err(|_| format!("Invalid number of days: {}", days))?);
+            }
+            "--no-log-file" => self.to_file = false,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Reads logging options from command line arguments, rejecting anything else.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut settings = LogSettings::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            if !settings.apply_arg(&flag, &mut args)? {
+                return Err(format!("Unknown option: {}", flag));
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Bevy's log plugin, set up to also write log files as configured.
+    pub fn log_plugin(&self) -> LogPlugin {
+        LogPlugin {
+            filter: self.filter.clone(),
+            custom_layer: file_layer,
+            ..default()
+        }
+    }
+}
+
+/// Deletes log files in `directory` last written more than `max_age` ago, returning how many.
+pub fn remove_old_logs(directory: &Path, max_age: Duration) -> usize {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return 0;
+    };
+    let now = SystemTime::now();
+
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(LOG_FILE_PREFIX))
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age > max_age)
+        })
+        .filter(|entry| std::fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+/// Log file layer for [`LogPlugin::custom_layer`], built from the [`LogSettings`] resource.
+///
+/// This runs while the global logger is being set up, so problems go to stderr.
+pub fn file_layer(app: &mut App) -> Option<BoxedLayer> {
+    let settings = app.world().get_resource::<LogSettings>().cloned().unwrap_or_default();
+    if !settings.to_file {
+        return None;
+    }
+    let directory = settings.directory.clone().unwrap_or_else(|| {
+        app.world()
+            .get_resource::<GamePaths>()
+            .cloned()
+            .unwrap_or_else(GamePaths::from_env)
+            .logs
+    });
+
+    if let Some(days) = settings.retention_days {
+        remove_old_logs(&directory, Duration::from_secs(days * 24 * 60 * 60));
+    }
+
+    let appender = match RollingFileAppender::builder()
+        .rotation(settings.rotation.into())
+        .filename_prefix(LOG_FILE_PREFIX)
+        .build(&directory)
+    {
+        Ok(appender) => appender,
+        Err(e) => {
+            eprintln!("Could not log to {}: {}", directory.display(), e);
+            return None;
+        }
+    };
+
+    let layer = tracing_subscriber::fmt::layer().with_writer(appender).with_ansi(false);
+    Some(match settings.format {
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logging_options_are_parsed() {
+        let args = ["--log-format", "json", "--log-rotation", "daily", "--log-filter", "idaho=debug"];
+        let settings = LogSettings::from_args(args.map(String::from)).unwrap();
+        assert_eq!(settings.format, LogFormat::Json);
+        assert_eq!(settings.rotation, LogRotation::Daily);
+        assert_eq!(settings.filter, "idaho=debug");
+
+        assert!(LogSettings::from_args(["--log-format".to_string()]).is_err());
+        assert!(LogSettings::from_args(["--log-rotation", "weekly"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn only_old_logs_are_removed() {
+        let directory = std::env::temp_dir().join(format!("idaho_log_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let ten_days_ago = SystemTime::now() - Duration::from_secs(10 * 24 * 60 * 60);
+        for name in [format!("{}.2024-01-01-00", LOG_FILE_PREFIX), "notes.txt".to_string()] {
+            let file = std::fs::File::create(directory.join(name)).unwrap();
+            file.set_modified(ten_days_ago).unwrap();
+        }
+        std::fs::write(directory.join(format!("{}.2024-01-10-00", LOG_FILE_PREFIX)), "new").unwrap();
+
+        assert_eq!(remove_old_logs(&directory, Duration::from_secs(7 * 24 * 60 * 60)), 1);
+        assert!(directory.join("notes.txt").exists());
+        assert!(directory.join(format!("{}.2024-01-10-00", LOG_FILE_PREFIX)).exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
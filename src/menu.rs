@@ -3,6 +3,8 @@ use tracing::{event, Level};
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
+use crate::settings::{Settings, SettingsSummary};
+
 #[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum GameState {
     #[default]
@@ -23,16 +25,19 @@ impl Plugin for MenuPlugin {
             .add_systems(Update, toggle_pause)
             .add_systems(OnEnter(GameState::Paused), setup_pause_menu)
             .add_systems(OnExit(GameState::Paused), cleanup_pause_menu)
+            .add_systems(Update, pause_physics.run_if(in_state(GameState::Paused)))
+            .add_systems(OnEnter(GameState::Playing), resume_physics)
             .add_systems(Startup, startup);
     }
 }
 
 fn toggle_pause(
     keyboard: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
     mut game_state: ResMut<NextState<GameState>>,
     current_state: Res<State<GameState>>,
 ) {
-    if keyboard.just_pressed(KeyCode::KeyM) {
+    if keyboard.just_pressed(settings.controls.pause.0) {
         event!(Level::INFO, "Toggling pause");
         match current_state.get() {
             GameState::Playing => game_state.set(GameState::Paused),
@@ -70,6 +75,17 @@ fn setup_pause_menu(mut commands: Commands) {
                     },
                 ),
             );
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                SettingsSummary,
+            ));
         });
 }
 
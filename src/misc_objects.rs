@@ -1,8 +1,75 @@
-use crate::{player::Player, character::Character};
+use crate::{
+    player::{InputLock, Player},
+    character::{Character, INTERACTION_DISTANCE},
+    settings::Settings,
+};
 use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
 
 pub struct MiscObjectsPlugin;
 
+/// Chests and doors, opened with the interact key.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Component)]
+pub enum Openable {
+    Chest,
+    Door,
+}
+
+impl Openable {
+    /// Kind of an LDtk entity, by its identifier.
+    pub fn from_identifier(identifier: &str) -> Option<Self> {
+        match identifier {
+            "Chest" => Some(Openable::Chest),
+            "Door" => Some(Openable::Door),
+            _ => None,
+        }
+    }
+}
+
+/// Sent when the player opens a chest or a door.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Event)]
+pub struct OpenObject {
+    pub entity: Entity,
+    pub kind: Openable,
+}
+
+pub fn add_openables(mut commands: Commands, entity_query: Query<(Entity, &EntityInstance), Added<EntityInstance>>) {
+    for (entity, entity_instance) in &entity_query {
+        if let Some(openable) = Openable::from_identifier(&entity_instance.identifier) {
+            commands.entity(entity).insert(openable);
+        }
+    }
+}
+
+/// Pressing the interact key opens the closest chest or door in reach.
+pub fn open_objects(
+    input: Res<ButtonInput<KeyCode>>,
+    input_lock: Res<InputLock>,
+    settings: Res<Settings>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    object_query: Query<(Entity, &GlobalTransform, &Openable)>,
+    mut open_events: EventWriter<OpenObject>,
+) {
+    if input_lock.is_locked() || !input.just_pressed(settings.controls.interact.0) {
+        return;
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    let closest = object_query
+        .iter()
+        .map(|(entity, transform, openable)| {
+            (entity, *openable, transform.translation().distance(player_transform.translation()))
+        })
+        .filter(|(_, _, distance)| *distance <= INTERACTION_DISTANCE)
+        .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
+
+    if let Some((entity, kind, _)) = closest {
+        open_events.send(OpenObject { entity, kind });
+    }
+}
+
 #[derive(Component)]
 pub struct FloatingInteractionIndicator {
     pub trigger_distance: f32,
@@ -110,7 +177,7 @@ pub fn spawn_interaction_indicator(
                 ..default()
             },
             FloatingInteractionIndicator{
-                trigger_distance: 45.0,
+                trigger_distance: INTERACTION_DISTANCE,
             },
             InteractionIndicator::default(),
         ));
@@ -119,8 +186,10 @@ pub fn spawn_interaction_indicator(
 
 impl Plugin for MiscObjectsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
-            update_interaction_indicators, animate_interaction_indicators
-        ));
+        app.add_event::<OpenObject>()
+            .add_systems(Update, (
+                update_interaction_indicators, animate_interaction_indicators,
+                (add_openables, open_objects).chain(),
+            ));
     }
 }
\ No newline at end of file
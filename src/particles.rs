@@ -0,0 +1,279 @@
+use rand::Rng;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{console::ConsoleAppExt, random::GameRng};
+
+/// Z of particles, above the level and its entities
+pub const PARTICLE_Z: f32 = 50.;
+
+/// How an emitter spawns and animates its particles.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParticleEffect {
+    /// Particles spawned per second
+    pub rate: f32,
+    /// Seconds each particle lives
+    pub lifetime: f32,
+    /// Half size of the rectangle particles spawn in, around the emitter
+    pub area: Vec2,
+    pub velocity: Vec2,
+    /// Random variation added to `velocity`, in both directions
+    pub velocity_spread: Vec2,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+    /// Emitting pauses while this many particles are alive
+    pub max_particles: usize,
+}
+
+impl ParticleEffect {
+    pub fn dust_storm() -> Self {
+        ParticleEffect {
+            rate: 120.,
+            lifetime: 2.5,
+            area: Vec2::new(200., 110.),
+            velocity: Vec2::new(-140., -10.),
+            velocity_spread: Vec2::new(40., 20.),
+            start_color: Color::srgba(0.85, 0.65, 0.4, 0.7),
+            end_color: Color::srgba(0.85, 0.65, 0.4, 0.),
+            start_size: 2.,
+            end_size: 4.,
+            max_particles: 400,
+        }
+    }
+
+    pub fn rain() -> Self {
+        ParticleEffect {
+            rate: 150.,
+            lifetime: 0.8,
+            area: Vec2::new(200., 110.),
+            velocity: Vec2::new(-20., -300.),
+            velocity_spread: Vec2::new(5., 30.),
+            start_color: Color::srgba(0.6, 0.7, 0.9, 0.8),
+            end_color: Color::srgba(0.6, 0.7, 0.9, 0.3),
+            start_size: 1.,
+            end_size: 1.,
+            max_particles: 200,
+        }
+    }
+
+    pub fn heat_shimmer() -> Self {
+        ParticleEffect {
+            rate: 20.,
+            lifetime: 3.,
+            area: Vec2::new(180., 90.),
+            velocity: Vec2::new(0., 8.),
+            velocity_spread: Vec2::new(4., 3.),
+            start_color: Color::srgba(1.0, 0.95, 0.85, 0.15),
+            end_color: Color::srgba(1.0, 0.95, 0.85, 0.),
+            start_size: 6.,
+            end_size: 10.,
+            max_particles: 80,
+        }
+    }
+
+    pub fn campfire_smoke() -> Self {
+        ParticleEffect {
+            rate: 6.,
+            lifetime: 3.,
+            area: Vec2::new(3., 1.),
+            velocity: Vec2::new(4., 18.),
+            velocity_spread: Vec2::new(4., 4.),
+            start_color: Color::srgba(0.4, 0.4, 0.4, 0.6),
+            end_color: Color::srgba(0.7, 0.7, 0.7, 0.),
+            start_size: 2.,
+            end_size: 7.,
+            max_particles: 30,
+        }
+    }
+
+    /// Color and size of a particle `t` of the way through its life.
+    pub fn appearance(&self, t: f32) -> (Color, f32) {
+        let color = Srgba::from(self.start_color).mix(&Srgba::from(self.end_color), t);
+        let size = self.start_size + (self.end_size - self.start_size) * t;
+        (color.into(), size)
+    }
+}
+
+/// Spawns particles around its entity while active.
+#[derive(Clone, PartialEq, Debug, Component)]
+pub struct ParticleEmitter {
+    pub effect: ParticleEffect,
+    pub active: bool,
+    /// Fraction of a particle carried over to the next frame
+    accumulator: f32,
+}
+
+impl ParticleEmitter {
+    pub fn new(effect: ParticleEffect) -> Self {
+        ParticleEmitter {
+            effect,
+            active: true,
+            accumulator: 0.,
+        }
+    }
+
+    /// Number of particles to spawn after `dt` seconds.
+    pub fn tick(&mut self, dt: f32) -> usize {
+        if !self.active {
+            self.accumulator = 0.;
+            return 0;
+        }
+        self.accumulator += self.effect.rate * dt;
+        let count = self.accumulator.floor();
+        self.accumulator -= count;
+        count as usize
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Component)]
+pub struct Particle {
+    pub emitter: Entity,
+    pub velocity: Vec2,
+    pub age: f32,
+}
+
+/// Campfire placed in LDtk, giving off smoke.
+#[derive(Clone, Bundle)]
+pub struct CampfireBundle {
+    pub emitter: ParticleEmitter,
+    pub spatial: SpatialBundle,
+}
+
+impl Default for CampfireBundle {
+    fn default() -> Self {
+        CampfireBundle {
+            emitter: ParticleEmitter::new(ParticleEffect::campfire_smoke()),
+            spatial: SpatialBundle::default(),
+        }
+    }
+}
+
+impl LdtkEntity for CampfireBundle {
+    fn bundle_entity(
+        _: &EntityInstance,
+        _: &LayerInstance,
+        _: Option<&Handle<Image>>,
+        _: Option<&TilesetDefinition>,
+        _: &AssetServer,
+        _: &mut Assets<TextureAtlasLayout>,
+    ) -> CampfireBundle {
+        CampfireBundle::default()
+    }
+}
+
+pub fn emit_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rng: ResMut<GameRng>,
+    mut emitter_query: Query<(Entity, &mut ParticleEmitter, &GlobalTransform, &InheritedVisibility)>,
+    particle_query: Query<&Particle>,
+) {
+    let mut alive: HashMap<Entity, usize> = HashMap::new();
+    for particle in &particle_query {
+        *alive.entry(particle.emitter).or_default() += 1;
+    }
+
+    let rng = &mut rng.0;
+    for (entity, mut emitter, transform, visibility) in &mut emitter_query {
+        let count = emitter.tick(time.delta_seconds());
+        // emitters hidden by a story gate stay quiet
+        if !visibility.get() {
+            continue;
+        }
+        let room = emitter
+            .effect
+            .max_particles
+            .saturating_sub(alive.get(&entity).copied().unwrap_or(0));
+        let effect = &emitter.effect;
+        let origin = transform.translation().truncate();
+
+        for _ in 0..count.min(room) {
+            let offset = Vec2::new(
+                rng.gen_range(-1.0..=1.0) * effect.area.x,
+                rng.gen_range(-1.0..=1.0) * effect.area.y,
+            );
+            let velocity = effect.velocity
+                + Vec2::new(
+                    rng.gen_range(-1.0..=1.0) * effect.velocity_spread.x,
+                    rng.gen_range(-1.0..=1.0) * effect.velocity_spread.y,
+                );
+            let (color, size) = effect.appearance(0.);
+
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::splat(size)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation((origin + offset).extend(PARTICLE_Z)),
+                    ..default()
+                },
+                Particle {
+                    emitter: entity,
+                    velocity,
+                    age: 0.,
+                },
+            ));
+        }
+    }
+}
+
+pub fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    emitter_query: Query<&ParticleEmitter>,
+    mut particle_query: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite) in &mut particle_query {
+        let Ok(emitter) = emitter_query.get(particle.emitter) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        particle.age += dt;
+        if particle.age >= emitter.effect.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.translation += (particle.velocity * dt).extend(0.);
+        let (color, size) = emitter.effect.appearance(particle.age / emitter.effect.lifetime);
+        sprite.color = color;
+        sprite.custom_size = Some(Vec2::splat(size));
+    }
+}
+
+/// Plugin for CPU driven sprite particles and LDtk campfires.
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (emit_particles, update_particles).chain())
+            .register_ldtk_entity::<CampfireBundle>("Campfire")
+            .add_console_spawnable::<CampfireBundle>("Campfire");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emitter_carries_over_partial_particles() {
+        let mut emitter = ParticleEmitter::new(ParticleEffect {
+            rate: 4.,
+            ..ParticleEffect::campfire_smoke()
+        });
+        assert_eq!(emitter.tick(0.125), 0);
+        assert_eq!(emitter.tick(0.125), 1);
+        assert_eq!(emitter.tick(0.625), 2);
+
+        emitter.active = false;
+        assert_eq!(emitter.tick(1.), 0);
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::{Component, Path, PathBuf};
+
+use path_clean::PathClean;
+use thiserror::Error;
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+
+/// Folder name used inside the platform's config and data directories.
+pub const APP_FOLDER: &str = "idaho";
+
+/// Why a relative path from game data was refused.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum PathError {
+    #[error("path is empty")]
+    Empty,
+    #[error("path {0} is absolute")]
+    Absolute(String),
+    #[error("path {0} leaves its folder")]
+    Escapes(String),
+}
+
+/// Normalizes a relative path coming from game data, like an LDtk field.
+///
+/// `a/./b/../c` becomes `a/c`, while absolute paths and paths climbing out of
+/// their folder with `..` are rejected.
+pub fn sanitize(relative: &str) -> Result<PathBuf, PathError> {
+    if relative.trim().is_empty() {
+        return Err(PathError::Empty);
+    }
+    let path = Path::new(relative);
+    if path.has_root() || path.is_absolute() {
+        return Err(PathError::Absolute(relative.to_string()));
+    }
+
+    let cleaned = path.clean();
+    match cleaned.components().next() {
+        Some(Component::ParentDir) => Err(PathError::Escapes(relative.to_string())),
+        Some(Component::Normal(_)) => Ok(cleaned),
+        _ => Err(PathError::Empty),
+    }
+}
+
+/// Where the game reads its assets from and writes its saves, settings and logs.
+#[derive(Clone, Debug, Eq, PartialEq, Resource)]
+pub struct GamePaths {
+    /// Read-only assets, the same folder Bevy's asset server loads from
+    pub asset_root: PathBuf,
+    /// Writable per-user data like save slots
+    pub user_data: PathBuf,
+    pub config: PathBuf,
+    pub logs: PathBuf,
+}
+
+impl Default for GamePaths {
+    fn default() -> Self {
+        GamePaths::from_env()
+    }
+}
+
+impl GamePaths {
+    /// Resolves every folder from the environment, following each platform's conventions.
+    pub fn from_env() -> Self {
+        let user_data = data_dir()
+            .map(|folder| folder.join(APP_FOLDER))
+            .unwrap_or_else(|| PathBuf::from("."));
+        let config = config_dir()
+            .map(|folder| folder.join(APP_FOLDER))
+            .unwrap_or_else(|| user_data.clone());
+
+        GamePaths {
+            asset_root: asset_base().join("assets"),
+            logs: user_data.join("logs"),
+            user_data,
+            config,
+        }
+    }
+
+    /// All folders inside `root`, for tests and portable installs.
+    pub fn portable(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        GamePaths {
+            asset_root: root.join("assets"),
+            user_data: root.join("data"),
+            config: root.join("config"),
+            logs: root.join("logs"),
+        }
+    }
+
+    /// Path of an asset given relative to the asset root.
+    pub fn asset(&self, relative: &str) -> Result<PathBuf, PathError> {
+        Ok(self.asset_root.join(sanitize(relative)?))
+    }
+
+    /// Path of a file given relative to the user data folder.
+    pub fn user_file(&self, relative: &str) -> Result<PathBuf, PathError> {
+        Ok(self.user_data.join(sanitize(relative)?))
+    }
+
+    pub fn saves(&self) -> PathBuf {
+        self.user_data.join("saves")
+    }
+}
+
+/// Folder containing `assets`, found the same way as Bevy's file asset reader.
+fn asset_base() -> PathBuf {
+    if let Some(root) = std::env::var_os("BEVY_ASSET_ROOT") {
+        PathBuf::from(root)
+    } else if let Some(manifest_dir) = std::env::var_os("CARGO_MANIFEST_DIR") {
+        PathBuf::from(manifest_dir)
+    } else {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+fn config_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home_dir().map(|home| home.join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".config")))
+    }
+}
+
+fn data_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home_dir().map(|home| home.join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".local/share")))
+    }
+}
+
+/// Plugin which makes [`GamePaths`] available, resolving them from the environment unless
+/// they were inserted beforehand.
+pub struct PathsPlugin;
+
+impl Plugin for PathsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<GamePaths>() {
+            app.insert_resource(GamePaths::from_env());
+        }
+        let paths = app.world().resource::<GamePaths>();
+        event!(
+            Level::INFO,
+            "Game paths | assets {} | data {} | config {} | logs {}",
+            paths.asset_root.display(),
+            paths.user_data.display(),
+            paths.config.display(),
+            paths.logs.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_normalizes_and_rejects_escapes() {
+        assert_eq!(sanitize("timelines/./a/../b.xml"), Ok(PathBuf::from("timelines/b.xml")));
+        assert_eq!(
+            sanitize("timelines/../../secret"),
+            Err(PathError::Escapes("timelines/../../secret".to_string()))
+        );
+        assert_eq!(sanitize("../x"), Err(PathError::Escapes("../x".to_string())));
+        assert_eq!(sanitize("/etc/passwd"), Err(PathError::Absolute("/etc/passwd".to_string())));
+        assert_eq!(sanitize("a/.."), Err(PathError::Empty));
+        assert_eq!(sanitize(""), Err(PathError::Empty));
+    }
+}
@@ -0,0 +1,190 @@
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    collision_layers::{cast_ray_on_layers, CollisionLayer},
+    game_flow::update_level_selection,
+};
+
+/// World gravity in side-view levels, in pixels per second squared
+pub const SIDE_VIEW_GRAVITY: Vec2 = Vec2::new(0., -981.);
+
+/// Vertical speed given by a jump in side-view levels
+pub const JUMP_SPEED: f32 = 300.;
+
+/// How the current level is played, set by the `physics` enum field of the LDtk level.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Resource)]
+pub enum PhysicsMode {
+    /// No gravity and 8-way movement
+    #[default]
+    TopDown,
+    /// Gravity, jumping and ladder climbing
+    SideView,
+}
+
+impl PhysicsMode {
+    /// Reads the `physics` field of an LDtk level, top-down if unset.
+    pub fn from_level(level: &bevy_ecs_ldtk::ldtk::Level) -> Self {
+        match level.get_enum_field("physics").map(String::as_str) {
+            Ok("Side_view") => PhysicsMode::SideView,
+            Ok("Top_down") | Err(_) => PhysicsMode::TopDown,
+            Ok(other) => {
+                event!(Level::WARN, "Unknown physics mode {} on level {}", other, level.identifier);
+                PhysicsMode::TopDown
+            }
+        }
+    }
+
+    pub fn gravity(&self) -> Vec2 {
+        match self {
+            PhysicsMode::TopDown => Vec2::ZERO,
+            PhysicsMode::SideView => SIDE_VIEW_GRAVITY,
+        }
+    }
+}
+
+/// Whether a side-view entity stands on something.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct GroundDetection {
+    pub on_ground: bool,
+}
+
+/// Distance below an entity's center that still counts as ground
+pub const GROUND_PROBE_LENGTH: f32 = 10.;
+
+/// Switches gravity whenever the selected level's physics mode differs from the current one.
+pub fn apply_level_physics(
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut physics_mode: ResMut<PhysicsMode>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut velocity_query: Query<&mut Velocity, With<GroundDetection>>,
+) {
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+
+    let Some(level) = ldtk_project
+        .iter_raw_levels()
+        .find(|level| level_selection.is_match(&LevelIndices::default(), level))
+    else {
+        return;
+    };
+
+    let mode = PhysicsMode::from_level(level);
+    if *physics_mode != mode {
+        event!(Level::INFO, "Level {} uses {:?} physics", level.identifier, mode);
+        *physics_mode = mode;
+        rapier_config.gravity = mode.gravity();
+
+        if mode == PhysicsMode::TopDown {
+            // don't carry a fall or a jump into a level without gravity
+            for mut velocity in &mut velocity_query {
+                velocity.linvel.y = 0.;
+            }
+        }
+    }
+}
+
+pub fn detect_ground(
+    rapier_context: Res<RapierContext>,
+    mut query: Query<(&GlobalTransform, &mut GroundDetection)>,
+) {
+    for (transform, mut ground_detection) in &mut query {
+        let on_ground = cast_ray_on_layers(
+            &rapier_context,
+            transform.translation().truncate(),
+            Vec2::NEG_Y,
+            GROUND_PROBE_LENGTH,
+            &[CollisionLayer::Wall, CollisionLayer::Npc, CollisionLayer::Pickup],
+        )
+        .is_some();
+
+        if ground_detection.on_ground != on_ground {
+            ground_detection.on_ground = on_ground;
+        }
+    }
+}
+
+/// Plugin which switches between top-down and side-view physics per level.
+pub struct PhysicsModePlugin;
+
+impl Plugin for PhysicsModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsMode>()
+            .add_systems(
+                Update,
+                apply_level_physics.after(update_level_selection),
+            )
+            .add_systems(
+                Update,
+                detect_ground.run_if(resource_equals(PhysicsMode::SideView)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::ldtk::{FieldInstance, FieldValue};
+
+    use super::*;
+    use crate::test_support::TestApp;
+
+    fn level_with_physics(physics: Option<&str>) -> bevy_ecs_ldtk::ldtk::Level {
+        let field_instances = physics
+            .map(|physics| FieldInstance {
+                identifier: "physics".to_string(),
+                tile: None,
+                field_instance_type: "LocalEnum.PhysicsMode".to_string(),
+                value: FieldValue::Enum(Some(physics.to_string())),
+                def_uid: 0,
+                real_editor_values: Vec::new(),
+            })
+            .into_iter()
+            .collect();
+        bevy_ecs_ldtk::ldtk::Level {
+            field_instances,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn mode_is_read_from_the_physics_field() {
+        assert_eq!(
+            PhysicsMode::from_level(&level_with_physics(Some("Side_view"))),
+            PhysicsMode::SideView
+        );
+        assert_eq!(
+            PhysicsMode::from_level(&level_with_physics(Some("Top_down"))),
+            PhysicsMode::TopDown
+        );
+        assert_eq!(
+            PhysicsMode::from_level(&level_with_physics(Some("Underwater"))),
+            PhysicsMode::TopDown
+        );
+        assert_eq!(PhysicsMode::from_level(&level_with_physics(None)), PhysicsMode::TopDown);
+    }
+
+    #[test]
+    fn gravity_follows_the_selected_level() {
+        let mut app = TestApp::new();
+        app.load_level();
+        assert_eq!(app.world().resource::<RapierConfiguration>().gravity, Vec2::ZERO);
+
+        // the fixture level has no physics field, so it is played top-down
+        *app.world().resource_mut::<PhysicsMode>() = PhysicsMode::SideView;
+        app.world().resource_mut::<RapierConfiguration>().gravity = SIDE_VIEW_GRAVITY;
+        app.advance(1);
+
+        assert_eq!(*app.world().resource::<PhysicsMode>(), PhysicsMode::TopDown);
+        assert_eq!(app.world().resource::<RapierConfiguration>().gravity, Vec2::ZERO);
+    }
+}
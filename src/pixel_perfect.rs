@@ -0,0 +1,345 @@
+use tracing::{event, Level};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{RenderTarget, Viewport},
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        texture::ImageSampler,
+        view::{RenderLayers, VisibilitySystems},
+    },
+    transform::TransformSystem,
+    window::{PrimaryWindow, WindowResized, WindowScaleFactorChanged},
+};
+
+use crate::camera::{spawn_camera, CameraController, CameraSettings};
+
+/// Extra texels rendered around the virtual screen, so that sub-pixel
+/// scrolling never shows the edge of the render target.
+pub const CANVAS_MARGIN: u32 = 1;
+
+/// Render layer of the upscaled canvas, nothing else should be drawn on it.
+pub const CANVAS_LAYER: usize = 1;
+
+#[derive(Clone, PartialEq, Debug, Resource)]
+pub struct PixelPerfectSettings {
+    /// Virtual resolution the world is rendered at, one texel per world pixel
+    pub resolution: UVec2,
+    /// Fit the whole virtual screen in the window with black bars,
+    /// or fill the window and crop the edges
+    pub letterbox: bool,
+    /// Round sprite positions to whole world pixels
+    pub snap_sprites: bool,
+}
+
+impl Default for PixelPerfectSettings {
+    fn default() -> Self {
+        PixelPerfectSettings {
+            resolution: UVec2::new(320, 176),
+            letterbox: true,
+            snap_sprites: true,
+        }
+    }
+}
+
+/// The low resolution image the world camera renders to.
+#[derive(Resource)]
+pub struct PixelCanvasImage(pub Handle<Image>);
+
+/// Sprite showing [`PixelCanvasImage`] upscaled in the window.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct PixelCanvas;
+
+/// Camera drawing the [`PixelCanvas`] to the window.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct CanvasCamera;
+
+/// Current integer upscaling factor and the sub-pixel part of the camera position.
+#[derive(Copy, Clone, PartialEq, Debug, Resource)]
+pub struct PixelScale {
+    /// Physical window pixels per texel
+    pub scale: u32,
+    /// Physical pixels per logical pixel of the window, which the canvas sprite is sized in
+    pub scale_factor: f32,
+    /// Fraction of a texel the world camera was rounded by
+    pub sub_pixel: Vec2,
+}
+
+impl Default for PixelScale {
+    fn default() -> Self {
+        PixelScale {
+            scale: 1,
+            scale_factor: 1.,
+            sub_pixel: Vec2::ZERO,
+        }
+    }
+}
+
+impl PixelScale {
+    /// Logical window pixels per texel, the scale of the canvas sprite.
+    pub fn logical_scale(&self) -> f32 {
+        self.scale as f32 / self.scale_factor
+    }
+}
+
+/// Largest whole number of window pixels per texel.
+///
+/// With `letterbox` the virtual screen fits inside the window, otherwise it covers it.
+pub fn integer_scale(window_size: UVec2, resolution: UVec2, letterbox: bool) -> u32 {
+    let ratio = window_size.as_vec2() / resolution.max(UVec2::ONE).as_vec2();
+    let scale = if letterbox {
+        ratio.min_element().floor()
+    } else {
+        ratio.max_element().ceil()
+    };
+    (scale as u32).max(1)
+}
+
+fn canvas_size(settings: &PixelPerfectSettings) -> UVec2 {
+    settings.resolution + UVec2::splat(CANVAS_MARGIN * 2)
+}
+
+/// Points the world camera at a low resolution image and spawns the canvas showing it.
+pub fn setup_pixel_canvas(
+    mut commands: Commands,
+    settings: Res<PixelPerfectSettings>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut world_camera_query: Query<&mut Camera, With<CameraController>>,
+) {
+    let size = canvas_size(&settings);
+    let extent = Extent3d {
+        width: size.x,
+        height: size.y,
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("pixel_canvas"),
+            size: extent,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        sampler: ImageSampler::nearest(),
+        ..default()
+    };
+    image.resize(extent);
+    let handle = images.add(image);
+
+    // one world pixel per texel, letterboxing happens on the canvas instead
+    if let Some(aspect_ratio) = camera_settings.letterbox_aspect_ratio.take() {
+        event!(
+            Level::WARN,
+            "Ignoring the camera letterbox aspect ratio, the pixel canvas letterboxes instead | {}",
+            aspect_ratio
+        );
+    }
+    if camera_settings.view_height != size.y as f32 {
+        event!(
+            Level::INFO,
+            "Camera view height set to the pixel canvas | {} -> {}",
+            camera_settings.view_height,
+            size.y
+        );
+        camera_settings.view_height = size.y as f32;
+    }
+
+    for mut camera in &mut world_camera_query {
+        camera.target = RenderTarget::Image(handle.clone());
+        camera.order = -1;
+    }
+
+    commands.spawn((
+        SpriteBundle {
+            texture: handle.clone(),
+            ..default()
+        },
+        PixelCanvas,
+        RenderLayers::layer(CANVAS_LAYER),
+    ));
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                clear_color: ClearColorConfig::Custom(Color::BLACK),
+                ..default()
+            },
+            ..default()
+        },
+        CanvasCamera,
+        RenderLayers::layer(CANVAS_LAYER),
+    ));
+    commands.insert_resource(PixelCanvasImage(handle));
+
+    event!(Level::INFO, "Rendering at {}x{}", settings.resolution.x, settings.resolution.y);
+}
+
+/// Picks the integer scale for the window size and crops the canvas camera to it.
+pub fn fit_canvas_to_window(
+    settings: Res<PixelPerfectSettings>,
+    mut resize_events: EventReader<WindowResized>,
+    mut scale_factor_events: EventReader<WindowScaleFactorChanged>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    added_canvas: Query<(), Added<CanvasCamera>>,
+    mut canvas_camera_query: Query<&mut Camera, With<CanvasCamera>>,
+    mut pixel_scale: ResMut<PixelScale>,
+) {
+    let resized = resize_events.read().count() + scale_factor_events.read().count() > 0;
+    if !resized && !settings.is_changed() && added_canvas.is_empty() {
+        return;
+    }
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    let window_size = window.physical_size();
+    let scale = integer_scale(window_size, settings.resolution, settings.letterbox);
+    if pixel_scale.scale != scale || pixel_scale.scale_factor != window.scale_factor() {
+        event!(Level::DEBUG, "Upscaling the canvas by {} | scale factor {}", scale, window.scale_factor());
+        pixel_scale.scale = scale;
+        pixel_scale.scale_factor = window.scale_factor();
+    }
+
+    let shown = (settings.resolution * scale).min(window_size);
+    for mut camera in &mut canvas_camera_query {
+        camera.viewport = Some(Viewport {
+            physical_position: (window_size - shown) / 2,
+            physical_size: shown,
+            ..default()
+        });
+    }
+}
+
+/// Renders the world camera from a whole texel position,
+/// and shifts the canvas by the remainder so scrolling stays smooth.
+pub fn snap_world_camera(
+    mut pixel_scale: ResMut<PixelScale>,
+    mut camera_query: Query<&mut GlobalTransform, With<CameraController>>,
+) {
+    for mut global_transform in &mut camera_query {
+        let mut transform = global_transform.compute_transform();
+        let rounded = transform.translation.truncate().round();
+        pixel_scale.sub_pixel = transform.translation.truncate() - rounded;
+        transform.translation.x = rounded.x;
+        transform.translation.y = rounded.y;
+        *global_transform = GlobalTransform::from(transform);
+    }
+}
+
+/// Moves the canvas against the world camera's sub-pixel remainder, in logical window pixels.
+pub fn offset_canvas(
+    pixel_scale: Res<PixelScale>,
+    mut canvas_query: Query<&mut GlobalTransform, With<PixelCanvas>>,
+) {
+    let scale = pixel_scale.logical_scale();
+    for mut global_transform in &mut canvas_query {
+        let offset = -pixel_scale.sub_pixel * scale;
+        *global_transform = GlobalTransform::from(
+            Transform::from_translation(offset.extend(0.)).with_scale(Vec3::new(scale, scale, 1.)),
+        );
+    }
+}
+
+/// Rounds sprites that moved to whole world pixels, after transforms are propagated
+/// so that their parents' positions are accounted for.
+pub fn snap_sprites(
+    settings: Res<PixelPerfectSettings>,
+    mut sprite_query: Query<
+        &mut GlobalTransform,
+        (With<Sprite>, Without<PixelCanvas>, Changed<GlobalTransform>),
+    >,
+) {
+    if !settings.snap_sprites {
+        return;
+    }
+    for mut global_transform in &mut sprite_query {
+        let mut transform = global_transform.compute_transform();
+        transform.translation.x = transform.translation.x.round();
+        transform.translation.y = transform.translation.y.round();
+        *global_transform = GlobalTransform::from(transform);
+    }
+}
+
+/// Plugin which renders the world at a fixed low resolution and upscales it by a whole number.
+///
+/// Needs [`CameraPlugin`](crate::camera::CameraPlugin) for the world camera.
+pub struct PixelPerfectPlugin;
+
+impl Plugin for PixelPerfectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PixelPerfectSettings>()
+            .init_resource::<PixelScale>()
+            .add_systems(Startup, setup_pixel_canvas.after(spawn_camera))
+            .add_systems(Update, fit_canvas_to_window)
+            .add_systems(
+                PostUpdate,
+                (snap_world_camera, offset_canvas, snap_sprites)
+                    .chain()
+                    .after(TransformSystem::TransformPropagate)
+                    .before(VisibilitySystems::CheckVisibility),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::window::WindowResolution;
+
+    #[test]
+    fn scale_is_a_whole_number() {
+        let resolution = UVec2::new(320, 176);
+
+        assert_eq!(integer_scale(UVec2::new(1280, 720), resolution, true), 4);
+        assert_eq!(integer_scale(UVec2::new(1920, 1080), resolution, true), 6);
+        assert_eq!(integer_scale(UVec2::new(1920, 1080), resolution, false), 7);
+        // never below one, even in a tiny window
+        assert_eq!(integer_scale(UVec2::new(100, 100), resolution, true), 1);
+    }
+
+    #[test]
+    fn canvas_fits_a_high_dpi_window() {
+        let mut app = App::new();
+        app.init_resource::<PixelPerfectSettings>()
+            .init_resource::<PixelScale>()
+            .add_event::<WindowResized>()
+            .add_event::<WindowScaleFactorChanged>()
+            .add_systems(Update, (fit_canvas_to_window, offset_canvas).chain());
+
+        let mut resolution = WindowResolution::default();
+        resolution.set_scale_factor_override(Some(2.));
+        resolution.set_physical_resolution(2560, 1440);
+        app.world_mut().spawn((
+            Window {
+                resolution,
+                ..default()
+            },
+            PrimaryWindow,
+        ));
+        let canvas_camera = app.world_mut().spawn((Camera::default(), CanvasCamera)).id();
+        let canvas = app
+            .world_mut()
+            .spawn((GlobalTransform::default(), PixelCanvas))
+            .id();
+        app.update();
+
+        let pixel_scale = *app.world().resource::<PixelScale>();
+        assert_eq!(pixel_scale.scale, 8);
+        assert_eq!(pixel_scale.scale_factor, 2.);
+
+        let viewport = app.world().get::<Camera>(canvas_camera).unwrap().viewport.clone().unwrap();
+        assert_eq!(viewport.physical_size, UVec2::new(2560, 1408));
+        assert_eq!(viewport.physical_position, UVec2::new(0, 16));
+
+        // 320 texels at 4 logical pixels each fill the 1280 logical pixels of the window
+        let canvas_scale = app.world().get::<GlobalTransform>(canvas).unwrap().compute_transform().scale;
+        assert_eq!(canvas_scale, Vec3::new(4., 4., 1.));
+    }
+}
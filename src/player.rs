@@ -1,14 +1,22 @@
 use tracing::{event, Level};
 
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashSet};
 use bevy_ecs_ldtk::prelude::*;
-use bevy_rapier2d::dynamics::Velocity;
+use bevy_rapier2d::prelude::{ColliderDisabled, GravityScale, Velocity};
 
 use crate::{
-    climbing::Climber, 
-    colliders::ColliderBundle,
+    climbing::{Climber, CLIMB_SPEED},
+    console::{parse_arg, player_entity, ConsoleAppExt, ConsoleCommand},
+    economy::Wallet,
+    health::{Health, Invulnerable},
     inventory::Inventory,
+    physics_mode::{GroundDetection, PhysicsMode, JUMP_SPEED},
+    settings::Settings,
+    survival::{Needs, SurvivalSettings},
+    terrain::CurrentTerrain,
     timeline::Timeline,
+    water::{Stamina, Swimmer, WaterSettings, WaterState},
+    weather::{Weather, WeatherSettings},
 };
 
 #[derive(Eq, PartialEq)]
@@ -32,15 +40,43 @@ pub struct AnimationTimer {
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Player;
 
+/// Lets the player fly through walls, set by the `noclip` console command.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Noclip;
+
+/// Reasons the player currently ignores input, like a running cutscene.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Resource)]
+pub struct InputLock(HashSet<&'static str>);
+
+impl InputLock {
+    pub fn lock(&mut self, reason: &'static str) {
+        self.0.insert(reason);
+    }
+
+    pub fn unlock(&mut self, reason: &'static str) {
+        self.0.remove(reason);
+    }
+
+    pub fn is_locked(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
 #[derive(Clone, Default, Bundle)]
 pub struct PlayerBundle {
     pub sprite_sheet_bundle: LdtkSpriteSheetBundle,
-    pub collider_bundle: ColliderBundle,
     pub player: Player,
     pub worldly: Worldly,
     pub climber: Climber,
     pub inventory: Inventory,
+    pub wallet: Wallet,
     pub timeline: Timeline,
+    pub swimmer: Swimmer,
+    pub stamina: Stamina,
+    pub needs: Needs,
+    pub health: Health,
+    pub current_terrain: CurrentTerrain,
+    pub ground_detection: GroundDetection,
     entity_instance: EntityInstance,
 }
 
@@ -69,12 +105,19 @@ impl LdtkEntity for PlayerBundle {
                 texture_atlas: atlas_layout.into(),
                 ..default()
             },
-            collider_bundle: ColliderBundle::from(entity_instance),
             player: Player,
             worldly: Worldly::from_entity_info(entity_instance),
             climber: Climber::default(),
-            inventory: Inventory::default(),
-            timeline: Timeline::from(entity_instance),
+            inventory: Inventory::from(entity_instance),
+            wallet: Wallet::from(entity_instance),
+            // filled in from the `timeline` field by the timeline plugin
+            timeline: Timeline::default(),
+            swimmer: Swimmer::default(),
+            stamina: Stamina::default(),
+            needs: Needs::default(),
+            health: Health::default(),
+            current_terrain: CurrentTerrain::default(),
+            ground_detection: GroundDetection::default(),
             entity_instance: entity_instance.clone(),
             // velocity: Velocity::default(),
         }
@@ -83,57 +126,112 @@ impl LdtkEntity for PlayerBundle {
 
 pub fn player_movement(
     input: Res<ButtonInput<KeyCode>>,
+    input_lock: Res<InputLock>,
+    settings: Res<Settings>,
     time: Res<Time>,
     mut config: ResMut<AnimationTimer>,
-    mut query: Query<(&mut Velocity, &mut Climber, &mut TextureAtlas), With<Player>>,
+    water_settings: Res<WaterSettings>,
+    physics_mode: Res<PhysicsMode>,
+    survival_settings: Res<SurvivalSettings>,
+    weather: Res<Weather>,
+    weather_settings: Res<WeatherSettings>,
+    mut query: Query<
+        (
+            &mut Velocity,
+            &mut Climber,
+            &mut TextureAtlas,
+            &Swimmer,
+            &CurrentTerrain,
+            &GroundDetection,
+            Option<&Needs>,
+            Has<Noclip>,
+        ),
+        With<Player>,
+    >,
 ) {
-    for (mut velocity, mut climber, mut tas) in &mut query {
+    let locked = input_lock.is_locked();
+    let pressed = |key| !locked && input.pressed(key);
+    let just_pressed = |key| !locked && input.just_pressed(key);
+
+    for (mut velocity, mut climber, mut tas, swimmer, current_terrain, ground_detection, needs, noclip) in &mut query {
+        // without collisions there's nothing to stand on, so noclip always moves freely
+        let side_view = *physics_mode == PhysicsMode::SideView && !noclip;
         // let right = if input.pressed(KeyCode::KeyD) { 1. } else { 0. };
         // let left = if input.pressed(KeyCode::KeyA) { 1. } else { 0. };
-        let up = if input.pressed(KeyCode::KeyW) { 1. } else { 0. };
-        let down = if input.pressed(KeyCode::KeyS) { 1. } else { 0. };
-        let right = if input.pressed(KeyCode::KeyD) { 1. } else { 0. };
-        let left = if input.pressed(KeyCode::KeyA) { 1. } else { 0. };
-
-        let current_direction = match (up, down, right, left) {
-            (1., 0., 0., 0.) => CharacterDirection::N,
-            (1., 0., 1., 0.) => CharacterDirection::NE,
-            (0., 0., 1., 0.) => CharacterDirection::E,
-            (0., 1., 1., 0.) => CharacterDirection::SE,
-            (0., 1., 0., 0.) => CharacterDirection::S,
-            (0., 1., 0., 1.) => CharacterDirection::SW,
-            (0., 0., 0., 1.) => CharacterDirection::W,
-            (1., 0., 0., 1.) => CharacterDirection::NW,
-            _ => CharacterDirection::LAST,
+        let controls = &settings.controls;
+        let up = if pressed(controls.up.0) { 1. } else { 0. };
+        let down = if pressed(controls.down.0) { 1. } else { 0. };
+        let right = if pressed(controls.right.0) { 1. } else { 0. };
+        let left = if pressed(controls.left.0) { 1. } else { 0. };
+
+        // attach to a climbable by pressing up or down on it, and let go once past its top or bottom
+        if climber.intersecting_climbables.is_empty() || noclip {
+            climber.climbing = false;
+        } else if just_pressed(controls.up.0) || just_pressed(controls.down.0) {
+            climber.climbing = true;
+        }
+
+        // while attached only vertical movement is allowed, facing the wall
+        let (right, left) = if climber.climbing { (0., 0.) } else { (right, left) };
+        // in side view, up and down only mean something on a climbable
+        let (up, down) = if side_view && !climber.climbing { (0., 0.) } else { (up, down) };
+
+        let current_direction = if climber.climbing {
+            // the climbing animation is the walk cycle seen from behind
+            if up != down {
+                CharacterDirection::N
+            } else {
+                CharacterDirection::LAST
+            }
+        } else {
+            match (up, down, right, left) {
+                (1., 0., 0., 0.) => CharacterDirection::N,
+                (1., 0., 1., 0.) => CharacterDirection::NE,
+                (0., 0., 1., 0.) => CharacterDirection::E,
+                (0., 1., 1., 0.) => CharacterDirection::SE,
+                (0., 1., 0., 0.) => CharacterDirection::S,
+                (0., 1., 0., 1.) => CharacterDirection::SW,
+                (0., 0., 0., 1.) => CharacterDirection::W,
+                (1., 0., 0., 1.) => CharacterDirection::NW,
+                _ => CharacterDirection::LAST,
+            }
         };
 
-        config.timer.tick(time.delta());
+        // the walk cycle slows down in water, and shortens to a two frame stroke when swimming
+        // running out of water, food or rest and bad weather slow the player down
+        let speed = water_settings.speed_multiplier(swimmer.state)
+            * current_terrain.0.speed_multiplier
+            * needs.map_or(1., |needs| survival_settings.speed_multiplier(needs))
+            * weather_settings.speed_multiplier(*weather);
+        let stride = if swimmer.state == WaterState::Swimming { 1 } else { 3 };
+
+        config.timer.tick(time.delta().mul_f32(speed));
         let update: bool = config.timer.just_finished();
 
         match &current_direction {
             CharacterDirection::N => {
-                animate(16, 19, &mut tas, &update);
+                animate(16, 16 + stride, &mut tas, &update);
             },
             CharacterDirection::NE => {
-                animate(20, 23, &mut tas, &update);
+                animate(20, 20 + stride, &mut tas, &update);
             },
             CharacterDirection::E => {
-                animate(24, 27, &mut tas, &update);
+                animate(24, 24 + stride, &mut tas, &update);
             },
             CharacterDirection::SE => {
-                animate(28, 31, &mut tas, &update);
+                animate(28, 28 + stride, &mut tas, &update);
             },
             CharacterDirection::S => {
-                animate(0, 3, &mut tas, &update);
+                animate(0, stride, &mut tas, &update);
             },
             CharacterDirection::SW => {
-                animate(4, 7, &mut tas, &update);
+                animate(4, 4 + stride, &mut tas, &update);
             },
             CharacterDirection::W => {
-                animate(8, 11, &mut tas, &update);
+                animate(8, 8 + stride, &mut tas, &update);
             },
             CharacterDirection::NW => {
-                animate(12, 15, &mut tas, &update);
+                animate(12, 12 + stride, &mut tas, &update);
             },
             _ => {
                 match tas.index {
@@ -150,13 +248,20 @@ pub fn player_movement(
             },
         }
 
-        velocity.linvel.x = (right - left) * 100.;
-        velocity.linvel.y = (up - down) * 100.;
+        // low traction terrain eases into the target velocity instead of snapping to it
+        let target = Vec2::new(right - left, up - down) * 100. * speed;
+        let response = 1. - (1. - current_terrain.0.traction).powf(time.delta_seconds() * 60.);
 
-        if climber.intersecting_climbables.is_empty() {
-            climber.climbing = false;
-        } else if input.just_pressed(KeyCode::KeyW) || input.just_pressed(KeyCode::KeyS) {
-            climber.climbing = true;
+        if climber.climbing {
+            velocity.linvel = Vec2::new(0., (up - down) * CLIMB_SPEED);
+        } else if side_view {
+            // gravity owns the vertical axis
+            velocity.linvel.x += (target.x - velocity.linvel.x) * response;
+            if ground_detection.on_ground && just_pressed(controls.jump.0) {
+                velocity.linvel.y = JUMP_SPEED;
+            }
+        } else {
+            velocity.linvel = velocity.linvel.lerp(target, response);
         }
     }
 }
@@ -169,12 +274,82 @@ fn animate(start_frame: usize, end_frame: usize, tas: &mut TextureAtlas, update:
     }
 }
 
+/// `tp <x> <y>`: moves the player to a world position.
+pub struct TeleportCommand;
+
+impl ConsoleCommand for TeleportCommand {
+    fn name(&self) -> &'static str {
+        "tp"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<x> <y>"
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        let position = Vec2::new(parse_arg(args, 0, "x")?, parse_arg(args, 1, "y")?);
+        let player = player_entity(world)?;
+        let mut transform = world.get_mut::<Transform>(player).ok_or("The player has no transform")?;
+        transform.translation = position.extend(transform.translation.z);
+        Ok(format!("Teleported to {}, {}", position.x, position.y))
+    }
+}
+
+/// `god`: toggles the player taking damage.
+pub struct GodCommand;
+
+impl ConsoleCommand for GodCommand {
+    fn name(&self) -> &'static str {
+        "god"
+    }
+
+    fn run(&self, world: &mut World, _: &[&str]) -> Result<String, String> {
+        let player = player_entity(world)?;
+        let mut player = world.entity_mut(player);
+        if player.contains::<Invulnerable>() {
+            player.remove::<Invulnerable>();
+            Ok("God mode off".to_string())
+        } else {
+            player.insert(Invulnerable);
+            Ok("God mode on".to_string())
+        }
+    }
+}
+
+/// `noclip`: toggles the player flying through walls.
+pub struct NoclipCommand;
+
+impl ConsoleCommand for NoclipCommand {
+    fn name(&self) -> &'static str {
+        "noclip"
+    }
+
+    fn run(&self, world: &mut World, _: &[&str]) -> Result<String, String> {
+        let player = player_entity(world)?;
+        let mut player = world.entity_mut(player);
+        if player.contains::<Noclip>() {
+            player
+                .remove::<(Noclip, ColliderDisabled)>()
+                .insert(GravityScale(1.));
+            Ok("Noclip off".to_string())
+        } else {
+            player.insert((Noclip, ColliderDisabled, GravityScale(0.)));
+            Ok("Noclip on".to_string())
+        }
+    }
+}
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, player_movement)
-            .register_ldtk_entity::<PlayerBundle>("Player");
+        app.insert_resource(AnimationTimer{timer: Timer::from_seconds(0.1, TimerMode::Repeating)})
+            .init_resource::<InputLock>()
+            .add_systems(Update, player_movement)
+            .register_ldtk_entity::<PlayerBundle>("Player")
+            .add_console_command(TeleportCommand)
+            .add_console_command(GodCommand)
+            .add_console_command(NoclipCommand);
         event!(Level::INFO, "Player plugin registered");
     }
 }
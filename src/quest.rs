@@ -0,0 +1,625 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_common_assets::xml::XmlAssetPlugin;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    character::{is_named, CharacterInteraction},
+    game_flow::LevelEntered,
+    health::Died,
+    inventory::{Inventory, InventoryChanged},
+    player::Player,
+    settings::Settings,
+    story::StoryState,
+};
+
+fn one() -> u32 {
+    1
+}
+
+/// Something the player has to do to finish a quest stage.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    /// Interact with a character, by its `name` field or identifier
+    TalkTo { name: String },
+    /// Hold `count` of an item at once
+    Collect {
+        item: String,
+        #[serde(default = "one")]
+        count: u32,
+    },
+    /// Enter a level, by identifier
+    Reach { level: String },
+    /// Kill entities, by `name` field or identifier
+    Defeat {
+        name: String,
+        #[serde(default = "one")]
+        count: u32,
+    },
+}
+
+/// Something that happened in the game which may move objectives forward.
+#[derive(Clone, Debug)]
+pub enum QuestProgressEvent<'a> {
+    TalkedTo(&'a EntityInstance),
+    /// The player now holds this many of an item
+    Holding { item: &'a str, count: u32 },
+    Reached { level: &'a str },
+    Defeated(&'a EntityInstance),
+}
+
+impl Objective {
+    pub fn required(&self) -> u32 {
+        match self {
+            Objective::TalkTo { .. } | Objective::Reach { .. } => 1,
+            Objective::Collect { count, .. } | Objective::Defeat { count, .. } => *count,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::TalkTo { name } => format!("Talk to {}", name),
+            Objective::Collect { item, .. } => format!("Collect {}", item.replace('_', " ")),
+            Objective::Reach { level } => format!("Reach {}", level.replace('_', " ")),
+            Objective::Defeat { name, .. } => format!("Defeat {}", name),
+        }
+    }
+
+    /// New progress towards the objective, if the event is relevant to it.
+    fn advance(&self, progress: u32, happened: &QuestProgressEvent) -> Option<u32> {
+        let progress = match (self, happened) {
+            (Objective::TalkTo { name }, QuestProgressEvent::TalkedTo(entity_instance))
+                if is_named(entity_instance, name) =>
+            {
+                progress + 1
+            }
+            (Objective::Collect { item, .. }, QuestProgressEvent::Holding { item: held, count })
+                if item == *held =>
+            {
+                *count
+            }
+            (Objective::Reach { level }, QuestProgressEvent::Reached { level: reached }) if level == *reached => {
+                progress + 1
+            }
+            (Objective::Defeat { name, .. }, QuestProgressEvent::Defeated(entity_instance))
+                if is_named(entity_instance, name) =>
+            {
+                progress + 1
+            }
+            _ => return None,
+        };
+        Some(progress.min(self.required()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct QuestStage {
+    pub description: String,
+    #[serde(rename = "objective", default)]
+    pub objectives: Vec<Objective>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct QuestReward {
+    #[serde(rename = "item", default)]
+    pub items: Vec<String>,
+    #[serde(default)]
+    pub money: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Quest {
+    pub id: String,
+    pub title: String,
+    #[serde(rename = "stage", default)]
+    pub stages: Vec<QuestStage>,
+    #[serde(default)]
+    pub reward: QuestReward,
+}
+
+impl Quest {
+    /// Items the collect objectives of every stage ask for, and how many.
+    pub fn collected_items(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.stages
+            .iter()
+            .flat_map(|stage| &stage.objectives)
+            .filter_map(|objective| match objective {
+                Objective::Collect { item, count } => Some((item.as_str(), *count)),
+                _ => None,
+            })
+    }
+}
+
+/// Every quest of the game, loaded from a `*.quests.xml` asset.
+#[derive(Asset, Clone, Debug, Default, PartialEq, Deserialize, TypePath)]
+pub struct QuestBook {
+    #[serde(rename = "quest", default)]
+    pub quests: Vec<Quest>,
+}
+
+impl QuestBook {
+    pub fn get(&self, id: &str) -> Option<&Quest> {
+        self.quests.iter().find(|quest| quest.id == id)
+    }
+}
+
+#[derive(Resource)]
+pub struct QuestBookHandle(pub Handle<QuestBook>);
+
+/// Progress through a started quest.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuestProgress {
+    pub stage: usize,
+    /// Progress of each objective of the current stage
+    pub objectives: Vec<u32>,
+    pub completed: bool,
+}
+
+/// Started and completed quests, by id.
+#[derive(Clone, Debug, Default, PartialEq, Resource, Serialize, Deserialize)]
+pub struct QuestLog {
+    quests: BTreeMap<String, QuestProgress>,
+}
+
+impl QuestLog {
+    pub fn get(&self, id: &str) -> Option<&QuestProgress> {
+        self.quests.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &QuestProgress)> {
+        self.quests.iter()
+    }
+
+    /// Starts a quest at its first stage, returns false if it was already started.
+    pub fn start(&mut self, quest: &Quest) -> bool {
+        if self.quests.contains_key(&quest.id) {
+            return false;
+        }
+        let objectives = quest.stages.first().map_or(0, |stage| stage.objectives.len());
+        self.quests.insert(
+            quest.id.clone(),
+            QuestProgress {
+                stage: 0,
+                objectives: vec![0; objectives],
+                completed: quest.stages.is_empty(),
+            },
+        );
+        true
+    }
+
+    /// Moves every running quest forward, returns the ids of quests whose stage changed
+    /// and whether they completed.
+    pub fn record(&mut self, book: &QuestBook, happened: &QuestProgressEvent) -> Vec<(String, bool)> {
+        let mut advanced = Vec::new();
+        for (id, progress) in self.quests.iter_mut().filter(|(_, progress)| !progress.completed) {
+            let Some(stage) = book.get(id).and_then(|quest| quest.stages.get(progress.stage)) else {
+                continue;
+            };
+
+            for (objective, count) in stage.objectives.iter().zip(progress.objectives.iter_mut()) {
+                if let Some(new_count) = objective.advance(*count, happened) {
+                    *count = new_count;
+                }
+            }
+
+            let stage_done = stage
+                .objectives
+                .iter()
+                .zip(&progress.objectives)
+                .all(|(objective, count)| *count >= objective.required());
+            if stage_done {
+                let quest = book.get(id).expect("quest was just found");
+                progress.stage += 1;
+                progress.objectives = quest
+                    .stages
+                    .get(progress.stage)
+                    .map_or(Vec::new(), |stage| vec![0; stage.objectives.len()]);
+                progress.completed = progress.stage >= quest.stages.len();
+                advanced.push((id.clone(), progress.completed));
+            }
+        }
+        advanced
+    }
+}
+
+/// Starts a quest from the [`QuestBook`], by id.
+#[derive(Clone, Debug, Eq, PartialEq, Event)]
+pub struct StartQuest(pub String);
+
+/// Sent when a quest moves on to its next stage.
+#[derive(Clone, Debug, Eq, PartialEq, Event)]
+pub struct QuestStageCompleted {
+    pub quest: String,
+    pub stage: usize,
+}
+
+/// Sent when a quest's last stage is done.
+///
+/// Collected items are handed over and reward items given by [`give_quest_rewards`],
+/// money by whoever keeps track of it.
+#[derive(Clone, Debug, PartialEq, Event)]
+pub struct QuestCompleted {
+    pub quest: String,
+    pub reward: QuestReward,
+}
+
+/// Shows the quest log while open.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Resource)]
+pub struct QuestLogOpen(pub bool);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct QuestLogUi;
+
+fn load_quest_book(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(QuestBookHandle(asset_server.load("quests/default.quests.xml")));
+}
+
+/// Starts quests, holding on to the ones asked for while the quest book is still loading.
+pub fn start_quests(
+    mut start_events: EventReader<StartQuest>,
+    mut pending: Local<Vec<String>>,
+    book_handle: Res<QuestBookHandle>,
+    books: Res<Assets<QuestBook>>,
+    mut quest_log: ResMut<QuestLog>,
+    player_query: Query<&Inventory, With<Player>>,
+) {
+    // events only live for two frames, so they are read even before the book is there
+    pending.extend(start_events.read().map(|StartQuest(id)| id.clone()));
+    let Some(book) = books.get(&book_handle.0) else {
+        return;
+    };
+
+    for id in pending.drain(..) {
+        let Some(quest) = book.get(&id) else {
+            event!(Level::WARN, "Unknown quest {}", id);
+            continue;
+        };
+        if quest_log.start(quest) {
+            event!(Level::INFO, "Quest started | {}", quest.title);
+            // items already in hand count towards collecting them
+            if let Ok(inventory) = player_query.get_single() {
+                for item in inventory.items() {
+                    quest_log.record(
+                        book,
+                        &QuestProgressEvent::Holding {
+                            item,
+                            count: inventory.count(item) as u32,
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Turns game events into quest progress.
+#[allow(clippy::too_many_arguments)]
+pub fn track_quest_progress(
+    mut interactions: EventReader<CharacterInteraction>,
+    mut inventory_events: EventReader<InventoryChanged>,
+    mut level_events: EventReader<LevelEntered>,
+    mut died_events: EventReader<Died>,
+    entity_query: Query<&EntityInstance>,
+    player_query: Query<&Inventory, With<Player>>,
+    book_handle: Res<QuestBookHandle>,
+    books: Res<Assets<QuestBook>>,
+    mut quest_log: ResMut<QuestLog>,
+    mut story: ResMut<StoryState>,
+    mut stage_events: EventWriter<QuestStageCompleted>,
+    mut completed_events: EventWriter<QuestCompleted>,
+) {
+    let Some(book) = books.get(&book_handle.0) else {
+        return;
+    };
+
+    let mut advanced = Vec::new();
+    for interaction in interactions.read() {
+        if let Ok(entity_instance) = entity_query.get(interaction.character) {
+            advanced.extend(quest_log.record(book, &QuestProgressEvent::TalkedTo(entity_instance)));
+        }
+    }
+    for changed in inventory_events.read() {
+        if let Ok(inventory) = player_query.get(changed.entity) {
+            let count = inventory.count(&changed.item) as u32;
+            advanced.extend(quest_log.record(
+                book,
+                &QuestProgressEvent::Holding {
+                    item: &changed.item,
+                    count,
+                },
+            ));
+        }
+    }
+    for entered in level_events.read() {
+        advanced.extend(quest_log.record(
+            book,
+            &QuestProgressEvent::Reached {
+                level: &entered.identifier,
+            },
+        ));
+    }
+    for died in died_events.read() {
+        if let Ok(entity_instance) = entity_query.get(died.entity) {
+            advanced.extend(quest_log.record(book, &QuestProgressEvent::Defeated(entity_instance)));
+        }
+    }
+
+    for (id, completed) in advanced {
+        let quest = book.get(&id).expect("recorded quests are in the book");
+        let stage = quest_log.get(&id).map_or(0, |progress| progress.stage);
+        stage_events.send(QuestStageCompleted {
+            quest: id.clone(),
+            stage: stage - 1,
+        });
+        if !completed {
+            event!(Level::INFO, "Quest {} moved to stage {}", quest.title, stage);
+            continue;
+        }
+
+        event!(Level::INFO, "Quest completed | {}", quest.title);
+        // lets timelines and story gates check for finished quests
+        story.set_flag(format!("{}_done", id));
+        completed_events.send(QuestCompleted {
+            quest: id,
+            reward: quest.reward.clone(),
+        });
+    }
+}
+
+/// Takes the items a completed quest had the player collect, and gives its reward items.
+pub fn give_quest_rewards(
+    mut completed_events: EventReader<QuestCompleted>,
+    book_handle: Res<QuestBookHandle>,
+    books: Res<Assets<QuestBook>>,
+    mut player_query: Query<(Entity, &mut Inventory), With<Player>>,
+    mut inventory_events: EventWriter<InventoryChanged>,
+) {
+    let Ok((player, mut inventory)) = player_query.get_single_mut() else {
+        return;
+    };
+    for completed in completed_events.read() {
+        let quest = books.get(&book_handle.0).and_then(|book| book.get(&completed.quest));
+        for (item, count) in quest.into_iter().flat_map(Quest::collected_items) {
+            let taken = (0..count).take_while(|_| inventory.remove(item)).count();
+            if taken > 0 {
+                event!(Level::INFO, "Handed over {} {} | {}", taken, item, completed.quest);
+                inventory_events.send(InventoryChanged {
+                    entity: player,
+                    item: item.to_string(),
+                });
+            }
+        }
+        for item in &completed.reward.items {
+            inventory.add(item.clone());
+            inventory_events.send(InventoryChanged {
+                entity: player,
+                item: item.clone(),
+            });
+        }
+    }
+}
+
+pub fn toggle_quest_log(
+    input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut open: ResMut<QuestLogOpen>,
+) {
+    if input.just_pressed(settings.controls.quest_log.0) {
+        open.0 = !open.0;
+    }
+}
+
+/// Rebuilds the quest log panel when it is opened or the quests change.
+pub fn update_quest_log_ui(
+    mut commands: Commands,
+    open: Res<QuestLogOpen>,
+    quest_log: Res<QuestLog>,
+    book_handle: Res<QuestBookHandle>,
+    books: Res<Assets<QuestBook>>,
+    ui_query: Query<Entity, With<QuestLogUi>>,
+) {
+    if !open.is_changed() && !quest_log.is_changed() {
+        return;
+    }
+    for entity in &ui_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    let (true, Some(book)) = (open.0, books.get(&book_handle.0)) else {
+        return;
+    };
+
+    let mut lines = vec!["QUESTS".to_string()];
+    for (id, progress) in quest_log.iter() {
+        let Some(quest) = book.get(id) else {
+            continue;
+        };
+        if progress.completed {
+            lines.push(format!("{} (done)", quest.title));
+            continue;
+        }
+        lines.push(quest.title.clone());
+        if let Some(stage) = quest.stages.get(progress.stage) {
+            lines.push(format!("  {}", stage.description));
+            for (objective, count) in stage.objectives.iter().zip(&progress.objectives) {
+                lines.push(format!("  - {} {}/{}", objective.describe(), count, objective.required()));
+            }
+        }
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(16.0),
+                    left: Val::Px(16.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.7).into(),
+                ..default()
+            },
+            QuestLogUi,
+        ))
+        .with_children(|parent| {
+            for line in lines {
+                parent.spawn(TextBundle::from_section(
+                    line,
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+/// Plugin which tracks quests from `quests/default.quests.xml`; J opens the quest log.
+pub struct QuestPlugin;
+
+impl Plugin for QuestPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(XmlAssetPlugin::<QuestBook>::new(&["quests.xml"]))
+            .init_resource::<QuestLog>()
+            .init_resource::<QuestLogOpen>()
+            .add_event::<StartQuest>()
+            .add_event::<QuestStageCompleted>()
+            .add_event::<QuestCompleted>()
+            .add_systems(Startup, load_quest_book)
+            .add_systems(
+                Update,
+                (
+                    start_quests,
+                    track_quest_progress,
+                    give_quest_rewards,
+                    toggle_quest_log,
+                    update_quest_log_ui,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        enemy::Enemy, health::Damage, inventory::InventoryPlugin, story::StoryPlugin,
+        test_support::TestApp,
+    };
+
+    #[test]
+    fn stages_advance_when_every_objective_is_done() {
+        let book: QuestBook = serde_xml_rs::from_str(
+            r#"<quests>
+                <quest>
+                    <id>water_for_hickman</id>
+                    <title>Water for Hickman</title>
+                    <stage>
+                        <description>Find two waterskins</description>
+                        <objective><collect><item>Water</item><count>2</count></collect></objective>
+                    </stage>
+                    <stage>
+                        <description>Reach the river</description>
+                        <objective><reach><level>River_Scene_One</level></reach></objective>
+                    </stage>
+                    <reward><item>Gem</item><money>25</money></reward>
+                </quest>
+            </quests>"#,
+        )
+        .unwrap();
+        let quest = book.get("water_for_hickman").unwrap();
+        assert_eq!(quest.reward.money, 25.);
+
+        let mut log = QuestLog::default();
+        assert!(log.start(quest));
+        assert!(!log.start(quest));
+
+        let holding = |count| QuestProgressEvent::Holding { item: "Water", count };
+        assert!(log.record(&book, &holding(1)).is_empty());
+        assert_eq!(log.record(&book, &holding(2)), vec![("water_for_hickman".to_string(), false)]);
+        assert_eq!(log.get("water_for_hickman").unwrap().stage, 1);
+
+        let reached = QuestProgressEvent::Reached { level: "River_Scene_One" };
+        assert_eq!(log.record(&book, &reached), vec![("water_for_hickman".to_string(), true)]);
+        assert!(log.get("water_for_hickman").unwrap().completed);
+    }
+
+    #[test]
+    fn quests_started_while_the_book_loads_are_kept() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<QuestBook>()
+            .init_resource::<QuestLog>()
+            .add_event::<StartQuest>()
+            .insert_resource(QuestBookHandle(Handle::default()))
+            .add_systems(Update, start_quests);
+
+        app.world_mut().send_event(StartQuest("water_for_hickman".to_string()));
+        // long enough for the event itself to be dropped
+        app.update();
+        app.update();
+        app.update();
+        assert!(app.world().resource::<QuestLog>().get("water_for_hickman").is_none());
+
+        let book: QuestBook = serde_xml_rs::from_str(include_str!("../assets/quests/default.quests.xml")).unwrap();
+        app.world_mut()
+            .resource_mut::<Assets<QuestBook>>()
+            .insert(&Handle::<QuestBook>::default(), book);
+        app.update();
+        assert!(app.world().resource::<QuestLog>().get("water_for_hickman").is_some());
+    }
+
+    #[test]
+    fn defeating_a_mob_completes_the_quest_and_hands_in_collected_items() {
+        let mut app = TestApp::new();
+        app.add_plugins((StoryPlugin, InventoryPlugin, QuestPlugin));
+        // sent by the GameFlowPlugin, left out here
+        app.world().init_resource::<Events<LevelEntered>>();
+        app.load_level();
+
+        let book: QuestBook = serde_xml_rs::from_str(
+            r#"<quests>
+                <quest>
+                    <id>clear_the_trail</id>
+                    <title>Clear the Trail</title>
+                    <stage>
+                        <description>Find a knife</description>
+                        <objective><collect><item>Knife</item></collect></objective>
+                    </stage>
+                    <stage>
+                        <description>Defeat the mob</description>
+                        <objective><defeat><name>Mob</name></defeat></objective>
+                    </stage>
+                    <reward><item>Gem</item></reward>
+                </quest>
+            </quests>"#,
+        )
+        .unwrap();
+        let handle = app.world().resource_mut::<Assets<QuestBook>>().add(book);
+        app.world().insert_resource(QuestBookHandle(handle));
+
+        // the fixture player starts with the knife
+        app.world().send_event(StartQuest("clear_the_trail".to_string()));
+        app.advance(1);
+        assert_eq!(app.world().resource::<QuestLog>().get("clear_the_trail").unwrap().stage, 1);
+
+        let world = app.world();
+        let mob = world.query_filtered::<Entity, With<Enemy>>().single(world);
+        app.world().send_event(Damage { target: mob, amount: 1000. });
+        app.advance(3);
+
+        assert!(app.world().resource::<QuestLog>().get("clear_the_trail").unwrap().completed);
+        let inventory = app.single::<Inventory, With<Player>>();
+        assert!(!inventory.contains("Knife"));
+        assert!(inventory.contains("Gem"));
+        assert_eq!(app.world().get::<Visibility>(mob), Some(&Visibility::Hidden));
+    }
+}
@@ -0,0 +1,45 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+
+/// Random numbers for gameplay and effects, seeded by [`RandomPlugin`].
+///
+/// Drawing from this instead of `rand::thread_rng` lets a run be replayed with the same seed.
+#[derive(Clone, Debug, Resource)]
+pub struct GameRng(pub StdRng);
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        GameRng(StdRng::seed_from_u64(seed))
+    }
+}
+
+/// Plugin which seeds [`GameRng`], from `seed` or else at random.
+///
+/// The seed is logged either way, so a run can be repeated with `--seed`.
+#[derive(Default)]
+pub struct RandomPlugin {
+    pub seed: Option<u64>,
+}
+
+impl Plugin for RandomPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        event!(Level::INFO, "Random seed | {}", seed);
+        app.insert_resource(GameRng::from_seed(seed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = GameRng::from_seed(42);
+        let mut b = GameRng::from_seed(42);
+        let draws = |rng: &mut GameRng| (0..8).map(|_| rng.0.gen::<u32>()).collect::<Vec<_>>();
+        assert_eq!(draws(&mut a), draws(&mut b));
+    }
+}
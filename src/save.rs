@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+
+use crate::{clock::GameClock, paths::GamePaths, quest::QuestLog, settings::Settings, story::StoryState};
+
+/// Everything written to a save slot.
+///
+/// Every field has a default so that saves from older versions keep loading.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SaveData {
+    #[serde(default)]
+    pub story: StoryState,
+    #[serde(default)]
+    pub quests: QuestLog,
+    #[serde(default)]
+    pub clock: GameClock,
+}
+
+/// Slot used by [`SaveGame`] and [`LoadGame`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Resource)]
+pub struct SaveSlot(pub u32);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Event)]
+pub struct SaveGame;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Event)]
+pub struct LoadGame;
+
+/// File of a save slot, in the user data folder.
+pub fn save_path(paths: &GamePaths, slot: SaveSlot) -> PathBuf {
+    paths.saves().join(format!("slot_{}.json", slot.0))
+}
+
+/// Quick save (F5) and quick load (F9) of the current slot.
+pub fn save_shortcuts(
+    input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut save_events: EventWriter<SaveGame>,
+    mut load_events: EventWriter<LoadGame>,
+) {
+    if input.just_pressed(settings.controls.quick_save.0) {
+        save_events.send(SaveGame);
+    }
+    if input.just_pressed(settings.controls.quick_load.0) {
+        load_events.send(LoadGame);
+    }
+}
+
+pub fn write_save(
+    mut save_events: EventReader<SaveGame>,
+    paths: Res<GamePaths>,
+    slot: Res<SaveSlot>,
+    story: Res<StoryState>,
+    quests: Res<QuestLog>,
+    clock: Res<GameClock>,
+) {
+    if save_events.read().count() == 0 {
+        return;
+    }
+
+    let data = SaveData {
+        story: story.clone(),
+        quests: quests.clone(),
+        clock: *clock,
+    };
+    let path = save_path(&paths, *slot);
+    let result = serde_json::to_string_pretty(&data)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            std::fs::create_dir_all(paths.saves()).map_err(|e| e.to_string())?;
+            std::fs::write(&path, json).map_err(|e| e.to_string())
+        });
+
+    match result {
+        Ok(()) => event!(Level::INFO, "Saved game | {}", path.display()),
+        Err(e) => event!(Level::ERROR, "Error saving game to {} | {}", path.display(), e),
+    }
+}
+
+pub fn read_save(
+    mut load_events: EventReader<LoadGame>,
+    paths: Res<GamePaths>,
+    slot: Res<SaveSlot>,
+    mut story: ResMut<StoryState>,
+    mut quests: ResMut<QuestLog>,
+    mut clock: ResMut<GameClock>,
+) {
+    if load_events.read().count() == 0 {
+        return;
+    }
+
+    let path = save_path(&paths, *slot);
+    let data: SaveData = match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+    {
+        Ok(data) => data,
+        Err(e) => {
+            event!(Level::ERROR, "Error loading save {} | {}", path.display(), e);
+            return;
+        }
+    };
+
+    *story = data.story;
+    *quests = data.quests;
+    *clock = data.clock;
+    event!(Level::INFO, "Loaded game | {}", path.display());
+}
+
+/// Plugin which writes and reads save slots.
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveSlot>()
+            .add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            .add_systems(Update, (save_shortcuts, write_save, read_save).chain());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn older_saves_still_load() {
+        let data: SaveData = serde_json::from_str("{}").unwrap();
+        assert_eq!(data, SaveData::default());
+    }
+}
@@ -0,0 +1,678 @@
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    character::is_named,
+    game_flow::LevelEntered,
+    inventory::{Inventory, InventoryChanged},
+    player::{InputLock, Player},
+    quest::StartQuest,
+    story::StoryState,
+    timeline::{ActionStarted, Timeline},
+};
+
+/// Name the [`InputLock`] is held under while a sequence runs
+const INPUT_LOCK_REASON: &str = "sequence";
+
+fn default_move_speed() -> f32 {
+    60.
+}
+
+fn default_frame_seconds() -> f32 {
+    0.1
+}
+
+fn default_counter_amount() -> i64 {
+    1
+}
+
+/// One step of a [`Sequence`].
+///
+/// Entities are found by their LDtk identifier or `name` field, see [`is_named`].
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    /// Walks an entity to a point in the world, in pixels per second
+    MoveTo {
+        entity: String,
+        x: f32,
+        y: f32,
+        #[serde(default = "default_move_speed")]
+        speed: f32,
+    },
+    /// Plays the frames `first..=last` of an entity's sprite sheet once
+    Animate {
+        entity: String,
+        first: usize,
+        last: usize,
+        #[serde(default = "default_frame_seconds")]
+        frame_seconds: f32,
+    },
+    Wait { seconds: f32 },
+    /// Fades the screen to black, 1 is fully black and 0 is clear
+    Fade { alpha: f32, seconds: f32 },
+    /// Adds an item to the player's inventory
+    GiveItem { item: String },
+    SetFlag { flag: String },
+    ClearFlag { flag: String },
+    /// Adds to a story counter, negative amounts subtract
+    AddToCounter {
+        counter: String,
+        #[serde(default = "default_counter_amount")]
+        amount: i64,
+    },
+    StartQuest { quest: String },
+    /// Moves the player to a level, by identifier or iid, at a point relative
+    /// to its bottom left corner or at its center
+    ChangeLevel {
+        level: String,
+        x: Option<f32>,
+        y: Option<f32>,
+    },
+    /// Runs commands one after the other
+    Serial {
+        #[serde(rename = "$value", default)]
+        commands: Vec<Command>,
+    },
+    /// Runs commands at the same time, until they have all finished
+    Parallel {
+        #[serde(rename = "$value", default)]
+        commands: Vec<Command>,
+    },
+}
+
+/// Commands run one after the other, authored in timeline XML.
+///
+/// ```xml
+/// <sequence>
+///     <move_to><entity>William Hickman</entity><x>180</x><y>-90</y></move_to>
+///     <parallel>
+///         <fade><alpha>1</alpha><seconds>1</seconds></fade>
+///         <give_item><item>Gem</item></give_item>
+///     </parallel>
+///     <set_flag><flag>met_hickman</flag></set_flag>
+/// </sequence>
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Sequence {
+    #[serde(rename = "$value", default)]
+    pub commands: Vec<Command>,
+}
+
+/// Starts a sequence alongside the ones already running.
+#[derive(Clone, Debug, PartialEq, Event)]
+pub struct PlaySequence(pub Sequence);
+
+/// Sent when a sequence started by a timeline action has run every command.
+#[derive(Clone, Debug, Eq, PartialEq, Event)]
+pub struct SequenceFinished {
+    pub entity: Entity,
+    pub action: String,
+}
+
+/// Full screen overlay used by [`Command::Fade`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct ScreenFade;
+
+/// Progress through a [`Command`].
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Leaf {
+        command: Command,
+        elapsed: f32,
+        /// Value the command interpolates from, captured when it starts
+        start: Option<f32>,
+    },
+    Serial {
+        pending: VecDeque<Command>,
+        current: Option<Box<Step>>,
+    },
+    Parallel {
+        running: Vec<Step>,
+    },
+}
+
+impl Step {
+    fn new(command: Command) -> Self {
+        match command {
+            Command::Serial { commands } => Step::Serial {
+                pending: commands.into(),
+                current: None,
+            },
+            Command::Parallel { commands } => Step::Parallel {
+                running: commands.into_iter().map(Step::new).collect(),
+            },
+            command => Step::Leaf {
+                command,
+                elapsed: 0.,
+                start: None,
+            },
+        }
+    }
+
+    /// Advances by `dt` seconds, returns whether the step has finished.
+    fn advance(&mut self, dt: f32, context: &mut SequenceContext) -> bool {
+        match self {
+            Step::Leaf {
+                command,
+                elapsed,
+                start,
+            } => {
+                *elapsed += dt;
+                context.run(command, *elapsed, dt, start)
+            }
+            Step::Serial { pending, current } => {
+                let mut dt = dt;
+                loop {
+                    if current.is_none() {
+                        match pending.pop_front() {
+                            Some(command) => *current = Some(Box::new(Step::new(command))),
+                            None => return true,
+                        }
+                    }
+                    let step = current.as_mut().expect("current step was just set");
+                    if !step.advance(dt, context) {
+                        return false;
+                    }
+                    // instant commands don't cost a frame, but the time was spent
+                    *current = None;
+                    dt = 0.;
+                }
+            }
+            Step::Parallel { running } => {
+                running.retain_mut(|step| !step.advance(dt, context));
+                running.is_empty()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct RunningSequence {
+    /// Timeline entity and action that started it
+    source: Option<(Entity, String)>,
+    step: Step,
+}
+
+/// Sequences currently running.
+#[derive(Clone, Debug, Default, PartialEq, Resource)]
+pub struct SequenceRunner {
+    running: Vec<RunningSequence>,
+}
+
+impl SequenceRunner {
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    fn start(&mut self, sequence: &Sequence, source: Option<(Entity, String)>) {
+        self.running.push(RunningSequence {
+            source,
+            step: Step::new(Command::Serial {
+                commands: sequence.commands.clone(),
+            }),
+        });
+    }
+}
+
+/// Everything the commands of a sequence can act on.
+#[derive(SystemParam)]
+pub struct SequenceContext<'w, 's> {
+    entity_query: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static EntityInstance,
+            &'static mut Transform,
+            Option<&'static mut TextureAtlas>,
+        ),
+    >,
+    player_query: Query<'w, 's, (Entity, &'static mut Inventory), With<Player>>,
+    fade_query: Query<'w, 's, &'static mut BackgroundColor, With<ScreenFade>>,
+    inventory_events: EventWriter<'w, InventoryChanged>,
+    level_events: EventWriter<'w, LevelEntered>,
+    quest_events: EventWriter<'w, StartQuest>,
+    story: ResMut<'w, StoryState>,
+    level_selection: ResMut<'w, LevelSelection>,
+    ldtk_projects: Query<'w, 's, &'static Handle<LdtkProject>>,
+    ldtk_project_assets: Res<'w, Assets<LdtkProject>>,
+}
+
+impl SequenceContext<'_, '_> {
+    fn find(&self, name: &str) -> Option<Entity> {
+        let found = self
+            .entity_query
+            .iter()
+            .find(|(_, entity_instance, _, _)| is_named(entity_instance, name))
+            .map(|(entity, ..)| entity);
+        if found.is_none() {
+            event!(Level::WARN, "Sequence entity {} not found", name);
+        }
+        found
+    }
+
+    /// Runs a command for one frame, returns whether it has finished.
+    fn run(&mut self, command: &Command, elapsed: f32, dt: f32, start: &mut Option<f32>) -> bool {
+        match command {
+            Command::MoveTo {
+                entity,
+                x,
+                y,
+                speed,
+            } => {
+                let Some(entity) = self.find(entity) else {
+                    return true;
+                };
+                let Ok((_, _, mut transform, _)) = self.entity_query.get_mut(entity) else {
+                    return true;
+                };
+                let target = Vec2::new(*x, *y);
+                let position = transform.translation.truncate();
+                let step = speed * dt;
+                let remaining = target - position;
+                let new_position = if remaining.length() <= step {
+                    target
+                } else {
+                    position + remaining.normalize() * step
+                };
+                transform.translation.x = new_position.x;
+                transform.translation.y = new_position.y;
+                new_position == target
+            }
+            Command::Animate {
+                entity,
+                first,
+                last,
+                frame_seconds,
+            } => {
+                let frames = last.saturating_sub(*first) + 1;
+                let frame = ((elapsed / frame_seconds) as usize).min(frames - 1);
+                if let Some(entity) = self.find(entity) {
+                    if let Ok((_, _, _, Some(mut texture_atlas))) = self.entity_query.get_mut(entity) {
+                        texture_atlas.index = first + frame;
+                    }
+                }
+                elapsed >= frames as f32 * frame_seconds
+            }
+            Command::Wait { seconds } => elapsed >= *seconds,
+            Command::Fade { alpha, seconds } => {
+                let Ok(mut background) = self.fade_query.get_single_mut() else {
+                    return true;
+                };
+                let from = *start.get_or_insert(background.0.alpha());
+                let t = if *seconds > 0. { (elapsed / seconds).min(1.) } else { 1. };
+                background.0.set_alpha(from + (alpha - from) * t);
+                t >= 1.
+            }
+            Command::GiveItem { item } => {
+                if let Ok((player, mut inventory)) = self.player_query.get_single_mut() {
+                    event!(Level::INFO, "Giving {} to the player", item);
+                    inventory.add(item.clone());
+                    self.inventory_events.send(InventoryChanged {
+                        entity: player,
+                        item: item.clone(),
+                    });
+                }
+                true
+            }
+            Command::SetFlag { flag } => {
+                self.story.set_flag(flag.clone());
+                true
+            }
+            Command::ClearFlag { flag } => {
+                self.story.clear_flag(flag);
+                true
+            }
+            Command::AddToCounter { counter, amount } => {
+                self.story.add_to_counter(counter.clone(), *amount);
+                true
+            }
+            Command::StartQuest { quest } => {
+                self.quest_events.send(StartQuest(quest.clone()));
+                true
+            }
+            Command::ChangeLevel { level, x, y } => {
+                let Some(ldtk_project) = self
+                    .ldtk_projects
+                    .get_single()
+                    .ok()
+                    .and_then(|handle| self.ldtk_project_assets.get(handle))
+                else {
+                    return true;
+                };
+                let Some(target) = ldtk_project
+                    .iter_raw_levels()
+                    .find(|raw_level| raw_level.identifier == *level || raw_level.iid == *level)
+                else {
+                    event!(Level::WARN, "Sequence level {} not found", level);
+                    return true;
+                };
+
+                // LDtk world coordinates go down, levels are placed by their bottom left corner
+                let origin = Vec2::new(target.world_x as f32, (-target.world_y - target.px_hei) as f32);
+                let local = Vec2::new(
+                    x.unwrap_or(target.px_wid as f32 / 2.),
+                    y.unwrap_or(target.px_hei as f32 / 2.),
+                );
+                if let Ok((player, _)) = self.player_query.get_single() {
+                    if let Ok((_, _, mut transform, _)) = self.entity_query.get_mut(player) {
+                        transform.translation.x = origin.x + local.x;
+                        transform.translation.y = origin.y + local.y;
+                    }
+                }
+                event!(Level::INFO, "Sequence changed level to {}", target.identifier);
+                *self.level_selection = LevelSelection::iid(target.iid.clone());
+                self.level_events.send(LevelEntered {
+                    iid: target.iid.clone(),
+                    identifier: target.identifier.clone(),
+                });
+                true
+            }
+            Command::Serial { .. } | Command::Parallel { .. } => {
+                unreachable!("groups are turned into steps")
+            }
+        }
+    }
+}
+
+pub fn spawn_screen_fade(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::srgba(0.0, 0.0, 0.0, 0.0).into(),
+            z_index: ZIndex::Global(100),
+            ..default()
+        },
+        ScreenFade,
+    ));
+}
+
+/// Starts sequences sent directly or by starting timeline actions.
+pub fn start_sequences(
+    mut play_events: EventReader<PlaySequence>,
+    mut action_started: EventReader<ActionStarted>,
+    timeline_query: Query<&Timeline>,
+    mut runner: ResMut<SequenceRunner>,
+) {
+    for PlaySequence(sequence) in play_events.read() {
+        runner.start(sequence, None);
+    }
+
+    for started in action_started.read() {
+        let sequence = timeline_query
+            .get(started.entity)
+            .ok()
+            .and_then(|timeline| timeline.action(&started.action))
+            .and_then(|action| action.sequence.as_ref());
+        if let Some(sequence) = sequence {
+            runner.start(sequence, Some((started.entity, started.action.clone())));
+        }
+    }
+}
+
+pub fn run_sequences(
+    time: Res<Time>,
+    mut runner: ResMut<SequenceRunner>,
+    mut input_lock: ResMut<InputLock>,
+    mut context: SequenceContext,
+    mut finished_events: EventWriter<SequenceFinished>,
+) {
+    if runner.is_running() {
+        input_lock.lock(INPUT_LOCK_REASON);
+    }
+
+    let dt = time.delta_seconds();
+    let mut finished = Vec::new();
+    runner.running.retain_mut(|sequence| {
+        let done = sequence.step.advance(dt, &mut context);
+        if done {
+            finished.extend(sequence.source.take());
+        }
+        !done
+    });
+
+    for (entity, action) in finished {
+        event!(Level::INFO, "Sequence of {} finished", action);
+        finished_events.send(SequenceFinished { entity, action });
+    }
+
+    if !runner.is_running() {
+        input_lock.unlock(INPUT_LOCK_REASON);
+    }
+}
+
+/// Completes actions which are nothing but a sequence, so that the ones depending on them can start.
+///
+/// Dialogue actions are completed by [`dialogue_input`](crate::dialogue::dialogue_input) instead.
+pub fn complete_sequence_actions(
+    mut finished_events: EventReader<SequenceFinished>,
+    mut timeline_query: Query<&mut Timeline>,
+) {
+    for finished in finished_events.read() {
+        let Ok(mut timeline) = timeline_query.get_mut(finished.entity) else {
+            continue;
+        };
+        if let Some(action) = timeline
+            .actions
+            .iter_mut()
+            .find(|action| action.name == finished.action)
+        {
+            if !action.is_dialogue() {
+                action.completed = true;
+            }
+        }
+    }
+}
+
+/// Plugin which runs [`Sequence`]s from timeline actions.
+pub struct SequencePlugin;
+
+impl Plugin for SequencePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SequenceRunner>()
+            .add_event::<PlaySequence>()
+            .add_event::<SequenceFinished>()
+            .add_systems(Startup, spawn_screen_fade)
+            .add_systems(
+                Update,
+                (start_sequences, run_sequences, complete_sequence_actions).chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        character::Character, inventory::InventoryPlugin, paths::GamePaths, story::StoryPlugin,
+        test_support::TestApp, timeline::TimelinePlugin,
+    };
+
+    fn sequence_app() -> TestApp {
+        let mut app = TestApp::new();
+        app.add_plugins((TimelinePlugin, StoryPlugin, InventoryPlugin, SequencePlugin));
+        app.world().init_resource::<GamePaths>();
+        // sent by sequences, but registered by plugins left out here
+        app.world().init_resource::<Events<LevelEntered>>();
+        app.world().init_resource::<Events<StartQuest>>();
+        app.load_level();
+        app
+    }
+
+    fn play(app: &mut TestApp, xml: &str) {
+        let sequence: Sequence = serde_xml_rs::from_str(xml).unwrap();
+        app.world().send_event(PlaySequence(sequence));
+    }
+
+    fn has_item(app: &mut TestApp, item: &str) -> bool {
+        app.single::<Inventory, With<Player>>().contains(item)
+    }
+
+    fn is_running(app: &mut TestApp) -> bool {
+        app.world().resource::<SequenceRunner>().is_running()
+    }
+
+    #[test]
+    fn nested_groups_parse() {
+        let sequence: Sequence = serde_xml_rs::from_str(
+            r#"<sequence>
+                <wait><seconds>1</seconds></wait>
+                <parallel>
+                    <give_item><item>Gem</item></give_item>
+                    <fade><alpha>1</alpha><seconds>0.5</seconds></fade>
+                </parallel>
+                <change_level><level>River_Scene_One</level></change_level>
+            </sequence>"#,
+        )
+        .unwrap();
+
+        assert_eq!(sequence.commands.len(), 3);
+        assert_eq!(
+            sequence.commands[1],
+            Command::Parallel {
+                commands: vec![
+                    Command::GiveItem {
+                        item: "Gem".to_string()
+                    },
+                    Command::Fade {
+                        alpha: 1.,
+                        seconds: 0.5
+                    },
+                ]
+            }
+        );
+        assert_eq!(
+            sequence.commands[2],
+            Command::ChangeLevel {
+                level: "River_Scene_One".to_string(),
+                x: None,
+                y: None
+            }
+        );
+    }
+
+    #[test]
+    fn commands_wait_for_the_one_before_them() {
+        let mut app = sequence_app();
+        play(
+            &mut app,
+            "<sequence><wait><seconds>0.5</seconds></wait><give_item><item>Gem</item></give_item></sequence>",
+        );
+
+        app.advance(15);
+        assert!(!has_item(&mut app, "Gem"));
+        assert!(app.world().resource::<InputLock>().is_locked());
+
+        assert!(app.run_until(30, |world| {
+            world
+                .query_filtered::<&Inventory, With<Player>>()
+                .single(world)
+                .contains("Gem")
+        }));
+        app.advance(1);
+        assert!(!is_running(&mut app));
+        assert!(!app.world().resource::<InputLock>().is_locked());
+    }
+
+    #[test]
+    fn parallel_commands_run_together() {
+        let mut app = sequence_app();
+        play(
+            &mut app,
+            "<sequence><parallel><wait><seconds>0.5</seconds></wait><give_item><item>Gem</item></give_item></parallel></sequence>",
+        );
+
+        app.advance(1);
+        assert!(has_item(&mut app, "Gem"));
+        assert!(is_running(&mut app));
+        app.advance(35);
+        assert!(!is_running(&mut app));
+    }
+
+    #[test]
+    fn move_to_walks_at_its_speed() {
+        let mut app = sequence_app();
+        let start = app.single::<Transform, With<Character>>().translation;
+        let target = start.truncate() + Vec2::new(30., 0.);
+        play(
+            &mut app,
+            &format!(
+                "<sequence><move_to><entity>William Hickman</entity><x>{}</x><y>{}</y></move_to></sequence>",
+                target.x, target.y
+            ),
+        );
+
+        // halfway at the default 60 pixels per second
+        app.advance(15);
+        let halfway = app.single::<Transform, With<Character>>().translation;
+        assert!((halfway.x - start.x - 15.).abs() < 1., "{start} -> {halfway}");
+
+        app.advance(20);
+        let end = app.single::<Transform, With<Character>>().translation.truncate();
+        assert!(end.distance(target) < 0.01, "{end} instead of {target}");
+        assert!(!is_running(&mut app));
+    }
+
+    #[test]
+    fn fade_blends_the_screen_to_black() {
+        let mut app = sequence_app();
+        play(
+            &mut app,
+            "<sequence><fade><alpha>1</alpha><seconds>0.5</seconds></fade></sequence>",
+        );
+
+        app.advance(15);
+        let alpha = app.single::<BackgroundColor, With<ScreenFade>>().0.alpha();
+        assert!(alpha > 0.3 && alpha < 0.7, "alpha {alpha}");
+
+        app.advance(20);
+        assert_eq!(app.single::<BackgroundColor, With<ScreenFade>>().0.alpha(), 1.);
+    }
+
+    #[test]
+    fn change_level_moves_the_player() {
+        let mut app = sequence_app();
+        play(
+            &mut app,
+            "<sequence><change_level><level>Test_Level</level><x>40</x><y>40</y></change_level></sequence>",
+        );
+        app.advance(2);
+
+        // the fixture level is 160 pixels high, at the world origin
+        let player = app.player_translation();
+        assert!((player.x - 40.).abs() < 1. && (player.y + 120.).abs() < 1., "player at {player}");
+        assert_eq!(
+            *app.world().resource::<LevelSelection>(),
+            LevelSelection::iid("7f1e4c20-8d7a-11ef-9a3c-0b5e2d1c4f01")
+        );
+    }
+
+    #[test]
+    fn story_commands_change_flags_and_counters() {
+        let mut app = sequence_app();
+        play(
+            &mut app,
+            "<sequence>
+                <set_flag><flag>met_hickman</flag></set_flag>
+                <add_to_counter><counter>gems</counter><amount>3</amount></add_to_counter>
+                <add_to_counter><counter>gems</counter></add_to_counter>
+            </sequence>",
+        );
+        app.advance(1);
+
+        let story = app.world().resource::<StoryState>();
+        assert!(story.flag("met_hickman"));
+        assert_eq!(story.counter("gems"), 4);
+    }
+}
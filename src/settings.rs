@@ -0,0 +1,429 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode},
+};
+
+use crate::{
+    audio::AudioSettings,
+    camera_script::CameraDirector,
+    clock::ClockSettings,
+    menu::GameState,
+    paths::GamePaths,
+    timeline::GameKeyCode,
+};
+
+/// Name of the settings file inside [`GamePaths::config`].
+pub const SETTINGS_FILE: &str = "settings.ron";
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl From<WindowModeSetting> for WindowMode {
+    fn from(mode: WindowModeSetting) -> Self {
+        match mode {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::Borderless => WindowMode::BorderlessFullscreen,
+            WindowModeSetting::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoSettings {
+    pub mode: WindowModeSetting,
+    pub width: u32,
+    pub height: u32,
+    pub vsync: bool,
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        VideoSettings {
+            mode: WindowModeSetting::Windowed,
+            width: 1280,
+            height: 704,
+            vsync: true,
+        }
+    }
+}
+
+/// Volumes from 0 to 1, see [`AudioSettings`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VolumeSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        let audio = AudioSettings::default();
+        VolumeSettings {
+            master: audio.master,
+            music: audio.music,
+            sfx: audio.sfx,
+        }
+    }
+}
+
+/// Key bindings, written as in timeline responses, like `"W"`, `"SPACE"` or `"Backquote"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    pub up: GameKeyCode,
+    pub down: GameKeyCode,
+    pub left: GameKeyCode,
+    pub right: GameKeyCode,
+    pub jump: GameKeyCode,
+    pub interact: GameKeyCode,
+    pub quest_log: GameKeyCode,
+    pub pause: GameKeyCode,
+    pub quick_save: GameKeyCode,
+    pub quick_load: GameKeyCode,
+    /// Confirms a choice, like buying in a shop
+    pub confirm: GameKeyCode,
+    /// Leaves a shop
+    pub cancel: GameKeyCode,
+    /// Switches between buying, selling and bartering
+    pub shop_mode: GameKeyCode,
+    pub skip_cutscene: GameKeyCode,
+    /// Opens the developer console
+    pub console: GameKeyCode,
+    /// Use the consumables in the order of `items/default.consumables.xml`
+    pub consumables: Vec<GameKeyCode>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls {
+            up: GameKeyCode(KeyCode::KeyW),
+            down: GameKeyCode(KeyCode::KeyS),
+            left: GameKeyCode(KeyCode::KeyA),
+            right: GameKeyCode(KeyCode::KeyD),
+            jump: GameKeyCode(KeyCode::Space),
+            interact: GameKeyCode(KeyCode::KeyE),
+            quest_log: GameKeyCode(KeyCode::KeyJ),
+            pause: GameKeyCode(KeyCode::KeyM),
+            quick_save: GameKeyCode(KeyCode::F5),
+            quick_load: GameKeyCode(KeyCode::F9),
+            confirm: GameKeyCode(KeyCode::Enter),
+            cancel: GameKeyCode(KeyCode::Escape),
+            shop_mode: GameKeyCode(KeyCode::Tab),
+            skip_cutscene: GameKeyCode(KeyCode::Space),
+            console: GameKeyCode(KeyCode::Backquote),
+            consumables: [
+                KeyCode::Digit1,
+                KeyCode::Digit2,
+                KeyCode::Digit3,
+                KeyCode::Digit4,
+                KeyCode::Digit5,
+                KeyCode::Digit6,
+                KeyCode::Digit7,
+                KeyCode::Digit8,
+                KeyCode::Digit9,
+            ]
+            .map(GameKeyCode)
+            .to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    /// Strength of camera shakes, 0 turns them off
+    pub screen_shake: f32,
+    /// Whether the scene is tinted with the time of day
+    pub day_night_tint: bool,
+    pub ui_scale: f32,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        AccessibilitySettings {
+            screen_shake: 1.,
+            day_night_tint: true,
+            ui_scale: 1.,
+        }
+    }
+}
+
+/// Player settings, loaded from and written back to [`SETTINGS_FILE`].
+///
+/// Missing fields fall back to their defaults, so older files keep loading.
+#[derive(Clone, Debug, PartialEq, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub video: VideoSettings,
+    pub audio: VolumeSettings,
+    pub controls: Controls,
+    /// Dialogue characters shown per second
+    pub text_speed: f32,
+    pub accessibility: AccessibilitySettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            video: VideoSettings::default(),
+            audio: VolumeSettings::default(),
+            controls: Controls::default(),
+            text_speed: 40.,
+            accessibility: AccessibilitySettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Brings out of range values back into range, returning what was fixed.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut clamp = |name: &str, value: &mut f32, min: f32, max: f32| {
+            if !(min..=max).contains(value) {
+                problems.push(format!("{} should be between {} and {}, was {}", name, min, max, value));
+                *value = value.clamp(min, max);
+            }
+        };
+
+        clamp("audio.master", &mut self.audio.master, 0., 1.);
+        clamp("audio.music", &mut self.audio.music, 0., 1.);
+        clamp("audio.sfx", &mut self.audio.sfx, 0., 1.);
+        clamp("text_speed", &mut self.text_speed, 1., 1000.);
+        clamp("accessibility.screen_shake", &mut self.accessibility.screen_shake, 0., 1.);
+        clamp("accessibility.ui_scale", &mut self.accessibility.ui_scale, 0.5, 3.);
+
+        let defaults = VideoSettings::default();
+        if self.video.width < 320 || self.video.height < 176 {
+            problems.push(format!(
+                "video resolution should be at least 320x176, was {}x{}",
+                self.video.width, self.video.height
+            ));
+            self.video.width = defaults.width;
+            self.video.height = defaults.height;
+        }
+
+        problems
+    }
+
+    /// Reads settings from `path`, using the defaults if it is missing or invalid.
+    pub fn load(path: &Path) -> Settings {
+        let mut settings = match std::fs::read_to_string(path) {
+            Ok(contents) => match ron::from_str(&contents) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    event!(Level::ERROR, "Invalid settings file {} | {}", path.display(), e);
+                    Settings::default()
+                }
+            },
+            Err(_) => {
+                event!(Level::INFO, "No settings file at {}, using defaults", path.display());
+                Settings::default()
+            }
+        };
+
+        for problem in settings.validate() {
+            event!(Level::WARN, "Settings | {}", problem);
+        }
+        settings
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, contents).map_err(|e| e.to_string())
+    }
+}
+
+/// Where [`Settings`] are written back to, nowhere if `None`.
+#[derive(Clone, Debug, Default, PartialEq, Resource)]
+pub struct SettingsPath(pub Option<PathBuf>);
+
+/// Text in the pause menu listing the settings that can be changed there.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct SettingsSummary;
+
+/// Pushes the settings into the window and the resources of other plugins.
+pub fn apply_settings(
+    settings: Res<Settings>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    audio_settings: Option<ResMut<AudioSettings>>,
+    clock_settings: Option<ResMut<ClockSettings>>,
+    camera_director: Option<ResMut<CameraDirector>>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    if let Ok(mut window) = window_query.get_single_mut() {
+        window.mode = settings.video.mode.into();
+        window
+            .resolution
+            .set(settings.video.width as f32, settings.video.height as f32);
+        window.present_mode = if settings.video.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+    }
+    if let Some(mut audio_settings) = audio_settings {
+        audio_settings.master = settings.audio.master;
+        audio_settings.music = settings.audio.music;
+        audio_settings.sfx = settings.audio.sfx;
+    }
+    if let Some(mut clock_settings) = clock_settings {
+        clock_settings.tint_enabled = settings.accessibility.day_night_tint;
+    }
+    if let Some(mut camera_director) = camera_director {
+        camera_director.shake_scale = settings.accessibility.screen_shake;
+        camera_director.skip_key = settings.controls.skip_cutscene.0;
+    }
+    ui_scale.0 = settings.accessibility.ui_scale;
+}
+
+/// Writes the settings back whenever they change after loading.
+pub fn save_settings(settings: Res<Settings>, path: Res<SettingsPath>) {
+    if settings.is_added() {
+        return;
+    }
+    let Some(path) = &path.0 else {
+        return;
+    };
+    match settings.save(path) {
+        Ok(()) => event!(Level::INFO, "Saved settings | {}", path.display()),
+        Err(e) => event!(Level::ERROR, "Error saving settings to {} | {}", path.display(), e),
+    }
+}
+
+/// Changes settings from the pause menu.
+///
+/// `[` and `]` change the master volume, F cycles the window mode, V toggles vsync,
+/// K toggles screen shake and T the day/night tint.
+pub fn settings_menu_input(input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<Settings>) {
+    if input.just_pressed(KeyCode::BracketLeft) {
+        settings.audio.master = (settings.audio.master - 0.1).max(0.);
+    }
+    if input.just_pressed(KeyCode::BracketRight) {
+        settings.audio.master = (settings.audio.master + 0.1).min(1.);
+    }
+    if input.just_pressed(KeyCode::KeyF) {
+        settings.video.mode = match settings.video.mode {
+            WindowModeSetting::Windowed => WindowModeSetting::Borderless,
+            WindowModeSetting::Borderless => WindowModeSetting::Fullscreen,
+            WindowModeSetting::Fullscreen => WindowModeSetting::Windowed,
+        };
+    }
+    if input.just_pressed(KeyCode::KeyV) {
+        settings.video.vsync = !settings.video.vsync;
+    }
+    if input.just_pressed(KeyCode::KeyK) {
+        settings.accessibility.screen_shake = if settings.accessibility.screen_shake > 0. { 0. } else { 1. };
+    }
+    if input.just_pressed(KeyCode::KeyT) {
+        settings.accessibility.day_night_tint = !settings.accessibility.day_night_tint;
+    }
+}
+
+pub fn update_settings_summary(
+    settings: Res<Settings>,
+    added_query: Query<(), Added<SettingsSummary>>,
+    mut summary_query: Query<&mut Text, With<SettingsSummary>>,
+) {
+    if !settings.is_changed() && added_query.is_empty() {
+        return;
+    }
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let summary = format!(
+        "[ ] Volume {:.0}%\nF   Window {:?}\nV   Vsync {}\nK   Screen shake {}\nT   Day/night tint {}",
+        settings.audio.master * 100.,
+        settings.video.mode,
+        on_off(settings.video.vsync),
+        on_off(settings.accessibility.screen_shake > 0.),
+        on_off(settings.accessibility.day_night_tint),
+    );
+    for mut text in &mut summary_query {
+        text.sections[0].value.clone_from(&summary);
+    }
+}
+
+/// Plugin which loads [`Settings`] when it is built and writes them back when they change.
+///
+/// Settings live in the [`GamePaths`] config folder unless `path` is set.
+#[derive(Default)]
+pub struct SettingsPlugin {
+    pub path: Option<PathBuf>,
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let path = self.path.clone().or_else(|| {
+            app.world()
+                .get_resource::<GamePaths>()
+                .map(|paths| paths.config.join(SETTINGS_FILE))
+        });
+        let settings = match &path {
+            Some(path) => Settings::load(path),
+            None => Settings::default(),
+        };
+
+        app.insert_resource(settings)
+            .insert_resource(SettingsPath(path))
+            .init_resource::<UiScale>()
+            .add_systems(
+                Update,
+                (
+                    settings_menu_input.run_if(in_state(GameState::Paused)),
+                    update_settings_summary,
+                    (apply_settings, save_settings).run_if(resource_changed::<Settings>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_settings_are_completed_and_validated() {
+        let mut settings: Settings =
+            ron::from_str("(audio: (master: 3.0), controls: (jump: \"W\"))").unwrap();
+        assert_eq!(settings.audio.music, VolumeSettings::default().music);
+        assert_eq!(settings.controls.jump, GameKeyCode(KeyCode::KeyW));
+        assert_eq!(settings.controls.up, GameKeyCode(KeyCode::KeyW));
+        assert_ne!(settings.controls.skip_cutscene, settings.controls.cancel);
+        assert_eq!(settings.controls.console, GameKeyCode(KeyCode::Backquote));
+        assert_eq!(settings.controls.consumables[8], GameKeyCode(KeyCode::Digit9));
+
+        let rebound: Settings =
+            ron::from_str("(controls: (console: \"F1\", consumables: [\"Numpad1\", \"Q\"]))").unwrap();
+        assert_eq!(rebound.controls.console, GameKeyCode(KeyCode::F1));
+        assert_eq!(
+            rebound.controls.consumables,
+            vec![GameKeyCode(KeyCode::Numpad1), GameKeyCode(KeyCode::KeyQ)]
+        );
+
+        let problems = settings.validate();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(settings.audio.master, 1.);
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = Settings::default();
+        let contents = ron::ser::to_string_pretty(&settings, ron::ser::PrettyConfig::default()).unwrap();
+        assert_eq!(ron::from_str::<Settings>(&contents).unwrap(), settings);
+    }
+}
@@ -0,0 +1,439 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::ColliderDisabled;
+
+use crate::{
+    clock::ActiveHours,
+    console::{ConsoleAppExt, ConsoleCommand},
+};
+
+/// Value of a story flag or counter.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StoryValue {
+    Flag(bool),
+    Counter(i64),
+}
+
+impl StoryValue {
+    fn as_int(self) -> i64 {
+        match self {
+            StoryValue::Flag(flag) => flag as i64,
+            StoryValue::Counter(count) => count,
+        }
+    }
+
+    fn is_truthy(self) -> bool {
+        self.as_int() != 0
+    }
+}
+
+/// Story progress shared by every timeline, like `met_hickman` or `gold_found`.
+///
+/// Unset flags are false and unset counters are zero.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Resource, Serialize, Deserialize)]
+pub struct StoryState {
+    values: BTreeMap<String, StoryValue>,
+}
+
+impl StoryState {
+    pub fn get(&self, name: &str) -> Option<StoryValue> {
+        self.values.get(name).copied()
+    }
+
+    /// Names of every flag and counter set so far.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    pub fn set_flag(&mut self, flag: impl Into<String>) {
+        let flag = flag.into();
+        event!(Level::INFO, "Story flag set | {}", flag);
+        self.values.insert(flag, StoryValue::Flag(true));
+    }
+
+    pub fn clear_flag(&mut self, flag: &str) {
+        event!(Level::INFO, "Story flag cleared | {}", flag);
+        self.values.insert(flag.to_string(), StoryValue::Flag(false));
+    }
+
+    pub fn flag(&self, flag: &str) -> bool {
+        self.get(flag).is_some_and(StoryValue::is_truthy)
+    }
+
+    pub fn counter(&self, counter: &str) -> i64 {
+        self.get(counter).map_or(0, StoryValue::as_int)
+    }
+
+    pub fn set_counter(&mut self, counter: impl Into<String>, value: i64) {
+        let counter = counter.into();
+        event!(Level::INFO, "Story counter {} set to {}", counter, value);
+        self.values.insert(counter, StoryValue::Counter(value));
+    }
+
+    pub fn add_to_counter(&mut self, counter: impl Into<String>, amount: i64) {
+        let counter = counter.into();
+        let value = self.counter(&counter) + amount;
+        self.set_counter(counter, value);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Boolean expression over the [`StoryState`].
+///
+/// Names are flags or counters, combined with `!`, `&&`, `||`, parentheses
+/// and integer comparisons: `met_hickman && !(gold_found || mobs_defeated >= 3)`.
+/// `and`, `or` and `not` can be used instead of the symbols, which is easier
+/// to write in XML.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Condition {
+    Literal(i64),
+    Value(String),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Compare(Box<Condition>, Comparison, Box<Condition>),
+}
+
+impl Condition {
+    fn value(&self, story: &StoryState) -> i64 {
+        match self {
+            Condition::Literal(value) => *value,
+            Condition::Value(name) => story.counter(name),
+            condition => condition.evaluate(story) as i64,
+        }
+    }
+
+    pub fn evaluate(&self, story: &StoryState) -> bool {
+        match self {
+            Condition::Literal(_) | Condition::Value(_) => self.value(story) != 0,
+            Condition::Not(condition) => !condition.evaluate(story),
+            Condition::And(left, right) => left.evaluate(story) && right.evaluate(story),
+            Condition::Or(left, right) => left.evaluate(story) || right.evaluate(story),
+            Condition::Compare(left, comparison, right) => {
+                let (left, right) = (left.value(story), right.value(story));
+                match comparison {
+                    Comparison::Equal => left == right,
+                    Comparison::NotEqual => left != right,
+                    Comparison::Less => left < right,
+                    Comparison::LessOrEqual => left <= right,
+                    Comparison::Greater => left > right,
+                    Comparison::GreaterOrEqual => left >= right,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Name(String),
+    Number(i64),
+    Not,
+    And,
+    Or,
+    Compare(Comparison),
+    Open,
+    Close,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '-' {
+            let mut number = String::from(c);
+            chars.next();
+            while let Some(&digit) = chars.peek().filter(|digit| digit.is_ascii_digit()) {
+                number.push(digit);
+                chars.next();
+            }
+            tokens.push(Token::Number(
+                number.parse().map_err(|_| format!("Invalid number {}", number))?,
+            ));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while let Some(&letter) = chars.peek().filter(|letter| letter.is_alphanumeric() || **letter == '_') {
+                name.push(letter);
+                chars.next();
+            }
+            tokens.push(match name.as_str() {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "not" => Token::Not,
+                "true" => Token::Number(1),
+                "false" => Token::Number(0),
+                _ => Token::Name(name),
+            });
+        } else {
+            chars.next();
+            let next = chars.peek().copied();
+            let (token, two_chars) = match (c, next) {
+                ('&', Some('&')) => (Token::And, true),
+                ('|', Some('|')) => (Token::Or, true),
+                ('=', Some('=')) => (Token::Compare(Comparison::Equal), true),
+                ('!', Some('=')) => (Token::Compare(Comparison::NotEqual), true),
+                ('<', Some('=')) => (Token::Compare(Comparison::LessOrEqual), true),
+                ('>', Some('=')) => (Token::Compare(Comparison::GreaterOrEqual), true),
+                ('<', _) => (Token::Compare(Comparison::Less), false),
+                ('>', _) => (Token::Compare(Comparison::Greater), false),
+                ('!', _) => (Token::Not, false),
+                ('(', _) => (Token::Open, false),
+                (')', _) => (Token::Close, false),
+                _ => return Err(format!("Unexpected character '{}'", c)),
+            };
+            if two_chars {
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens, lowest precedence first: or, and, not, comparison.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Condition, String> {
+        let left = self.primary()?;
+        if let Some(Token::Compare(comparison)) = self.peek().cloned() {
+            self.next();
+            let right = self.primary()?;
+            return Ok(Condition::Compare(Box::new(left), comparison, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn primary(&mut self) -> Result<Condition, String> {
+        match self.next() {
+            Some(Token::Name(name)) => Ok(Condition::Value(name)),
+            Some(Token::Number(number)) => Ok(Condition::Literal(number)),
+            Some(Token::Open) => {
+                let condition = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(condition),
+                    _ => Err("Missing closing parenthesis".to_string()),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Unexpected end of condition".to_string()),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let condition = parser.or()?;
+        match parser.peek() {
+            None => Ok(condition),
+            Some(token) => Err(format!("Unexpected {:?} in condition {}", token, s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Hides an LDtk entity and disables its collider while its `condition` field is false.
+///
+/// Gates NPCs, mobs, chests and doors on story progress.
+#[derive(Clone, Debug, Eq, PartialEq, Component)]
+pub struct StoryGate(pub Condition);
+
+/// Adds a [`StoryGate`] to every LDtk entity with a `condition` field.
+pub fn add_story_gates(
+    mut commands: Commands,
+    entity_query: Query<(Entity, &EntityInstance), Added<EntityInstance>>,
+) {
+    for (entity, entity_instance) in &entity_query {
+        let Ok(source) = entity_instance.get_string_field("condition") else {
+            continue;
+        };
+        match source.parse() {
+            Ok(condition) => {
+                commands.entity(entity).insert(StoryGate(condition));
+            }
+            Err(e) => {
+                event!(Level::WARN, "Invalid condition on {} | {}", entity_instance.identifier, e);
+            }
+        }
+    }
+}
+
+/// Gated entities with [`ActiveHours`] are shown and hidden by
+/// [`apply_active_hours`](crate::clock::apply_active_hours) instead.
+pub fn apply_story_gates(
+    mut commands: Commands,
+    story: Res<StoryState>,
+    added_gates: Query<(), Added<StoryGate>>,
+    mut gate_query: Query<(Entity, &StoryGate, &mut Visibility), Without<ActiveHours>>,
+) {
+    if !story.is_changed() && added_gates.is_empty() {
+        return;
+    }
+
+    for (entity, gate, mut visibility) in &mut gate_query {
+        let open = gate.0.evaluate(&story);
+        let new_visibility = if open {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+            if open {
+                commands.entity(entity).remove::<ColliderDisabled>();
+            } else {
+                commands.entity(entity).insert(ColliderDisabled);
+            }
+        }
+    }
+}
+
+/// `flag set <name>` and `flag clear <name>`: changes a story flag.
+pub struct FlagCommand;
+
+impl ConsoleCommand for FlagCommand {
+    fn name(&self) -> &'static str {
+        "flag"
+    }
+
+    fn usage(&self) -> &'static str {
+        "set|clear <name>"
+    }
+
+    fn complete(&self, world: &mut World, args: &[&str]) -> Vec<String> {
+        match args.len() {
+            1 => vec!["set".to_string(), "clear".to_string()],
+            2 => world.resource::<StoryState>().names().map(String::from).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        let name = *args.get(1).ok_or("Missing flag name")?;
+        let mut story = world.resource_mut::<StoryState>();
+        match args[0] {
+            "set" => story.set_flag(name),
+            "clear" => story.clear_flag(name),
+            other => return Err(format!("Expected set or clear, got {}", other)),
+        }
+        Ok(format!("{} is {}", name, story.flag(name)))
+    }
+}
+
+pub struct StoryPlugin;
+
+impl Plugin for StoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StoryState>()
+            .add_systems(Update, (add_story_gates, apply_story_gates).chain())
+            .add_console_command(FlagCommand);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions_evaluate_against_story() {
+        let mut story = StoryState::default();
+        story.set_flag("met_hickman");
+        story.set_counter("mobs_defeated", 2);
+
+        let check = |source: &str| source.parse::<Condition>().unwrap().evaluate(&story);
+        assert!(check("met_hickman"));
+        assert!(!check("gold_found"));
+        assert!(check("met_hickman && !gold_found"));
+        assert!(check("gold_found or mobs_defeated >= 2"));
+        assert!(check("not (met_hickman and mobs_defeated > 2)"));
+        assert!(check("mobs_defeated == 2 && true"));
+    }
+
+    #[test]
+    fn invalid_conditions_are_rejected() {
+        assert!("met_hickman &&".parse::<Condition>().is_err());
+        assert!("(gold_found".parse::<Condition>().is_err());
+        assert!("gold_found $ 3".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn story_round_trips_through_json() {
+        let mut story = StoryState::default();
+        story.set_flag("gold_found");
+        story.add_to_counter("gems", 3);
+
+        let json = serde_json::to_string(&story).unwrap();
+        assert_eq!(serde_json::from_str::<StoryState>(&json).unwrap(), story);
+    }
+}
@@ -0,0 +1,369 @@
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_common_assets::xml::XmlAssetPlugin;
+use bevy_rapier2d::dynamics::Velocity;
+
+use crate::{
+    clock::GameClock,
+    health::Damage,
+    inventory::{Inventory, InventoryChanged},
+    player::{InputLock, Player},
+    settings::Settings,
+    terrain::CurrentTerrain,
+};
+
+/// Highest value of each need, fully satisfied
+pub const NEED_MAX: f32 = 100.;
+
+/// Thirst, hunger and fatigue of the player, from [`NEED_MAX`] (satisfied) down to 0.
+#[derive(Copy, Clone, PartialEq, Debug, Component)]
+pub struct Needs {
+    pub thirst: f32,
+    pub hunger: f32,
+    pub fatigue: f32,
+}
+
+impl Default for Needs {
+    fn default() -> Self {
+        Needs {
+            thirst: NEED_MAX,
+            hunger: NEED_MAX,
+            fatigue: NEED_MAX,
+        }
+    }
+}
+
+impl Needs {
+    /// Whether any need has run out.
+    pub fn depleted(&self) -> bool {
+        self.thirst <= 0. || self.hunger <= 0. || self.fatigue <= 0.
+    }
+
+    /// Lowers needs over `hours` of in-game time, `heat` comes from the terrain.
+    ///
+    /// Resting recovers fatigue instead of using it up.
+    pub fn decay(&mut self, settings: &SurvivalSettings, hours: f32, heat: f32, resting: bool) {
+        let heat = 1. + heat;
+        self.thirst -= settings.thirst_per_hour * heat * hours;
+        self.hunger -= settings.hunger_per_hour * hours;
+        if resting {
+            self.fatigue += settings.rest_recovery_per_hour * hours;
+        } else {
+            self.fatigue -= settings.fatigue_per_hour * heat * hours;
+        }
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        self.thirst = self.thirst.clamp(0., NEED_MAX);
+        self.hunger = self.hunger.clamp(0., NEED_MAX);
+        self.fatigue = self.fatigue.clamp(0., NEED_MAX);
+    }
+}
+
+/// Rates of change of [`Needs`], per in-game hour of the [`GameClock`].
+#[derive(Clone, PartialEq, Debug, Resource)]
+pub struct SurvivalSettings {
+    pub thirst_per_hour: f32,
+    pub hunger_per_hour: f32,
+    pub fatigue_per_hour: f32,
+    /// Fatigue recovered while standing still
+    pub rest_recovery_per_hour: f32,
+    /// Speed at or below which the player counts as resting
+    pub rest_speed: f32,
+    /// Movement speed multiplier while any need is depleted
+    pub depleted_speed_multiplier: f32,
+    /// Health lost while any need is depleted
+    pub depleted_damage_per_hour: f32,
+}
+
+impl Default for SurvivalSettings {
+    fn default() -> Self {
+        SurvivalSettings {
+            thirst_per_hour: 25.,
+            hunger_per_hour: 12.5,
+            fatigue_per_hour: 10.,
+            rest_recovery_per_hour: 100.,
+            rest_speed: 5.,
+            depleted_speed_multiplier: 0.6,
+            depleted_damage_per_hour: 100.,
+        }
+    }
+}
+
+impl SurvivalSettings {
+    pub fn speed_multiplier(&self, needs: &Needs) -> f32 {
+        if needs.depleted() {
+            self.depleted_speed_multiplier
+        } else {
+            1.
+        }
+    }
+}
+
+/// What using an item restores.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct Consumable {
+    pub item: String,
+    #[serde(default)]
+    pub thirst: f32,
+    #[serde(default)]
+    pub hunger: f32,
+    #[serde(default)]
+    pub fatigue: f32,
+    #[serde(default)]
+    pub health: f32,
+}
+
+/// Items that can be used, loaded from a `*.consumables.xml` asset.
+///
+/// The keys 1 to 9 use the consumables in the order they are listed.
+#[derive(Asset, Clone, Debug, Default, PartialEq, Deserialize, TypePath)]
+pub struct Consumables {
+    #[serde(rename = "consumable", default)]
+    pub consumables: Vec<Consumable>,
+}
+
+impl Consumables {
+    pub fn get(&self, item: &str) -> Option<&Consumable> {
+        self.consumables.iter().find(|consumable| consumable.item == item)
+    }
+}
+
+#[derive(Resource)]
+pub struct ConsumablesHandle(pub Handle<Consumables>);
+
+/// Uses up one of an item from an entity's inventory.
+#[derive(Clone, Debug, Eq, PartialEq, Event)]
+pub struct UseItem {
+    pub entity: Entity,
+    pub item: String,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Component)]
+pub enum NeedsBar {
+    Thirst,
+    Hunger,
+    Fatigue,
+}
+
+fn load_consumables(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ConsumablesHandle(asset_server.load("items/default.consumables.xml")));
+}
+
+pub fn decay_needs(
+    clock: Res<GameClock>,
+    settings: Res<SurvivalSettings>,
+    mut query: Query<(&mut Needs, Option<&CurrentTerrain>, Option<&Velocity>)>,
+) {
+    for (mut needs, current_terrain, velocity) in &mut query {
+        let heat = current_terrain.map_or(0., |terrain| terrain.0.heat);
+        let resting = velocity.map_or(true, |velocity| velocity.linvel.length() <= settings.rest_speed);
+        needs.decay(&settings, clock.delta_hours(), heat, resting);
+    }
+}
+
+pub fn apply_need_penalties(
+    clock: Res<GameClock>,
+    settings: Res<SurvivalSettings>,
+    query: Query<(Entity, &Needs)>,
+    mut damage_events: EventWriter<Damage>,
+) {
+    for (entity, needs) in &query {
+        if needs.depleted() {
+            damage_events.send(Damage {
+                target: entity,
+                amount: settings.depleted_damage_per_hour * clock.delta_hours(),
+            });
+        }
+    }
+}
+
+pub fn item_hotkeys(
+    input: Res<ButtonInput<KeyCode>>,
+    input_lock: Res<InputLock>,
+    settings: Res<Settings>,
+    consumables_handle: Res<ConsumablesHandle>,
+    consumables: Res<Assets<Consumables>>,
+    player_query: Query<Entity, With<Player>>,
+    mut use_events: EventWriter<UseItem>,
+) {
+    let (Some(consumables), Ok(player)) = (consumables.get(&consumables_handle.0), player_query.get_single()) else {
+        return;
+    };
+    if input_lock.is_locked() {
+        return;
+    }
+
+    for (key, consumable) in settings.controls.consumables.iter().zip(&consumables.consumables) {
+        if input.just_pressed(key.0) {
+            use_events.send(UseItem {
+                entity: player,
+                item: consumable.item.clone(),
+            });
+        }
+    }
+}
+
+pub fn use_items(
+    mut use_events: EventReader<UseItem>,
+    consumables_handle: Res<ConsumablesHandle>,
+    consumables: Res<Assets<Consumables>>,
+    mut query: Query<(&mut Inventory, &mut Needs)>,
+    mut inventory_events: EventWriter<InventoryChanged>,
+    mut damage_events: EventWriter<Damage>,
+) {
+    let Some(consumables) = consumables.get(&consumables_handle.0) else {
+        return;
+    };
+
+    for use_item in use_events.read() {
+        let Some(consumable) = consumables.get(&use_item.item) else {
+            event!(Level::WARN, "{} can't be used", use_item.item);
+            continue;
+        };
+        let Ok((mut inventory, mut needs)) = query.get_mut(use_item.entity) else {
+            continue;
+        };
+        if !inventory.remove(&use_item.item) {
+            event!(Level::INFO, "No {} left", use_item.item);
+            continue;
+        }
+
+        event!(Level::INFO, "Used {}", use_item.item);
+        needs.thirst += consumable.thirst;
+        needs.hunger += consumable.hunger;
+        needs.fatigue += consumable.fatigue;
+        needs.clamp();
+        if consumable.health != 0. {
+            // negative damage heals
+            damage_events.send(Damage {
+                target: use_item.entity,
+                amount: -consumable.health,
+            });
+        }
+        inventory_events.send(InventoryChanged {
+            entity: use_item.entity,
+            item: use_item.item.clone(),
+        });
+    }
+}
+
+pub fn spawn_needs_hud(mut commands: Commands, added_query: Query<(), (Added<Needs>, With<Player>)>) {
+    if added_query.is_empty() {
+        return;
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(16.0),
+                right: Val::Px(16.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for (bar, color) in [
+                (NeedsBar::Thirst, Color::srgb(0.3, 0.6, 1.0)),
+                (NeedsBar::Hunger, Color::srgb(0.9, 0.5, 0.2)),
+                (NeedsBar::Fatigue, Color::srgb(0.7, 0.7, 0.3)),
+            ] {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(80.0),
+                            height: Val::Px(6.0),
+                            ..default()
+                        },
+                        background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+                        ..default()
+                    })
+                    .with_children(|background| {
+                        background.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(100.0),
+                                    height: Val::Percent(100.0),
+                                    ..default()
+                                },
+                                background_color: color.into(),
+                                ..default()
+                            },
+                            bar,
+                        ));
+                    });
+            }
+        });
+}
+
+pub fn update_needs_hud(
+    needs_query: Query<&Needs, (With<Player>, Changed<Needs>)>,
+    mut bar_query: Query<(&NeedsBar, &mut Style)>,
+) {
+    let Ok(needs) = needs_query.get_single() else {
+        return;
+    };
+    for (bar, mut style) in &mut bar_query {
+        let value = match bar {
+            NeedsBar::Thirst => needs.thirst,
+            NeedsBar::Hunger => needs.hunger,
+            NeedsBar::Fatigue => needs.fatigue,
+        };
+        style.width = Val::Percent(value / NEED_MAX * 100.);
+    }
+}
+
+/// Plugin for thirst, hunger and fatigue, with items from `items/default.consumables.xml`.
+pub struct SurvivalPlugin;
+
+impl Plugin for SurvivalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(XmlAssetPlugin::<Consumables>::new(&["consumables.xml"]))
+            .init_resource::<SurvivalSettings>()
+            .add_event::<UseItem>()
+            .add_systems(Startup, load_consumables)
+            .add_systems(
+                Update,
+                (
+                    decay_needs,
+                    apply_need_penalties,
+                    item_hotkeys,
+                    use_items,
+                    spawn_needs_hud,
+                    update_needs_hud,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heat_speeds_up_thirst_and_rest_recovers_fatigue() {
+        let settings = SurvivalSettings::default();
+
+        let mut shade = Needs::default();
+        shade.decay(&settings, 1., 0., false);
+        let mut hot_rock = Needs::default();
+        hot_rock.decay(&settings, 1., 2., false);
+        assert_eq!(NEED_MAX - hot_rock.thirst, 3. * (NEED_MAX - shade.thirst));
+        assert_eq!(hot_rock.hunger, shade.hunger);
+
+        let mut resting = Needs {
+            fatigue: 50.,
+            ..default()
+        };
+        resting.decay(&settings, 0.1, 0., true);
+        assert!(resting.fatigue > 50.);
+        assert!(!resting.depleted());
+    }
+}
@@ -0,0 +1,316 @@
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_common_assets::xml::XmlAssetPlugin;
+use bevy_ecs_ldtk::{ldtk::Type, prelude::*};
+
+use crate::{health::Damage, walls::Wall};
+
+fn yes() -> bool {
+    true
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+/// Gameplay properties of an IntGrid value.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TerrainProperties {
+    /// IntGrid value, 0 is bare ground
+    pub value: i32,
+    pub name: String,
+    /// Unwalkable terrain gets wall colliders
+    #[serde(default = "yes")]
+    pub walkable: bool,
+    #[serde(default = "one")]
+    pub speed_multiplier: f32,
+    /// Sound played for each step, relative to the asset folder
+    pub footstep: Option<String>,
+    #[serde(default)]
+    pub damage_per_second: f32,
+    /// How quickly movement responds to input, from 1 (instant) down to 0 (ice)
+    #[serde(default = "one")]
+    pub traction: f32,
+    /// Whether entities standing in it are hidden, like tall brush
+    #[serde(default)]
+    pub hides: bool,
+    /// Extra thirst and fatigue, 1 doubles how fast they drop
+    #[serde(default)]
+    pub heat: f32,
+}
+
+/// Properties of any IntGrid value missing from the terrain table.
+pub static DEFAULT_TERRAIN: TerrainProperties = TerrainProperties {
+    value: 0,
+    name: String::new(),
+    walkable: true,
+    speed_multiplier: 1.0,
+    footstep: None,
+    damage_per_second: 0.,
+    traction: 1.0,
+    hides: false,
+    heat: 0.,
+};
+
+impl Default for TerrainProperties {
+    fn default() -> Self {
+        DEFAULT_TERRAIN.clone()
+    }
+}
+
+/// Terrain properties keyed by IntGrid value, loaded from a `*.terrain.xml` asset.
+#[derive(Asset, Clone, Debug, Default, PartialEq, Deserialize, TypePath)]
+pub struct TerrainTable {
+    #[serde(rename = "terrain", default)]
+    pub terrains: Vec<TerrainProperties>,
+}
+
+impl TerrainTable {
+    pub fn get(&self, value: i32) -> &TerrainProperties {
+        self.terrains
+            .iter()
+            .find(|terrain| terrain.value == value)
+            .unwrap_or(&DEFAULT_TERRAIN)
+    }
+
+    /// Describes every problem with the table.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut seen = HashSet::new();
+        for terrain in &self.terrains {
+            if !seen.insert(terrain.value) {
+                errors.push(format!("Terrain value {} is defined more than once", terrain.value));
+            }
+            if terrain.speed_multiplier < 0. {
+                errors.push(format!("Terrain {} has a negative speed multiplier", terrain.name));
+            }
+            if terrain.traction <= 0. || terrain.traction > 1. {
+                errors.push(format!("Terrain {} has traction outside of (0, 1]", terrain.name));
+            }
+            if terrain.heat < 0. {
+                errors.push(format!("Terrain {} has negative heat", terrain.name));
+            }
+        }
+        errors
+    }
+}
+
+#[derive(Resource)]
+pub struct TerrainTableHandle(pub Handle<TerrainTable>);
+
+/// Terrain under a moving entity, kept up to date by [`detect_terrain`].
+#[derive(Clone, Debug, Default, PartialEq, Component)]
+pub struct CurrentTerrain(pub TerrainProperties);
+
+/// Sent when an entity with [`CurrentTerrain`] steps onto a different IntGrid value.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Event)]
+pub struct TerrainChanged {
+    pub entity: Entity,
+    pub from: i32,
+    pub to: i32,
+}
+
+/// Entities standing on terrain that hides them.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Hidden;
+
+/// Marks IntGrid cells already checked against the terrain table.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct TerrainChecked;
+
+/// IntGrid value at `position`, relative to the bottom left of a level.
+///
+/// The first IntGrid layer with a non-zero value at that position wins.
+pub fn int_grid_value_at(layer_instances: &[LayerInstance], position: Vec2) -> i32 {
+    layer_instances
+        .iter()
+        .filter(|layer| layer.layer_instance_type == Type::IntGrid)
+        .find_map(|layer| {
+            let x = (position.x / layer.grid_size as f32).floor() as i32;
+            // LDtk rows go top to bottom
+            let y = layer.c_hei - 1 - (position.y / layer.grid_size as f32).floor() as i32;
+            if x < 0 || y < 0 || x >= layer.c_wid || y >= layer.c_hei {
+                return None;
+            }
+            layer
+                .int_grid_csv
+                .get((y * layer.c_wid + x) as usize)
+                .copied()
+                .filter(|value| *value != 0)
+        })
+        .unwrap_or(0)
+}
+
+fn load_terrain_table(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TerrainTableHandle(
+        asset_server.load("terrain/default.terrain.xml"),
+    ));
+}
+
+fn validate_terrain_table(
+    mut asset_events: EventReader<AssetEvent<TerrainTable>>,
+    tables: Res<Assets<TerrainTable>>,
+) {
+    for asset_event in asset_events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } = asset_event {
+            if let Some(table) = tables.get(*id) {
+                for e in table.validate() {
+                    event!(Level::WARN, "{}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Flags IntGrid cells of unwalkable terrain as walls,
+/// so that [`spawn_wall_collision`](crate::walls::spawn_wall_collision) blocks them.
+pub fn block_unwalkable_terrain(
+    mut commands: Commands,
+    cell_query: Query<(Entity, &IntGridCell), (Without<Wall>, Without<TerrainChecked>)>,
+    table_handle: Res<TerrainTableHandle>,
+    tables: Res<Assets<TerrainTable>>,
+) {
+    let Some(table) = tables.get(&table_handle.0) else {
+        return;
+    };
+
+    for (entity, cell) in &cell_query {
+        let mut entity_commands = commands.entity(entity);
+        if !table.get(cell.value).walkable {
+            entity_commands.insert(Wall);
+        }
+        entity_commands.insert(TerrainChecked);
+    }
+}
+
+pub fn detect_terrain(
+    mut walker_query: Query<(Entity, &GlobalTransform, &mut CurrentTerrain)>,
+    level_query: Query<(&LevelIid, &GlobalTransform)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    table_handle: Res<TerrainTableHandle>,
+    tables: Res<Assets<TerrainTable>>,
+    mut terrain_changed: EventWriter<TerrainChanged>,
+) {
+    let (Some(table), Ok(project_handle)) = (tables.get(&table_handle.0), ldtk_projects.get_single()) else {
+        return;
+    };
+    let Some(ldtk_project) = ldtk_project_assets.get(project_handle) else {
+        return;
+    };
+
+    for (entity, walker_transform, mut current_terrain) in &mut walker_query {
+        let position = walker_transform.translation().truncate();
+
+        let value = level_query
+            .iter()
+            .find_map(|(level_iid, level_transform)| {
+                let level = ldtk_project.get_raw_level_by_iid(&level_iid.to_string())?;
+                let local = position - level_transform.translation().truncate();
+                let inside = local.x >= 0.
+                    && local.y >= 0.
+                    && local.x < level.px_wid as f32
+                    && local.y < level.px_hei as f32;
+                inside.then(|| int_grid_value_at(level.layer_instances.as_deref().unwrap_or(&[]), local))
+            })
+            .unwrap_or(0);
+
+        let terrain = table.get(value);
+        if current_terrain.0 != *terrain {
+            if current_terrain.0.value != value {
+                terrain_changed.send(TerrainChanged {
+                    entity,
+                    from: current_terrain.0.value,
+                    to: value,
+                });
+            }
+            current_terrain.0 = terrain.clone();
+        }
+    }
+}
+
+pub fn apply_terrain_damage(
+    time: Res<Time>,
+    walker_query: Query<(Entity, &CurrentTerrain)>,
+    mut damage_events: EventWriter<Damage>,
+) {
+    for (entity, current_terrain) in &walker_query {
+        if current_terrain.0.damage_per_second != 0. {
+            damage_events.send(Damage {
+                target: entity,
+                amount: current_terrain.0.damage_per_second * time.delta_seconds(),
+            });
+        }
+    }
+}
+
+/// Marks entities on hiding terrain as [`Hidden`] and fades their sprite.
+pub fn apply_terrain_hiding(
+    mut commands: Commands,
+    mut walker_query: Query<(Entity, &CurrentTerrain, Option<&mut Sprite>), Changed<CurrentTerrain>>,
+) {
+    for (entity, current_terrain, sprite) in &mut walker_query {
+        let alpha = if current_terrain.0.hides {
+            commands.entity(entity).insert(Hidden);
+            0.5
+        } else {
+            commands.entity(entity).remove::<Hidden>();
+            1.0
+        };
+        if let Some(mut sprite) = sprite {
+            sprite.color.set_alpha(alpha);
+        }
+    }
+}
+
+/// Plugin which gives IntGrid values gameplay meaning from `terrain/default.terrain.xml`.
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(XmlAssetPlugin::<TerrainTable>::new(&["terrain.xml"]))
+            .add_event::<TerrainChanged>()
+            .add_systems(Startup, load_terrain_table)
+            .add_systems(
+                Update,
+                (
+                    validate_terrain_table,
+                    block_unwalkable_terrain,
+                    (detect_terrain, apply_terrain_damage, apply_terrain_hiding).chain(),
+                ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_parses_and_falls_back_to_ground() {
+        let table: TerrainTable = serde_xml_rs::from_str(
+            r#"<terrains>
+                <terrain>
+                    <value>6</value>
+                    <name>mud</name>
+                    <speed_multiplier>0.5</speed_multiplier>
+                    <footstep>sounds/footsteps/mud.wav</footstep>
+                </terrain>
+                <terrain>
+                    <value>3</value>
+                    <name>stone</name>
+                    <walkable>false</walkable>
+                </terrain>
+            </terrains>"#,
+        )
+        .unwrap();
+
+        assert!(table.validate().is_empty());
+        assert_eq!(table.get(6).speed_multiplier, 0.5);
+        assert_eq!(table.get(6).traction, 1.0);
+        assert!(!table.get(3).walkable);
+        assert_eq!(table.get(42), &DEFAULT_TERRAIN);
+    }
+}
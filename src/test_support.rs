@@ -0,0 +1,284 @@
+use std::time::Duration;
+
+use bevy::{
+    ecs::query::{QueryData, QueryFilter},
+    input::{
+        keyboard::{Key, KeyboardInput, NativeKey},
+        ButtonState, InputPlugin,
+    },
+    prelude::*,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    character, climbing, collision_layers, colliders, enemy, health, misc_objects, physics_mode,
+    player, settings, survival, terrain, walls, water, weather,
+};
+
+/// LDtk project used by default, relative to the `assets` folder.
+///
+/// A single 20x10 level with walls along the border, a ladder, a stone block,
+/// a water channel, the Player, a Character and a patrolling Mob, in the rain. The Player's
+/// timeline is `timelines/test_fixture.xml`.
+pub const FIXTURE_PROJECT: &str = "test_fixture.ldtk";
+
+/// Simulated duration of one frame.
+pub const FRAME: Duration = Duration::from_micros(16_667);
+
+/// Headless `App` wrapper for integration tests.
+///
+/// Runs without a window or GPU: only `MinimalPlugins`, assets, input,
+/// LDtk, Rapier and the gameplay plugins are added. Time advances by a fixed
+/// [`FRAME`] every update so tests are deterministic.
+pub struct TestApp {
+    pub app: App,
+    project: String,
+}
+
+impl TestApp {
+    /// Builds a headless app with the gameplay plugins, loading [`FIXTURE_PROJECT`].
+    pub fn new() -> Self {
+        Self::with_project(FIXTURE_PROJECT)
+    }
+
+    /// Builds a headless app with the gameplay plugins, loading the given LDtk project.
+    pub fn with_project(project: &str) -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            ImagePlugin::default_nearest(),
+            TransformPlugin,
+            HierarchyPlugin,
+            InputPlugin,
+            StatesPlugin,
+        ))
+        .init_asset::<TextureAtlasLayout>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .add_plugins((
+            LdtkPlugin,
+            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0),
+        ))
+        .insert_resource(RapierConfiguration {
+            gravity: Vec2::new(0.0, 0.0),
+            physics_pipeline_active: true,
+            query_pipeline_active: true,
+            timestep_mode: TimestepMode::Fixed {
+                dt: FRAME.as_secs_f32(),
+                substeps: 1,
+            },
+            scaled_shape_subdivision: 10,
+            force_update_from_transform_changes: false,
+        })
+        .insert_resource(LevelSelection::Uid(0))
+        .insert_resource(LdtkSettings {
+            level_spawn_behavior: LevelSpawnBehavior::UseWorldTranslation {
+                load_level_neighbors: true,
+            },
+            ..Default::default()
+        })
+        // read by gameplay systems, without the plugins updating them
+        .init_resource::<settings::Settings>()
+        .init_resource::<survival::SurvivalSettings>()
+        .init_resource::<weather::Weather>()
+        .init_resource::<weather::WeatherSettings>()
+        .add_plugins((
+            collision_layers::CollisionLayerPlugin,
+            colliders::ColliderPlugin,
+            health::HealthPlugin,
+            physics_mode::PhysicsModePlugin,
+            terrain::TerrainPlugin,
+            walls::WallPlugin,
+            water::WaterPlugin,
+            climbing::ClimbingPlugin,
+            player::PlayerPlugin,
+            enemy::EnemyPlugin,
+            character::CharacterPlugin,
+            misc_objects::MiscObjectsPlugin,
+        ));
+
+        TestApp {
+            app,
+            project: project.to_string(),
+        }
+    }
+
+    /// Adds extra plugins before the app is started.
+    pub fn add_plugins<M>(&mut self, plugins: impl Plugins<M>) -> &mut Self {
+        self.app.add_plugins(plugins);
+        self
+    }
+
+    /// Finishes plugin setup, spawns the LDtk world and runs frames until the
+    /// player has spawned.
+    ///
+    /// Panics if the level has not spawned after 300 frames.
+    pub fn load_level(&mut self) -> &mut Self {
+        self.app.finish();
+        self.app.cleanup();
+
+        let ldtk_handle = self
+            .app
+            .world()
+            .resource::<AssetServer>()
+            .load(self.project.clone());
+        self.app.world_mut().spawn(LdtkWorldBundle {
+            ldtk_handle,
+            ..Default::default()
+        });
+
+        assert!(
+            self.run_until(300, |world| {
+                world
+                    .query_filtered::<(), With<player::Player>>()
+                    .iter(world)
+                    .next()
+                    .is_some()
+            }),
+            "level {} did not spawn a player",
+            self.project
+        );
+        // one more frame so systems reacting to `Added` components have run
+        self.advance(1)
+    }
+
+    /// Runs `frames` updates.
+    pub fn advance(&mut self, frames: usize) -> &mut Self {
+        for _ in 0..frames {
+            self.app.update();
+        }
+        self
+    }
+
+    /// Runs updates until `condition` holds, up to `max_frames`.
+    ///
+    /// Returns whether the condition was met.
+    pub fn run_until(&mut self, max_frames: usize, mut condition: impl FnMut(&mut World) -> bool) -> bool {
+        for _ in 0..max_frames {
+            self.app.update();
+            if condition(self.app.world_mut()) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Sends a virtual key press, as winit would.
+    pub fn press(&mut self, key_code: KeyCode) -> &mut Self {
+        self.send_key(key_code, ButtonState::Pressed)
+    }
+
+    /// Sends a virtual key release, as winit would.
+    pub fn release(&mut self, key_code: KeyCode) -> &mut Self {
+        self.send_key(key_code, ButtonState::Released)
+    }
+
+    /// Presses a key, holds it for `frames` updates, then releases it.
+    pub fn hold(&mut self, key_code: KeyCode, frames: usize) -> &mut Self {
+        self.press(key_code).advance(frames).release(key_code).advance(1)
+    }
+
+    fn send_key(&mut self, key_code: KeyCode, state: ButtonState) -> &mut Self {
+        self.app.world_mut().send_event(KeyboardInput {
+            key_code,
+            logical_key: Key::Unidentified(NativeKey::Unidentified),
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+        self
+    }
+
+    pub fn world(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+
+    /// Number of entities matching the filter `F`.
+    pub fn count<F: QueryFilter>(&mut self) -> usize {
+        let world = self.app.world_mut();
+        world.query_filtered::<(), F>().iter(world).count()
+    }
+
+    /// Clones of the component `C` on every entity matching the filter `F`.
+    pub fn collect<C: Component + Clone, F: QueryFilter>(&mut self) -> Vec<C> {
+        let world = self.app.world_mut();
+        world
+            .query_filtered::<&C, F>()
+            .iter(world)
+            .cloned()
+            .collect()
+    }
+
+    /// Clone of the component `C` on the single entity matching the filter `F`.
+    ///
+    /// Panics if there isn't exactly one match.
+    pub fn single<C: Component + Clone, F: QueryFilter>(&mut self) -> C {
+        let world = self.app.world_mut();
+        world.query_filtered::<&C, F>().single(world).clone()
+    }
+
+    /// Runs `f` against every item of the query `D` filtered by `F`.
+    pub fn for_each<D: QueryData, F: QueryFilter>(&mut self, mut f: impl FnMut(D::Item<'_>)) {
+        let world = self.app.world_mut();
+        world.query_filtered::<D, F>().iter_mut(world).for_each(&mut f);
+    }
+
+    /// World translation of the player.
+    pub fn player_translation(&mut self) -> Vec3 {
+        let world = self.app.world_mut();
+        world
+            .query_filtered::<&GlobalTransform, With<player::Player>>()
+            .single(world)
+            .translation()
+    }
+}
+
+impl Default for TestApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{character::Character, enemy::Enemy, player::Player, walls::Wall};
+
+    #[test]
+    fn fixture_spawns_entities() {
+        let mut app = TestApp::new();
+        app.load_level();
+
+        assert_eq!(app.count::<With<Player>>(), 1);
+        assert_eq!(app.count::<With<Character>>(), 1);
+        assert_eq!(app.count::<With<Enemy>>(), 1);
+        assert!(app.count::<With<Wall>>() > 0);
+    }
+
+    #[test]
+    fn walls_are_merged_into_few_colliders() {
+        let mut app = TestApp::new();
+        app.load_level();
+
+        let wall_tiles = app.count::<With<Wall>>();
+        let wall_colliders =
+            app.count::<(With<Collider>, With<RigidBody>, Without<Player>, Without<Character>, Without<Enemy>)>();
+        assert!(wall_colliders > 0);
+        assert!(wall_colliders < wall_tiles);
+    }
+
+    #[test]
+    fn player_moves_right_when_d_is_held() {
+        let mut app = TestApp::new();
+        app.load_level();
+
+        let start = app.player_translation();
+        app.hold(KeyCode::KeyD, 30);
+        let end = app.player_translation();
+
+        assert!(end.x > start.x, "player did not move right: {start} -> {end}");
+        assert!((end.y - start.y).abs() < 1.0);
+    }
+}
@@ -1,13 +1,61 @@
-use dotenv::dotenv;
 use serde::{Deserialize, Serialize};
-use std::{env, io::Read};
 use tracing::{event, Level};
 
 use bevy::prelude::*;
+use bevy_common_assets::xml::XmlAssetPlugin;
 use bevy_ecs_ldtk::prelude::*;
 
+use crate::{
+    camera_script::CameraScript,
+    character::is_named,
+    console::{ConsoleAppExt, ConsoleCommand},
+    paths::GamePaths,
+    sequence::Sequence,
+    story::{Condition, StoryState},
+};
+
+/// Key written by name in XML and the settings file.
+///
+/// Letters and digits are written as themselves, like `"W"` or `"1"`, a few keys have short
+/// names like `"SPACE"` or `"UP"`, and every other key uses Bevy's [`KeyCode`] name, like
+/// `"Backquote"` or `"F5"`.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct GameKeyCode(KeyCode);
+pub struct GameKeyCode(pub KeyCode);
+
+/// Short names of keys, checked before Bevy's [`KeyCode`] names.
+const KEY_ALIASES: [(&str, KeyCode); 8] = [
+    ("SPACE", KeyCode::Space),
+    ("ENTER", KeyCode::Enter),
+    ("ESCAPE", KeyCode::Escape),
+    ("TAB", KeyCode::Tab),
+    ("UP", KeyCode::ArrowUp),
+    ("DOWN", KeyCode::ArrowDown),
+    ("LEFT", KeyCode::ArrowLeft),
+    ("RIGHT", KeyCode::ArrowRight),
+];
+
+impl std::str::FromStr for GameKeyCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_uppercase();
+        if let Some((_, key_code)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == upper) {
+            return Ok(GameKeyCode(*key_code));
+        }
+
+        let key_name = match upper.chars().next() {
+            Some(c) if upper.len() == 1 && c.is_ascii_alphabetic() => format!("Key{}", c),
+            Some(c) if upper.len() == 1 && c.is_ascii_digit() => format!("Digit{}", c),
+            // function keys were written in upper case before Bevy's names were accepted
+            Some('F') if upper[1..].parse::<u8>().is_ok() => upper.clone(),
+            _ => s.to_string(),
+        };
+        let deserializer = serde::de::value::StrDeserializer::<serde::de::value::Error>::new(&key_name);
+        KeyCode::deserialize(deserializer)
+            .map(GameKeyCode)
+            .map_err(|_| format!("Invalid key: {}", s))
+    }
+}
 
 impl<'de> Deserialize<'de> for GameKeyCode {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -15,59 +63,40 @@ impl<'de> Deserialize<'de> for GameKeyCode {
         D: serde::Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        match s.to_uppercase().as_str() {
-            "W" => Ok(GameKeyCode(KeyCode::KeyW)),
-            "A" => Ok(GameKeyCode(KeyCode::KeyA)),
-            "S" => Ok(GameKeyCode(KeyCode::KeyS)),
-            "D" => Ok(GameKeyCode(KeyCode::KeyD)),
-            "Q" => Ok(GameKeyCode(KeyCode::KeyQ)),
-            "E" => Ok(GameKeyCode(KeyCode::KeyE)),
-            "R" => Ok(GameKeyCode(KeyCode::KeyR)),
-            "T" => Ok(GameKeyCode(KeyCode::KeyT)),
-            "Y" => Ok(GameKeyCode(KeyCode::KeyY)),
-            "N" => Ok(GameKeyCode(KeyCode::KeyN)),
-            "M" => Ok(GameKeyCode(KeyCode::KeyM)),
-            "F" => Ok(GameKeyCode(KeyCode::KeyF)),
-            "G" => Ok(GameKeyCode(KeyCode::KeyG)),
-            "H" => Ok(GameKeyCode(KeyCode::KeyH)),
-            "Z" => Ok(GameKeyCode(KeyCode::KeyZ)),
-            "X" => Ok(GameKeyCode(KeyCode::KeyX)),
-            "C" => Ok(GameKeyCode(KeyCode::KeyC)),
-            "SPACE" => Ok(GameKeyCode(KeyCode::Space)),
-            "ENTER" => Ok(GameKeyCode(KeyCode::Enter)),
-            _ => Err(serde::de::Error::custom(format!("Invalid key: {}", s))),
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl GameKeyCode {
+    /// Name of the key as written in XML and the settings file, like `"W"` or `"SPACE"`.
+    pub fn name(&self) -> String {
+        if let Some((alias, _)) = KEY_ALIASES.iter().find(|(_, key_code)| *key_code == self.0) {
+            return alias.to_string();
+        }
+        let debug_name = format!("{:?}", self.0);
+        match (debug_name.strip_prefix("Key"), debug_name.strip_prefix("Digit")) {
+            (Some(letter), _) if letter.len() == 1 => letter.to_string(),
+            (_, Some(digit)) if digit.len() == 1 => digit.to_string(),
+            _ => debug_name,
         }
     }
 }
 
+impl std::fmt::Display for GameKeyCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
 impl Serialize for GameKeyCode {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let key_str = match self.0 {
-            KeyCode::KeyW => "W",
-            KeyCode::KeyA => "A", 
-            KeyCode::KeyS => "S",
-            KeyCode::KeyD => "D",
-            KeyCode::KeyQ => "Q",
-            KeyCode::KeyE => "E",
-            KeyCode::KeyR => "R",
-            KeyCode::KeyT => "T",
-            KeyCode::KeyY => "Y",
-            KeyCode::KeyN => "N",
-            KeyCode::KeyM => "M",
-            KeyCode::KeyF => "F",
-            KeyCode::KeyG => "G",
-            KeyCode::KeyH => "H",
-            KeyCode::KeyZ => "Z",
-            KeyCode::KeyX => "X",
-            KeyCode::KeyC => "C",
-            KeyCode::Space => "SPACE",
-            KeyCode::Enter => "ENTER",
-            _ => return Err(serde::ser::Error::custom(format!("Invalid key: {:?}", self.0))),
-        };
-        serializer.serialize_str(key_str)
+        if let KeyCode::Unidentified(_) = self.0 {
+            return Err(serde::ser::Error::custom(format!("Invalid key: {:?}", self.0)));
+        }
+        serializer.serialize_str(&self.name())
     }
 }
 
@@ -77,85 +106,283 @@ pub struct Response {
     pub key: GameKeyCode,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct Action {
     pub name: String,
     pub depends_on: Option<String>,
+    /// Story condition that must hold for the action to start
+    pub condition: Option<Condition>,
     pub text: Option<String>,
-    #[serde(rename = "available_response")]
+    #[serde(rename = "available_response", default)]
     pub available_responses: Vec<Response>,
     pub given_response: Option<Response>,
+    /// Camera moves played when the action starts
+    pub camera: Option<CameraScript>,
+    /// Commands run when the action starts
+    pub sequence: Option<Sequence>,
     pub acknowledged: bool,
     pub completed: bool,
 }
 
-#[derive(Asset, Clone, Component, Debug, Eq, Default, PartialEq, Deserialize, TypePath)]
+#[derive(Asset, Clone, Component, Debug, Default, PartialEq, Deserialize, TypePath)]
 pub struct Timeline {
     #[serde(rename = "action")]
     pub actions: Vec<Action>
 }
 
+impl Action {
+    /// Whether the action is shown as dialogue, completed once the player responds.
+    pub fn is_dialogue(&self) -> bool {
+        self.text.is_some() || !self.available_responses.is_empty()
+    }
+}
+
 impl Timeline {
-    pub fn from(entity_instance: &EntityInstance) -> Self {
-        // attempt to load the timeline file for the entity instance
-        let timeline_filename = match LdtkFields::get_string_field(entity_instance, "timeline") {
-            Ok(filename) => filename,
-            Err(_) => {
-                event!(Level::ERROR, "No timeline file set");
-                return Timeline { actions: vec![] };
-            },
-        };
-        
-        dotenv().ok();
-
-        let asset_folder = match env::var("ASSET_BASE_DIR") {
-            Ok(folder) => folder,
-            Err(_) => {
-                event!(Level::ERROR, "Error loading environment variable");
-                return Timeline { actions: vec![] };
+    pub fn action(&self, name: &str) -> Option<&Action> {
+        self.actions.iter().find(|action| action.name == name)
+    }
+
+    /// Whether an action hasn't started yet, the action it depends on is
+    /// completed and its condition holds.
+    pub fn is_available(&self, action: &Action, story: &StoryState) -> bool {
+        !action.acknowledged
+            && action.depends_on.as_ref().map_or(true, |dependency| {
+                self.action(dependency).is_some_and(|dependency| dependency.completed)
+            })
+            && action
+                .condition
+                .as_ref()
+                .map_or(true, |condition| condition.evaluate(story))
+    }
+
+    /// Reads `timelines/<filename>` from the asset root, falling back to
+    /// `timelines/<name>_init.xml` for a fresh game.
+    pub fn load(paths: &GamePaths, filename: &str) -> Self {
+        let init_filename = filename.replace(".xml", "_init.xml");
+        let mut contents = None;
+        for candidate in [filename, init_filename.as_str()] {
+            let path = match paths.asset(&format!("timelines/{}", candidate)) {
+                Ok(path) => path,
+                Err(e) => {
+                    event!(Level::ERROR, "Invalid timeline file {} | {}", candidate, e);
+                    return Timeline::default();
+                }
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(file_contents) => {
+                    event!(Level::INFO, "Loaded timeline file | {}", path.display());
+                    contents = Some(file_contents);
+                    break;
+                }
+                Err(_) => event!(Level::WARN, "Error opening timeline file {}", path.display()),
             }
+        }
+
+        let Some(contents) = contents else {
+            event!(Level::ERROR, "No timeline file for {}", filename);
+            return Timeline::default();
         };
 
-        // get xml file from std::fs
-        let mut file = match std::fs::File::open(format!("{}/{}/{}", asset_folder, "timelines", timeline_filename)) {
-            Ok(file) => {
-                event!(Level::INFO, "Loaded timeline file | {}", timeline_filename);
-                file
-            },
-            Err(_) => {
-                event!(Level::WARN, "Error opening file. Attempting init");
-                match std::fs::File::open(format!("{}/{}/{}", asset_folder, "timelines", timeline_filename.replace(".xml", "_init.xml"))) {
-                    Ok(file) => {
-                        event!(Level::INFO, "Loaded init timeline file | {}", timeline_filename.replace(".xml", "_init.xml"));
-                        file
-                    },
-                    Err(_) => {
-                        event!(Level::ERROR, "Error opening file.");
-                        return Timeline { actions: vec![] };
-                    }
-                };
-                return Timeline { actions: vec![] };
+        match serde_xml_rs::from_str(&contents) {
+            Ok(timeline) => timeline,
+            Err(e) => {
+                event!(Level::ERROR, "Error parsing file | {}", e);
+                Timeline::default()
             }
-        };
+        }
+    }
+}
 
-        let mut contents = String::new();
+/// Loads the timeline named by the `timeline` field of newly spawned LDtk entities.
+pub fn load_entity_timelines(
+    mut commands: Commands,
+    paths: Res<GamePaths>,
+    entity_query: Query<(Entity, &EntityInstance), Added<EntityInstance>>,
+) {
+    for (entity, entity_instance) in &entity_query {
+        if let Ok(filename) = entity_instance.get_string_field("timeline") {
+            commands.entity(entity).insert(Timeline::load(&paths, filename));
+        }
+    }
+}
 
-        match file.read_to_string(&mut contents) {
-            Ok(_) => (),
-            Err(_) => {
-                event!(Level::ERROR, "Error reading file");
-                return Timeline { actions: vec![] };
+/// Sent when a timeline action becomes available and is acknowledged.
+#[derive(Clone, Debug, Eq, PartialEq, Event)]
+pub struct ActionStarted {
+    /// Entity holding the [`Timeline`]
+    pub entity: Entity,
+    pub action: String,
+}
+
+/// Acknowledges every available action, whenever a timeline or the story changes.
+pub fn start_available_actions(
+    story: Res<StoryState>,
+    mut timeline_query: Query<(Entity, &mut Timeline)>,
+    mut action_started: EventWriter<ActionStarted>,
+) {
+    for (entity, mut timeline) in &mut timeline_query {
+        if !timeline.is_changed() && !story.is_changed() {
+            continue;
+        }
+
+        let available: Vec<String> = timeline
+            .actions
+            .iter()
+            .filter(|action| timeline.is_available(action, &story))
+            .map(|action| action.name.clone())
+            .collect();
+
+        for name in available {
+            event!(Level::INFO, "Starting action | {}", name);
+            if let Some(action) = timeline.actions.iter_mut().find(|action| action.name == name) {
+                action.acknowledged = true;
             }
+            action_started.send(ActionStarted {
+                entity,
+                action: name,
+            });
         }
+    }
+}
 
-        let timeline: Timeline = match serde_xml_rs::from_str(&contents) {
-            Ok(timeline) => timeline,
-            Err(e) => {
-                event!(Level::ERROR, "Error parsing file | {}", e);
-                Timeline { actions: vec![] }
+/// `timeline reset [name]`: makes every action of the named entity's timeline, or of all
+/// timelines, available again.
+pub struct TimelineCommand;
+
+impl ConsoleCommand for TimelineCommand {
+    fn name(&self) -> &'static str {
+        "timeline"
+    }
+
+    fn usage(&self) -> &'static str {
+        "reset [name]"
+    }
+
+    fn complete(&self, _: &mut World, args: &[&str]) -> Vec<String> {
+        match args.len() {
+            1 => vec!["reset".to_string()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<String, String> {
+        if args.first() != Some(&"reset") {
+            return Err("Expected reset".to_string());
+        }
+        let name = args.get(1).copied();
+
+        let mut reset = 0;
+        let mut timeline_query = world.query::<(&mut Timeline, Option<&EntityInstance>)>();
+        for (mut timeline, entity_instance) in timeline_query.iter_mut(world) {
+            let named = match (name, entity_instance) {
+                (None, _) => true,
+                (Some(name), Some(entity_instance)) => is_named(entity_instance, name),
+                (Some(_), None) => false,
+            };
+            if !named {
+                continue;
             }
-        };
+            for action in &mut timeline.actions {
+                action.acknowledged = false;
+                action.completed = false;
+            }
+            reset += 1;
+        }
+        Ok(format!("Reset {} timelines", reset))
+    }
+}
+
+/// Plugin which loads timeline assets and starts their actions.
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(XmlAssetPlugin::<Timeline>::new(&["timelines/timeline.xml"]))
+            .add_event::<ActionStarted>()
+            .add_systems(Update, (load_entity_timelines, start_available_actions).chain())
+            .add_console_command(TimelineCommand);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{player::Player, story::StoryPlugin, test_support::TestApp};
+
+    #[test]
+    fn every_key_has_a_name() {
+        for (name, key_code) in [
+            ("w", KeyCode::KeyW),
+            ("7", KeyCode::Digit7),
+            ("space", KeyCode::Space),
+            ("f5", KeyCode::F5),
+            ("Backquote", KeyCode::Backquote),
+            ("NumpadAdd", KeyCode::NumpadAdd),
+        ] {
+            let key: GameKeyCode = name.parse().unwrap();
+            assert_eq!(key, GameKeyCode(key_code));
+            assert_eq!(key.name().parse::<GameKeyCode>(), Ok(key));
+        }
+        assert_eq!(GameKeyCode(KeyCode::Digit7).name(), "7");
+        assert_eq!(GameKeyCode(KeyCode::ArrowUp).name(), "UP");
+        assert!("Kazoo".parse::<GameKeyCode>().is_err());
+    }
+
+    #[test]
+    fn actions_wait_for_their_condition() {
+        let timeline: Timeline = serde_xml_rs::from_str(
+            r#"<timeline>
+                <action>
+                    <name>gold rush</name>
+                    <condition>met_hickman and gems >= 2</condition>
+                    <acknowledged>false</acknowledged>
+                    <completed>false</completed>
+                </action>
+            </timeline>"#,
+        )
+        .unwrap();
+        let action = timeline.action("gold rush").unwrap();
+
+        let mut story = StoryState::default();
+        story.set_flag("met_hickman");
+        assert!(!timeline.is_available(action, &story));
+        story.add_to_counter("gems", 2);
+        assert!(timeline.is_available(action, &story));
+    }
+
+    #[test]
+    fn actions_wait_for_their_dependency() {
+        let timeline: Timeline = serde_xml_rs::from_str(
+            r#"<timeline>
+                <action>
+                    <name>greeting</name>
+                    <acknowledged>false</acknowledged>
+                    <completed>false</completed>
+                </action>
+                <action>
+                    <name>farewell</name>
+                    <depends_on>greeting</depends_on>
+                    <acknowledged>false</acknowledged>
+                    <completed>false</completed>
+                </action>
+            </timeline>"#,
+        )
+        .unwrap();
+
+        let story = StoryState::default();
+        assert!(timeline.is_available(timeline.action("greeting").unwrap(), &story));
+        assert!(!timeline.is_available(timeline.action("farewell").unwrap(), &story));
+    }
+
+    #[test]
+    fn fixture_player_timeline_loads() {
+        let mut app = TestApp::new();
+        app.add_plugins((TimelinePlugin, StoryPlugin));
+        app.world().init_resource::<GamePaths>();
+        app.load_level();
+        app.advance(1);
 
-        timeline
+        let timeline = app.single::<Timeline, With<Player>>();
+        assert!(timeline.action("fixture loaded").is_some_and(|action| action.acknowledged));
     }
 }
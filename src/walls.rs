@@ -1,18 +1,153 @@
 use std::collections::HashSet;
 
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{ecs::query::QueryFilter, prelude::*, utils::HashMap};
 use bevy_ecs_ldtk::prelude::*;
 
 use bevy_rapier2d::prelude::*;
 
+use crate::collision_layers::CollisionLayer;
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Wall;
 
+/// Collider of a merged rectangle of wall cells, spawned by [`spawn_wall_collision`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct WallCollider;
+
 #[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
 pub struct WallBundle {
     wall: Wall,
 }
 
+/// A rectangle of grid cells, inclusive on every side
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct GridRect {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+impl GridRect {
+    /// Half extents in pixels, as taken by `Collider::cuboid`
+    pub fn half_extents(&self, grid_size: i32) -> Vec2 {
+        Vec2::new(
+            (self.right as f32 - self.left as f32 + 1.) * grid_size as f32 / 2.,
+            (self.top as f32 - self.bottom as f32 + 1.) * grid_size as f32 / 2.,
+        )
+    }
+
+    /// Center in pixels, relative to the level
+    pub fn center(&self, grid_size: i32) -> Vec2 {
+        Vec2::new(
+            (self.left + self.right + 1) as f32 * grid_size as f32 / 2.,
+            (self.bottom + self.top + 1) as f32 * grid_size as f32 / 2.,
+        )
+    }
+}
+
+/// Groups the grid coordinates of newly spawned int cells by the level entity they belong to.
+///
+/// The key of this map will be the entity of the level the cell belongs to.
+/// This has two consequences in the resulting collision entities:
+/// 1. it forces the rectangles to be split along level boundaries
+/// 2. it lets us easily add the collision entities as children of the appropriate level entity
+pub fn cells_by_level<'a, F: QueryFilter>(
+    cells: impl Iterator<Item = (&'a GridCoords, &'a Parent)>,
+    parent_query: &Query<&Parent, F>,
+) -> HashMap<Entity, HashSet<GridCoords>> {
+    let mut level_to_cell_locations: HashMap<Entity, HashSet<GridCoords>> = HashMap::new();
+
+    cells.for_each(|(&grid_coords, parent)| {
+        // An intgrid tile's direct parent will be a layer entity, not the level entity
+        // To get the level entity, you need the tile's grandparent.
+        // This is where parent_query comes in.
+        if let Ok(grandparent) = parent_query.get(parent.get()) {
+            level_to_cell_locations
+                .entry(grandparent.get())
+                .or_default()
+                .insert(grid_coords);
+        }
+    });
+
+    level_to_cell_locations
+}
+
+/// Combines a set of grid cells into as few rectangles as is simple.
+///
+/// The algorithm used here is a nice compromise between simplicity, speed,
+/// and a small number of rectangles.
+/// In basic terms, it will:
+/// 1. combine cells into flat "plates" in each individual row
+/// 2. combine the plates into rectangles across multiple rows wherever possible
+pub fn merge_into_rects(cells: &HashSet<GridCoords>, width: i32, height: i32) -> Vec<GridRect> {
+    /// Represents a wide wall that is 1 tile tall
+    /// Used to spawn wall collisions
+    #[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
+    struct Plate {
+        left: i32,
+        right: i32,
+    }
+
+    // combine wall tiles into flat "plates" in each individual row
+    let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
+
+    for y in 0..height {
+        let mut row_plates: Vec<Plate> = Vec::new();
+        let mut plate_start = None;
+
+        // + 1 to the width so the algorithm "terminates" plates that touch the right edge
+        for x in 0..width + 1 {
+            match (plate_start, cells.contains(&GridCoords { x, y })) {
+                (Some(s), false) => {
+                    row_plates.push(Plate {
+                        left: s,
+                        right: x - 1,
+                    });
+                    plate_start = None;
+                }
+                (None, true) => plate_start = Some(x),
+                _ => (),
+            }
+        }
+
+        plate_stack.push(row_plates);
+    }
+
+    // combine "plates" into rectangles across multiple rows
+    let mut rect_builder: HashMap<Plate, GridRect> = HashMap::new();
+    let mut prev_row: Vec<Plate> = Vec::new();
+    let mut rects: Vec<GridRect> = Vec::new();
+
+    // an extra empty row so the algorithm "finishes" the rects that touch the top edge
+    plate_stack.push(Vec::new());
+
+    for (y, current_row) in plate_stack.into_iter().enumerate() {
+        for prev_plate in &prev_row {
+            if !current_row.contains(prev_plate) {
+                // remove the finished rect so that the same plate in the future starts a new rect
+                if let Some(rect) = rect_builder.remove(prev_plate) {
+                    rects.push(rect);
+                }
+            }
+        }
+        for plate in &current_row {
+            rect_builder
+                .entry(plate.clone())
+                .and_modify(|e| e.top += 1)
+                .or_insert(GridRect {
+                    bottom: y as i32,
+                    top: y as i32,
+                    left: plate.left,
+                    right: plate.right,
+                });
+        }
+        prev_row = current_row;
+    }
+
+    rects
+}
+
 /// Spawns heron collisions for the walls of a level
 ///
 /// You could just insert a ColliderBundle into the WallBundle,
@@ -22,13 +157,10 @@ pub struct WallBundle {
 /// Instead, by flagging the wall tiles and spawning the collisions later,
 /// we can minimize the amount of colliding entities.
 ///
-/// The algorithm used here is a nice compromise between simplicity, speed,
-/// and a small number of rectangle colliders.
 /// In basic terms, it will:
 /// 1. consider where the walls are
-/// 2. combine wall tiles into flat "plates" in each individual row
-/// 3. combine the plates into rectangles across multiple rows wherever possible
-/// 4. spawn colliders for each rectangle
+/// 2. merge them into rectangles with [`merge_into_rects`]
+/// 3. spawn colliders for each rectangle
 pub fn spawn_wall_collision(
     mut commands: Commands,
     wall_query: Query<(&GridCoords, &Parent), Added<Wall>>,
@@ -37,42 +169,9 @@ pub fn spawn_wall_collision(
     ldtk_projects: Query<&Handle<LdtkProject>>,
     ldtk_project_assets: Res<Assets<LdtkProject>>,
 ) {
-    /// Represents a wide wall that is 1 tile tall
-    /// Used to spawn wall collisions
-    #[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
-    struct Plate {
-        left: i32,
-        right: i32,
-    }
-
-    /// A simple rectangle type representing a wall of any size
-    struct Rect {
-        left: i32,
-        right: i32,
-        top: i32,
-        bottom: i32,
-    }
-
     // Consider where the walls are
     // storing them as GridCoords in a HashSet for quick, easy lookup
-    //
-    // The key of this map will be the entity of the level the wall belongs to.
-    // This has two consequences in the resulting collision entities:
-    // 1. it forces the walls to be split along level boundaries
-    // 2. it lets us easily add the collision entities as children of the appropriate level entity
-    let mut level_to_wall_locations: HashMap<Entity, HashSet<GridCoords>> = HashMap::new();
-
-    wall_query.iter().for_each(|(&grid_coords, parent)| {
-        // An intgrid tile's direct parent will be a layer entity, not the level entity
-        // To get the level entity, you need the tile's grandparent.
-        // This is where parent_query comes in.
-        if let Ok(grandparent) = parent_query.get(parent.get()) {
-            level_to_wall_locations
-                .entry(grandparent.get())
-                .or_default()
-                .insert(grid_coords);
-        }
-    });
+    let level_to_wall_locations = cells_by_level(wall_query.iter(), &parent_query);
 
     if !wall_query.is_empty() {
         level_query.iter().for_each(|(level_entity, level_iid)| {
@@ -93,61 +192,7 @@ pub fn spawn_wall_collision(
                     ..
                 } = level.layer_instances()[0];
 
-                // combine wall tiles into flat "plates" in each individual row
-                let mut plate_stack: Vec<Vec<Plate>> = Vec::new();
-
-                for y in 0..height {
-                    let mut row_plates: Vec<Plate> = Vec::new();
-                    let mut plate_start = None;
-
-                    // + 1 to the width so the algorithm "terminates" plates that touch the right edge
-                    for x in 0..width + 1 {
-                        match (plate_start, level_walls.contains(&GridCoords { x, y })) {
-                            (Some(s), false) => {
-                                row_plates.push(Plate {
-                                    left: s,
-                                    right: x - 1,
-                                });
-                                plate_start = None;
-                            }
-                            (None, true) => plate_start = Some(x),
-                            _ => (),
-                        }
-                    }
-
-                    plate_stack.push(row_plates);
-                }
-
-                // combine "plates" into rectangles across multiple rows
-                let mut rect_builder: HashMap<Plate, Rect> = HashMap::new();
-                let mut prev_row: Vec<Plate> = Vec::new();
-                let mut wall_rects: Vec<Rect> = Vec::new();
-
-                // an extra empty row so the algorithm "finishes" the rects that touch the top edge
-                plate_stack.push(Vec::new());
-
-                for (y, current_row) in plate_stack.into_iter().enumerate() {
-                    for prev_plate in &prev_row {
-                        if !current_row.contains(prev_plate) {
-                            // remove the finished rect so that the same plate in the future starts a new rect
-                            if let Some(rect) = rect_builder.remove(prev_plate) {
-                                wall_rects.push(rect);
-                            }
-                        }
-                    }
-                    for plate in &current_row {
-                        rect_builder
-                            .entry(plate.clone())
-                            .and_modify(|e| e.top += 1)
-                            .or_insert(Rect {
-                                bottom: y as i32,
-                                top: y as i32,
-                                left: plate.left,
-                                right: plate.right,
-                            });
-                    }
-                    prev_row = current_row;
-                }
+                let wall_rects = merge_into_rects(level_walls, width, height);
 
                 commands.entity(level_entity).with_children(|level| {
                     // Spawn colliders for every rectangle..
@@ -155,24 +200,16 @@ pub fn spawn_wall_collision(
                     // 1. Adjusts the transforms to be relative to the level for free
                     // 2. the colliders will be despawned automatically when levels unload
                     for wall_rect in wall_rects {
+                        let half_extents = wall_rect.half_extents(grid_size);
                         level
                             .spawn_empty()
-                            .insert(Collider::cuboid(
-                                (wall_rect.right as f32 - wall_rect.left as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                                (wall_rect.top as f32 - wall_rect.bottom as f32 + 1.)
-                                    * grid_size as f32
-                                    / 2.,
-                            ))
+                            .insert(Collider::cuboid(half_extents.x, half_extents.y))
                             .insert(RigidBody::Fixed)
                             .insert(Friction::new(1.0))
-                            .insert(Transform::from_xyz(
-                                (wall_rect.left + wall_rect.right + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                (wall_rect.bottom + wall_rect.top + 1) as f32 * grid_size as f32
-                                    / 2.,
-                                0.,
+                            .insert(CollisionLayer::Wall)
+                            .insert(WallCollider)
+                            .insert(Transform::from_translation(
+                                wall_rect.center(grid_size).extend(0.),
                             ))
                             .insert(GlobalTransform::default());
                     }
@@ -185,14 +222,13 @@ pub fn spawn_wall_collision(
 /// Plugin which spawns walls on appropriate LDtk int cells,
 /// then merges them together to reduce physics load.
 ///
-/// Walls are hardcoded as int cell values 1 (dirt) and 3 (stone) and 4 (water).
+/// Walls are hardcoded as int cell values 1 (dirt) and 3 (stone).
 pub struct WallPlugin;
 
 impl Plugin for WallPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, spawn_wall_collision)
             .register_ldtk_int_cell::<WallBundle>(1) //dirt
-            .register_ldtk_int_cell::<WallBundle>(3) //stone
-            .register_ldtk_int_cell::<WallBundle>(4);//water
+            .register_ldtk_int_cell::<WallBundle>(3); //stone
     }
 }
@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+
+use tracing::{event, Level};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_ldtk::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    collision_layers::CollisionLayer,
+    health::Damage,
+    inventory::Inventory,
+    walls::{cells_by_level, merge_into_rects},
+};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct Water;
+
+#[derive(Clone, Debug, Default, Bundle, LdtkIntCell)]
+pub struct WaterBundle {
+    water: Water,
+}
+
+/// Water tiles next to the shore are shallow, the rest is deep.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Default)]
+pub enum WaterDepth {
+    #[default]
+    Shallow,
+    Deep,
+}
+
+/// Sensor covering a merged rectangle of water tiles of the same depth.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct WaterZone {
+    pub depth: WaterDepth,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum WaterState {
+    #[default]
+    Dry,
+    Wading,
+    Swimming,
+}
+
+/// Entity that can enter water.
+#[derive(Clone, PartialEq, Debug, Default, Component)]
+pub struct Swimmer {
+    pub state: WaterState,
+    pub intersecting_water: HashMap<Entity, WaterDepth>,
+    /// Where the swimmer is put back when too exhausted to keep swimming
+    pub last_dry_position: Option<Vec2>,
+    /// Sprite color out of the water, the water tint is applied on top of it
+    pub dry_color: Option<Color>,
+}
+
+impl Swimmer {
+    pub fn deepest(&self) -> Option<WaterDepth> {
+        self.intersecting_water.values().max().copied()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Component)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Stamina {
+            current: 100.,
+            max: 100.,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Resource)]
+pub struct WaterSettings {
+    pub wading_speed: f32,
+    pub swimming_speed: f32,
+    /// Stamina lost per second while swimming
+    pub swim_drain: f32,
+    /// Stamina regained per second out of deep water
+    pub stamina_recovery: f32,
+    /// Damage per second to exhausted swimmers with no shore to return to
+    pub drowning_damage: f32,
+    /// Item that lets the wearer wade through deep water
+    pub deep_water_item: String,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        WaterSettings {
+            wading_speed: 0.6,
+            swimming_speed: 0.45,
+            swim_drain: 15.,
+            stamina_recovery: 10.,
+            drowning_damage: 10.,
+            deep_water_item: "Boots".to_string(),
+        }
+    }
+}
+
+impl WaterSettings {
+    pub fn speed_multiplier(&self, state: WaterState) -> f32 {
+        match state {
+            WaterState::Dry => 1.,
+            WaterState::Wading => self.wading_speed,
+            WaterState::Swimming => self.swimming_speed,
+        }
+    }
+}
+
+/// Water cells whose eight neighbours are all water.
+///
+/// Cells outside of the level count as water,
+/// so rivers crossing a level boundary stay deep.
+pub fn deep_cells(cells: &HashSet<GridCoords>, width: i32, height: i32) -> HashSet<GridCoords> {
+    cells
+        .iter()
+        .filter(|cell| {
+            (-1..=1).all(|dx| {
+                (-1..=1).all(|dy| {
+                    let neighbour = GridCoords::new(cell.x + dx, cell.y + dy);
+                    neighbour.x < 0
+                        || neighbour.y < 0
+                        || neighbour.x >= width
+                        || neighbour.y >= height
+                        || cells.contains(&neighbour)
+                })
+            })
+        })
+        .copied()
+        .collect()
+}
+
+/// Spawns sensors for the water of a level.
+///
+/// Like [`spawn_wall_collision`](crate::walls::spawn_wall_collision),
+/// the water tiles are merged into rectangles, separately for shallow and deep water.
+pub fn spawn_water_sensors(
+    mut commands: Commands,
+    water_query: Query<(&GridCoords, &Parent), Added<Water>>,
+    parent_query: Query<&Parent, Without<Water>>,
+    level_query: Query<(Entity, &LevelIid)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+) {
+    if water_query.is_empty() {
+        return;
+    }
+
+    let level_to_water_locations = cells_by_level(water_query.iter(), &parent_query);
+
+    for (level_entity, level_iid) in &level_query {
+        let Some(level_water) = level_to_water_locations.get(&level_entity) else {
+            continue;
+        };
+
+        let ldtk_project = ldtk_project_assets
+            .get(ldtk_projects.single())
+            .expect("Project should be loaded if level has spawned");
+
+        let level = ldtk_project
+            .as_standalone()
+            .get_loaded_level_by_iid(&level_iid.to_string())
+            .expect("Spawned level should exist in LDtk project");
+
+        let LayerInstance {
+            c_wid: width,
+            c_hei: height,
+            grid_size,
+            ..
+        } = level.layer_instances()[0];
+
+        let deep = deep_cells(level_water, width, height);
+        let shallow: HashSet<GridCoords> = level_water.difference(&deep).copied().collect();
+
+        commands.entity(level_entity).with_children(|level| {
+            for (cells, depth) in [(shallow, WaterDepth::Shallow), (deep, WaterDepth::Deep)] {
+                for rect in merge_into_rects(&cells, width, height) {
+                    let half_extents = rect.half_extents(grid_size);
+                    level.spawn((
+                        Collider::cuboid(half_extents.x, half_extents.y),
+                        Sensor,
+                        ActiveEvents::COLLISION_EVENTS,
+                        CollisionLayer::Water,
+                        WaterZone { depth },
+                        Transform::from_translation(rect.center(grid_size).extend(0.)),
+                        GlobalTransform::default(),
+                    ));
+                }
+            }
+        });
+    }
+}
+
+pub fn detect_water(
+    mut swimmers: Query<&mut Swimmer>,
+    zones: Query<&WaterZone>,
+    mut collisions: EventReader<CollisionEvent>,
+) {
+    for collision in collisions.read() {
+        let (collider_a, collider_b, started) = match collision {
+            CollisionEvent::Started(a, b, _) => (*a, *b, true),
+            CollisionEvent::Stopped(a, b, _) => (*a, *b, false),
+        };
+
+        for (swimmer_entity, zone_entity) in [(collider_a, collider_b), (collider_b, collider_a)] {
+            if let (Ok(mut swimmer), Ok(zone)) =
+                (swimmers.get_mut(swimmer_entity), zones.get(zone_entity))
+            {
+                if started {
+                    swimmer.intersecting_water.insert(zone_entity, zone.depth);
+                } else {
+                    swimmer.intersecting_water.remove(&zone_entity);
+                }
+            }
+        }
+    }
+}
+
+pub fn update_water_state(
+    settings: Res<WaterSettings>,
+    mut query: Query<(&mut Swimmer, Option<&Inventory>, &Transform)>,
+) {
+    for (mut swimmer, inventory, transform) in &mut query {
+        let can_wade_deep = inventory.is_some_and(|inventory| inventory.contains(&settings.deep_water_item));
+
+        let state = match swimmer.deepest() {
+            None => WaterState::Dry,
+            Some(WaterDepth::Shallow) => WaterState::Wading,
+            Some(WaterDepth::Deep) if can_wade_deep => WaterState::Wading,
+            Some(WaterDepth::Deep) => WaterState::Swimming,
+        };
+
+        if state == WaterState::Dry {
+            // not a change other systems need to react to
+            swimmer.bypass_change_detection().last_dry_position =
+                Some(transform.translation.truncate());
+        }
+        if swimmer.state != state {
+            swimmer.state = state;
+        }
+    }
+}
+
+/// Drains stamina while swimming, and pulls exhausted swimmers back to the shore.
+///
+/// Swimmers that never stood on dry land, like ones spawned in the water, drown instead.
+pub fn drain_stamina(
+    time: Res<Time>,
+    settings: Res<WaterSettings>,
+    mut query: Query<(Entity, &mut Stamina, &Swimmer, &mut Transform, Option<&mut Velocity>)>,
+    mut damage_events: EventWriter<Damage>,
+) {
+    for (entity, mut stamina, swimmer, mut transform, velocity) in &mut query {
+        if swimmer.state == WaterState::Swimming {
+            stamina.current = (stamina.current - settings.swim_drain * time.delta_seconds()).max(0.);
+        } else {
+            stamina.current =
+                (stamina.current + settings.stamina_recovery * time.delta_seconds()).min(stamina.max);
+        }
+
+        if stamina.current <= 0. {
+            match swimmer.last_dry_position {
+                Some(shore) => {
+                    event!(Level::INFO, "Swimmer exhausted, returning to shore");
+                    transform.translation.x = shore.x;
+                    transform.translation.y = shore.y;
+                    if let Some(mut velocity) = velocity {
+                        velocity.linvel = Vec2::ZERO;
+                    }
+                }
+                None => {
+                    damage_events.send(Damage {
+                        target: entity,
+                        amount: settings.drowning_damage * time.delta_seconds(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Tints swimmers bluer the deeper they are in the water.
+///
+/// The tint multiplies the sprite's own color, which is restored on dry land.
+/// Alpha is left alone, it belongs to terrain hiding and the weather.
+pub fn tint_swimmers(mut query: Query<(&mut Swimmer, &mut Sprite), Changed<Swimmer>>) {
+    for (mut swimmer, mut sprite) in &mut query {
+        let alpha = sprite.color.alpha();
+        // remembering the dry color isn't a change other systems need to react to
+        let swimmer = swimmer.bypass_change_detection();
+        let tint = match swimmer.state {
+            WaterState::Dry => {
+                if let Some(dry_color) = swimmer.dry_color.take() {
+                    sprite.color = dry_color.with_alpha(alpha);
+                }
+                continue;
+            }
+            WaterState::Wading => Srgba::rgb(0.85, 0.9, 1.0),
+            WaterState::Swimming => Srgba::rgb(0.6, 0.75, 1.0),
+        };
+        let dry = swimmer.dry_color.get_or_insert(sprite.color).to_srgba();
+        sprite.color = Color::srgba(dry.red * tint.red, dry.green * tint.green, dry.blue * tint.blue, alpha);
+    }
+}
+
+/// Plugin which spawns water sensors on int cell value 4 (water)
+/// and handles wading and swimming through them.
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaterSettings>()
+            .add_systems(
+                Update,
+                (
+                    spawn_water_sensors,
+                    detect_water,
+                    update_water_state,
+                    drain_stamina,
+                    tint_swimmers,
+                )
+                    .chain(),
+            )
+            .register_ldtk_int_cell::<WaterBundle>(4);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{health::Health, test_support::TestApp};
+
+    #[test]
+    fn only_interior_water_is_deep() {
+        // a 5 wide river running the full height of a 7x4 level
+        let cells: HashSet<GridCoords> = (1..6)
+            .flat_map(|x| (0..4).map(move |y| GridCoords::new(x, y)))
+            .collect();
+
+        let deep = deep_cells(&cells, 7, 4);
+        let expected: HashSet<GridCoords> = (2..5)
+            .flat_map(|x| (0..4).map(move |y| GridCoords::new(x, y)))
+            .collect();
+        assert_eq!(deep, expected);
+    }
+
+    #[test]
+    fn swimming_drains_stamina_until_the_swimmer_drowns() {
+        let mut app = TestApp::new();
+        app.load_level();
+        let swimmer = app
+            .world()
+            .spawn((
+                Swimmer {
+                    intersecting_water: HashMap::from_iter([(Entity::PLACEHOLDER, WaterDepth::Deep)]),
+                    ..default()
+                },
+                Stamina::default(),
+                Health::default(),
+                Sprite::default(),
+                Transform::default(),
+            ))
+            .id();
+
+        app.advance(60);
+        let entity = app.world().entity(swimmer);
+        assert_eq!(entity.get::<Swimmer>().unwrap().state, WaterState::Swimming);
+        assert_ne!(entity.get::<Sprite>().unwrap().color, Color::WHITE);
+        let stamina = entity.get::<Stamina>().unwrap().current;
+        assert!((stamina - 85.).abs() < 1., "stamina {stamina}");
+
+        // spawned in the water, so there is no shore to return to
+        app.advance(420);
+        let entity = app.world().entity(swimmer);
+        assert_eq!(entity.get::<Stamina>().unwrap().current, 0.);
+        assert!(entity.get::<Health>().unwrap().current < 100.);
+
+        app.world().get_mut::<Swimmer>(swimmer).unwrap().intersecting_water.clear();
+        app.advance(1);
+        let entity = app.world().entity(swimmer);
+        assert_eq!(entity.get::<Swimmer>().unwrap().state, WaterState::Dry);
+        assert_eq!(entity.get::<Sprite>().unwrap().color, Color::WHITE);
+    }
+}
@@ -0,0 +1,266 @@
+use tracing::{event, Level};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{
+    camera::CameraController,
+    character::Character,
+    enemy::Enemy,
+    game_flow::update_level_selection,
+    particles::{ParticleEffect, ParticleEmitter},
+    player::Player,
+};
+
+/// Weather of the current level, set by the `weather` enum field of the LDtk level.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Resource)]
+pub enum Weather {
+    #[default]
+    Clear,
+    DustStorm,
+    Rain,
+    HeatShimmer,
+}
+
+impl Weather {
+    /// Reads the `weather` field of an LDtk level, clear if unset.
+    pub fn from_level(level: &bevy_ecs_ldtk::ldtk::Level) -> Self {
+        match level.get_enum_field("weather").map(String::as_str) {
+            Ok("Clear") | Err(_) => Weather::Clear,
+            Ok("Dust_storm") => Weather::DustStorm,
+            Ok("Rain") => Weather::Rain,
+            Ok("Heat_shimmer") => Weather::HeatShimmer,
+            Ok(other) => {
+                event!(Level::WARN, "Unknown weather {} on level {}", other, level.identifier);
+                Weather::Clear
+            }
+        }
+    }
+}
+
+/// Gameplay effects of each [`Weather`].
+#[derive(Clone, PartialEq, Debug, Resource)]
+pub struct WeatherSettings {
+    /// Distance from the player past which NPCs and mobs can't be seen in a dust storm
+    pub dust_storm_visibility: f32,
+    pub rain_visibility: f32,
+    pub dust_storm_speed_multiplier: f32,
+    pub rain_speed_multiplier: f32,
+    pub heat_shimmer_speed_multiplier: f32,
+    /// Distance over which entities fade out at the edge of visibility
+    pub fade_distance: f32,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        WeatherSettings {
+            dust_storm_visibility: 80.,
+            rain_visibility: 140.,
+            dust_storm_speed_multiplier: 0.7,
+            rain_speed_multiplier: 0.9,
+            heat_shimmer_speed_multiplier: 0.85,
+            fade_distance: 24.,
+        }
+    }
+}
+
+impl WeatherSettings {
+    /// How far the player can see, unlimited if `None`.
+    pub fn visibility_radius(&self, weather: Weather) -> Option<f32> {
+        match weather {
+            Weather::DustStorm => Some(self.dust_storm_visibility),
+            Weather::Rain => Some(self.rain_visibility),
+            Weather::Clear | Weather::HeatShimmer => None,
+        }
+    }
+
+    pub fn speed_multiplier(&self, weather: Weather) -> f32 {
+        match weather {
+            Weather::Clear => 1.,
+            Weather::DustStorm => self.dust_storm_speed_multiplier,
+            Weather::Rain => self.rain_speed_multiplier,
+            Weather::HeatShimmer => self.heat_shimmer_speed_multiplier,
+        }
+    }
+
+    pub fn particles(&self, weather: Weather) -> Option<ParticleEffect> {
+        match weather {
+            Weather::Clear => None,
+            Weather::DustStorm => Some(ParticleEffect::dust_storm()),
+            Weather::Rain => Some(ParticleEffect::rain()),
+            Weather::HeatShimmer => Some(ParticleEffect::heat_shimmer()),
+        }
+    }
+
+    /// Opacity of something `distance` away from the player.
+    pub fn sight(&self, weather: Weather, distance: f32) -> f32 {
+        match self.visibility_radius(weather) {
+            Some(radius) => ((radius - distance) / self.fade_distance + 1.).clamp(0., 1.),
+            None => 1.,
+        }
+    }
+}
+
+/// Particle emitter following the camera for the current weather.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct WeatherEmitter;
+
+/// Changes the weather whenever the selected level's `weather` field differs from the current one.
+pub fn apply_level_weather(
+    level_selection: Res<LevelSelection>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut weather: ResMut<Weather>,
+) {
+    let Some(ldtk_project) = ldtk_projects
+        .get_single()
+        .ok()
+        .and_then(|handle| ldtk_project_assets.get(handle))
+    else {
+        return;
+    };
+
+    let Some(level) = ldtk_project
+        .iter_raw_levels()
+        .find(|level| level_selection.is_match(&LevelIndices::default(), level))
+    else {
+        return;
+    };
+
+    let new_weather = Weather::from_level(level);
+    if *weather != new_weather {
+        event!(Level::INFO, "Level {} has {:?} weather", level.identifier, new_weather);
+        *weather = new_weather;
+    }
+}
+
+/// Replaces the camera's weather particles when the weather changes.
+pub fn update_weather_emitter(
+    mut commands: Commands,
+    weather: Res<Weather>,
+    settings: Res<WeatherSettings>,
+    camera_query: Query<Entity, With<CameraController>>,
+    emitter_query: Query<Entity, With<WeatherEmitter>>,
+) {
+    if !weather.is_changed() {
+        return;
+    }
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+
+    for emitter in &emitter_query {
+        commands.entity(emitter).despawn_recursive();
+    }
+    if let Some(effect) = settings.particles(*weather) {
+        commands.entity(camera).with_children(|parent| {
+            parent.spawn((
+                ParticleEmitter::new(effect),
+                SpatialBundle::default(),
+                WeatherEmitter,
+            ));
+        });
+    }
+}
+
+/// Opacity a sprite had before [`fade_out_of_sight`] scaled it, like the half transparency of
+/// entities hiding in terrain.
+#[derive(Copy, Clone, PartialEq, Debug, Component)]
+pub struct SightFade {
+    pub base_alpha: f32,
+    /// Alpha last written to the sprite, any other value means another system changed it
+    pub applied: f32,
+}
+
+/// Fades out NPCs and mobs beyond the weather's visibility radius.
+pub fn fade_out_of_sight(
+    mut commands: Commands,
+    weather: Res<Weather>,
+    settings: Res<WeatherSettings>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    mut sprite_query: Query<
+        (Entity, &GlobalTransform, &mut Sprite, Option<&mut SightFade>),
+        (Or<(With<Character>, With<Enemy>)>, Without<Player>),
+    >,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_position = player_transform.translation().truncate();
+
+    for (entity, transform, mut sprite, fade) in &mut sprite_query {
+        let current = sprite.color.alpha();
+        let base_alpha = match &fade {
+            Some(fade) if fade.applied == current => fade.base_alpha,
+            _ => current,
+        };
+        let distance = transform.translation().truncate().distance(player_position);
+        let alpha = base_alpha * settings.sight(*weather, distance);
+        if current != alpha {
+            sprite.color.set_alpha(alpha);
+        }
+
+        let new_fade = SightFade { base_alpha, applied: alpha };
+        match fade {
+            Some(mut fade) => {
+                if *fade != new_fade {
+                    *fade = new_fade;
+                }
+            }
+            None => {
+                commands.entity(entity).insert(new_fade);
+            }
+        }
+    }
+}
+
+/// Plugin for per-level weather, its particles and its effect on visibility.
+///
+/// Movement is slowed by [`WeatherSettings::speed_multiplier`] in the player and mob systems.
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Weather>()
+            .init_resource::<WeatherSettings>()
+            .add_systems(
+                Update,
+                (apply_level_weather, update_weather_emitter, fade_out_of_sight)
+                    .chain()
+                    .after(update_level_selection),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestApp;
+
+    #[test]
+    fn dust_storms_hide_distant_entities() {
+        let settings = WeatherSettings::default();
+        assert_eq!(settings.sight(Weather::DustStorm, 10.), 1.);
+        assert_eq!(settings.sight(Weather::DustStorm, 200.), 0.);
+        assert_eq!(settings.sight(Weather::Clear, 200.), 1.);
+        assert!(settings.speed_multiplier(Weather::DustStorm) < 1.);
+    }
+
+    #[test]
+    fn level_weather_spawns_its_emitter() {
+        let mut app = TestApp::new();
+        app.add_plugins(WeatherPlugin);
+        let camera = app
+            .world()
+            .spawn((CameraController::default(), SpatialBundle::default()))
+            .id();
+        app.load_level();
+
+        assert!(app.run_until(10, |world| *world.resource::<Weather>() == Weather::Rain));
+        app.advance(1);
+        let world = app.world();
+        let emitter = world.query_filtered::<Entity, With<WeatherEmitter>>().single(world);
+        assert_eq!(world.get::<Parent>(emitter).map(Parent::get), Some(camera));
+        assert!(world.get::<ParticleEmitter>(emitter).is_some());
+    }
+}